@@ -0,0 +1,79 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use usi::{EngineCommand, EngineCommandReader, EngineCommandRef, ParseMode};
+
+const PV: &str = "7g7f 3c3d 2g2f 8c8d 2f2e 8d8e 6i7h 4a3b 2e2d 2c2d 2h2d 8e8f 8g8f 8b8f 2d3d 2b8h+";
+
+/// Builds an engine log resembling a few seconds of search output.
+fn engine_log() -> String {
+    let mut log = String::new();
+    log.push_str("id name Lesserkai\nid author Program Writer\n");
+    log.push_str("option name USI_Hash type spin default 256 min 1 max 33554432\n");
+    log.push_str("option name Style type combo default Normal var Solid var Normal var Risky\n");
+    log.push_str("usiok\nreadyok\n");
+    for depth in 1..=200 {
+        log.push_str(&format!(
+            "info depth {} seldepth {} time {} nodes {} nps 1234567 hashfull 104 \
+             score cp {} multipv 1 pv {}\n",
+            depth,
+            depth + 4,
+            depth * 13,
+            depth * 11_000,
+            depth % 37 - 18,
+            PV
+        ));
+        log.push_str("info currmove 7g7f\n");
+        log.push_str("info string 7g7f (70%)\n");
+    }
+    log.push_str("bestmove 7g7f ponder 3c3d\n");
+    log
+}
+
+fn parse(c: &mut Criterion) {
+    let log = engine_log();
+    let lines = log.lines().collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(log.len() as u64));
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            for line in &lines {
+                black_box(EngineCommand::parse_with(line, ParseMode::Lenient).unwrap());
+            }
+        })
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            for line in &lines {
+                black_box(EngineCommandRef::parse_with(line, ParseMode::Lenient).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+fn reader(c: &mut Criterion) {
+    let log = engine_log();
+
+    let mut group = c.benchmark_group("reader");
+    group.throughput(Throughput::Bytes(log.len() as u64));
+    group.bench_function("next_command", |b| {
+        b.iter(|| {
+            let mut reader = EngineCommandReader::new(log.as_bytes());
+            while reader.next_command().unwrap().response().is_some() {}
+        })
+    });
+    group.bench_function("next_command_ref", |b| {
+        b.iter(|| {
+            let mut reader = EngineCommandReader::new(log.as_bytes());
+            while let Some(output) = reader.next_command_ref().unwrap() {
+                black_box(output.response());
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse, reader);
+criterion_main!(benches);
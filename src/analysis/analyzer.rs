@@ -0,0 +1,724 @@
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+use crate::game::{EnginePlayer, EngineSpec, GameRecord};
+use crate::kifu::TextEncoding;
+use crate::protocol::{
+    BestMoveParams, EngineCommand, GuiCommand, InfoParams, ScoreKind, ThinkParams,
+};
+use crate::shogi::Position;
+
+/// Represents when to stop searching each position.
+///
+/// The engine searches with `go infinite` and is stopped once the limit is reached,
+/// so that engines without support for fixed-depth searches can be used as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AnalysisLimit {
+    /// Stops when the engine reports a PV at the depth.
+    Depth(i32),
+    /// Stops when the engine reports that it searched the number of nodes.
+    Nodes(i32),
+    /// Stops after the time elapsed.
+    Time(Duration),
+}
+
+/// Represents formats of analysis output files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AnalysisFormat {
+    /// A JSON object per line.
+    Jsonl,
+    /// Comma-separated values with a header line.
+    Csv,
+}
+
+impl AnalysisFormat {
+    /// Guesses the format from the extension of the path. Returns `None` if unknown.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<AnalysisFormat> {
+        match path.as_ref().extension()?.to_str()? {
+            "jsonl" | "json" => Some(AnalysisFormat::Jsonl),
+            "csv" => Some(AnalysisFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Represents settings of `Analyzer`.
+#[derive(Clone, Debug)]
+pub struct AnalysisConfig {
+    limit: AnalysisLimit,
+    concurrency: usize,
+    ready_timeout: Duration,
+    max_time: Duration,
+    output: Option<(PathBuf, AnalysisFormat)>,
+}
+
+impl AnalysisConfig {
+    pub fn new() -> Self {
+        AnalysisConfig::default()
+    }
+
+    #[must_use]
+    pub fn limit(mut self, limit: AnalysisLimit) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Sets the number of engine processes analyzing positions at the same time.
+    #[must_use]
+    pub fn concurrency(mut self, n: usize) -> Self {
+        self.concurrency = n.max(1);
+        self
+    }
+
+    /// Sets how long to wait for `readyok`, and for `bestmove` after `stop`.
+    #[must_use]
+    pub fn ready_timeout(mut self, t: Duration) -> Self {
+        self.ready_timeout = t;
+        self
+    }
+
+    /// Sets the longest time to search a position with depth or nodes limits, after which
+    /// the engine is stopped even if it has not reached the limit.
+    #[must_use]
+    pub fn max_time(mut self, t: Duration) -> Self {
+        self.max_time = t;
+        self
+    }
+
+    /// Sets a file to append results to. Positions already written in the file are skipped,
+    /// so that an interrupted analysis can be resumed.
+    #[must_use]
+    pub fn output<P: AsRef<Path>>(mut self, path: P, format: AnalysisFormat) -> Self {
+        self.output = Some((path.as_ref().to_path_buf(), format));
+        self
+    }
+}
+
+impl Default for AnalysisConfig {
+    fn default() -> Self {
+        AnalysisConfig {
+            limit: AnalysisLimit::Time(Duration::from_secs(1)),
+            concurrency: 1,
+            ready_timeout: Duration::from_secs(30),
+            max_time: Duration::from_secs(600),
+            output: None,
+        }
+    }
+}
+
+/// Represents the final result of a search on a position.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AnalysisResult {
+    /// Index of the position in the input.
+    pub index: usize,
+    /// The position as given, in SFEN optionally followed by `moves`.
+    pub sfen: String,
+    pub best_move: BestMoveParams,
+    /// The last score of the principal variation, from the point of view of the side to move.
+    pub score: Option<(i32, ScoreKind)>,
+    pub depth: Option<i32>,
+    pub nodes: Option<i32>,
+    pub pv: Vec<String>,
+    /// Time until `bestmove` was received.
+    pub elapsed: Duration,
+}
+
+impl AnalysisResult {
+    /// The header line of CSV output.
+    pub const CSV_HEADER: &'static str =
+        "index,sfen,bestmove,ponder,score_type,score,depth,nodes,time_ms,pv";
+
+    /// Returns the result as a line of JSON without the line terminator.
+    pub fn to_json(&self) -> String {
+        let (best_move, ponder) = self.best_move_fields();
+        let (score_type, score) = self.score_fields();
+        let number = |v: Option<i32>| v.map_or("null".to_string(), |v| v.to_string());
+        let string = |v: Option<&str>| v.map_or("null".to_string(), json_string);
+
+        format!(
+            "{{\"index\":{},\"sfen\":{},\"bestmove\":{},\"ponder\":{},\"score_type\":{},\
+             \"score\":{},\"depth\":{},\"nodes\":{},\"time_ms\":{},\"pv\":[{}]}}",
+            self.index,
+            json_string(&self.sfen),
+            json_string(best_move),
+            string(ponder),
+            string(score_type),
+            number(score),
+            number(self.depth),
+            number(self.nodes),
+            self.elapsed.as_millis(),
+            self.pv
+                .iter()
+                .map(|m| json_string(m))
+                .collect::<Vec<_>>()
+                .join(","),
+        )
+    }
+
+    /// Returns the result as a line of CSV without the line terminator.
+    /// The PV is written as moves separated by spaces.
+    pub fn to_csv(&self) -> String {
+        let (best_move, ponder) = self.best_move_fields();
+        let (score_type, score) = self.score_fields();
+        let number = |v: Option<i32>| v.map_or(String::new(), |v| v.to_string());
+
+        format!(
+            "{},{},{},{},{},{},{},{},{},{}",
+            self.index,
+            self.sfen,
+            best_move,
+            ponder.unwrap_or_default(),
+            score_type.unwrap_or_default(),
+            number(score),
+            number(self.depth),
+            number(self.nodes),
+            self.elapsed.as_millis(),
+            self.pv.join(" "),
+        )
+    }
+
+    fn best_move_fields(&self) -> (&str, Option<&str>) {
+        match self.best_move {
+            BestMoveParams::MakeMove(ref m, ref ponder) => (m, ponder.as_deref()),
+            BestMoveParams::Resign => ("resign", None),
+            BestMoveParams::Win => ("win", None),
+        }
+    }
+
+    /// Returns `cp` or `mate` and the value. Bounds of the score are not distinguished.
+    fn score_fields(&self) -> (Option<&'static str>, Option<i32>) {
+        match self.score {
+            Some((v, ScoreKind::CpExact | ScoreKind::CpLowerbound | ScoreKind::CpUpperbound)) => {
+                (Some("cp"), Some(v))
+            }
+            Some((v, _)) => (Some("mate"), Some(v)),
+            None => (None, None),
+        }
+    }
+}
+
+pub(super) fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// `Analyzer` searches many positions with one or more processes of an engine.
+///
+/// # Examples
+/// ```no_run
+/// use std::ops::ControlFlow;
+/// use usi::{AnalysisConfig, AnalysisFormat, AnalysisLimit, Analyzer, EngineSpec};
+///
+/// let engine = EngineSpec::new("engine", "/path/to/engine").option("USI_Hash", "256");
+/// let config = AnalysisConfig::new()
+///     .limit(AnalysisLimit::Depth(10))
+///     .concurrency(4)
+///     .output("analysis.jsonl", AnalysisFormat::Jsonl);
+/// let positions = usi::load_positions("positions.sfen").unwrap();
+///
+/// Analyzer::new(engine, config)
+///     .run(&positions, |result| {
+///         println!("#{}: {:?} {:?}", result.index, result.best_move, result.score);
+///         ControlFlow::Continue(())
+///     })
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Analyzer {
+    engine: EngineSpec,
+    config: AnalysisConfig,
+}
+
+impl Analyzer {
+    pub fn new(engine: EngineSpec, config: AnalysisConfig) -> Analyzer {
+        Analyzer { engine, config }
+    }
+
+    /// Analyzes the positions, and calls `on_result` for each position in the order of completion.
+    /// Positions already written in the output file are skipped.
+    /// Positions without legal moves are not searched, and reported as `BestMoveParams::Resign`.
+    ///
+    /// If `on_result` returns `ControlFlow::Break`, no more positions are sent to engines.
+    /// Searches in progress are finished and written to the output without calling `on_result`.
+    pub fn run<F>(&self, positions: &[String], mut on_result: F) -> Result<(), Error>
+    where
+        F: FnMut(&AnalysisResult) -> ControlFlow<()>,
+    {
+        let (mut output, finished) = match self.config.output {
+            Some((ref path, format)) => {
+                let (file, finished) = open_output(path, format)?;
+                (Some((file, format)), finished)
+            }
+            None => (None, HashSet::new()),
+        };
+        let pending = positions
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !finished.contains(i))
+            .collect::<Vec<_>>();
+
+        let next = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|s| -> Result<(), Error> {
+            for _ in 0..self.config.concurrency.min(pending.len()) {
+                let sender = sender.clone();
+                let (next, stop, pending) = (&next, &stop, &pending);
+                s.spawn(move || {
+                    let mut player = match self.spawn() {
+                        Ok(player) => player,
+                        Err(e) => {
+                            let _ = sender.send(Err(e));
+                            return;
+                        }
+                    };
+                    while !stop.load(Ordering::SeqCst) {
+                        let (index, sfen) = match pending.get(next.fetch_add(1, Ordering::SeqCst)) {
+                            Some(p) => *p,
+                            None => break,
+                        };
+                        let result = self.analyze(&mut player, index, sfen);
+                        let failed = result.is_err();
+                        if sender.send(result).is_err() || failed {
+                            break;
+                        }
+                    }
+                    let _ = player.send_command(&GuiCommand::Quit);
+                });
+            }
+            drop(sender);
+
+            for result in receiver {
+                let result = match result {
+                    Ok(result) => result,
+                    Err(e) => {
+                        stop.store(true, Ordering::SeqCst);
+                        return Err(e);
+                    }
+                };
+                if let Some((ref mut file, format)) = output {
+                    let line = match format {
+                        AnalysisFormat::Jsonl => result.to_json(),
+                        AnalysisFormat::Csv => result.to_csv(),
+                    };
+                    writeln!(file, "{line}")?;
+                    file.flush()?;
+                }
+                if !stop.load(Ordering::SeqCst) && on_result(&result).is_break() {
+                    stop.store(true, Ordering::SeqCst);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn spawn(&self) -> Result<EnginePlayer, Error> {
+        let mut player = self.engine.spawn()?;
+        player.prepare(self.config.ready_timeout)?;
+        player.send_command(&GuiCommand::UsiNewGame)?;
+        Ok(player)
+    }
+
+    /// Searches a position until the limit is reached, and waits for `bestmove`.
+    fn analyze(
+        &self,
+        player: &mut EnginePlayer,
+        index: usize,
+        sfen: &str,
+    ) -> Result<AnalysisResult, Error> {
+        let mut result = AnalysisResult {
+            index,
+            sfen: sfen.to_string(),
+            best_move: BestMoveParams::Resign,
+            score: None,
+            depth: None,
+            nodes: None,
+            pv: Vec::new(),
+            elapsed: Duration::ZERO,
+        };
+        // Engines may wait for `stop` forever on checkmated positions.
+        if Position::from_sfen(sfen)?.legal_moves().is_empty() {
+            return Ok(result);
+        }
+
+        let (best_move, elapsed) = search(
+            player,
+            GuiCommand::Position(sfen.to_string()),
+            self.config.limit,
+            self.config.max_time,
+            self.config.ready_timeout,
+            |params| update(&mut result, params),
+        )?;
+        result.best_move = best_move;
+        result.elapsed = elapsed;
+        Ok(result)
+    }
+}
+
+/// Returns the index of the PV an `info` command is about, which is 1 unless in MultiPV mode.
+pub(super) fn multipv(params: &[InfoParams]) -> usize {
+    params
+        .iter()
+        .find_map(|p| match p {
+            InfoParams::MultiPv(n) => Some((*n).max(1) as usize),
+            _ => None,
+        })
+        .unwrap_or(1)
+}
+
+/// Searches the position with `go infinite` until the limit is reached, or `max_time` passed
+/// for depth and nodes limits, calling `on_info` for each `info` command.
+/// Returns `bestmove` and the time until it was received.
+pub(super) fn search<F>(
+    player: &mut EnginePlayer,
+    position: GuiCommand,
+    limit: AnalysisLimit,
+    max_time: Duration,
+    ready_timeout: Duration,
+    mut on_info: F,
+) -> Result<(BestMoveParams, Duration), Error>
+where
+    F: FnMut(&[InfoParams]),
+{
+    player.send_command(&position)?;
+    player.send_command(&GuiCommand::Go(ThinkParams::new().infinite()))?;
+
+    let start = Instant::now();
+    let mut deadline = match limit {
+        AnalysisLimit::Time(t) => start + t,
+        _ => start + max_time,
+    };
+    let mut stopped = false;
+
+    loop {
+        let output = player.recv_deadline(deadline)?;
+        let reached = match output {
+            Some(ref output) => match output.response() {
+                Some(EngineCommand::Info(params)) => {
+                    on_info(params);
+                    limit_reached(params, limit)
+                }
+                Some(EngineCommand::BestMove(best_move)) => {
+                    let elapsed = output.timestamp().saturating_duration_since(start);
+                    return Ok((best_move.clone(), elapsed));
+                }
+                _ => false,
+            },
+            None if stopped => return Err(Error::Timeout),
+            None => true,
+        };
+
+        if reached && !stopped {
+            player.send_command(&GuiCommand::Stop)?;
+            stopped = true;
+            deadline = Instant::now() + ready_timeout;
+        }
+    }
+}
+
+/// Returns whether an `info` command shows that the search reached the depth or nodes limit.
+/// Only the best PV counts for the depth in MultiPV mode.
+fn limit_reached(params: &[InfoParams], limit: AnalysisLimit) -> bool {
+    match limit {
+        AnalysisLimit::Depth(d) => {
+            multipv(params) == 1
+                && params.iter().any(|p| matches!(p, InfoParams::Pv(_)))
+                && params
+                    .iter()
+                    .any(|p| matches!(p, InfoParams::Depth(v, _) if *v >= d))
+        }
+        AnalysisLimit::Nodes(n) => params
+            .iter()
+            .any(|p| matches!(p, InfoParams::Nodes(v) if *v >= n)),
+        AnalysisLimit::Time(_) => false,
+    }
+}
+
+/// Applies an `info` command to the result.
+/// Lines about other than the best PV in MultiPV mode are ignored.
+fn update(result: &mut AnalysisResult, params: &[InfoParams]) {
+    if multipv(params) > 1 {
+        return;
+    }
+
+    for p in params {
+        match p {
+            InfoParams::Depth(d, _) => result.depth = Some(*d),
+            InfoParams::Nodes(n) => result.nodes = Some(*n),
+            InfoParams::Score(v, kind) => result.score = Some((*v, kind.clone())),
+            InfoParams::Pv(pv) => result.pv = pv.clone(),
+            _ => {}
+        }
+    }
+}
+
+/// Opens the output file for appending, and returns indices of positions already written.
+fn open_output(path: &Path, format: AnalysisFormat) -> Result<(File, HashSet<usize>), Error> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let mut s = String::new();
+    file.read_to_string(&mut s)?;
+
+    // A line cut off by the interruption is removed, so that the position is analyzed again.
+    let complete = s.rfind('\n').map_or(0, |i| i + 1);
+    if complete < s.len() {
+        file.set_len(complete as u64)?;
+        s.truncate(complete);
+    }
+
+    let finished = s
+        .lines()
+        .filter_map(|line| match format {
+            AnalysisFormat::Jsonl => {
+                let rest = line.strip_prefix("{\"index\":")?;
+                if !rest.ends_with("]}") {
+                    return None;
+                }
+                rest[..rest.find(',')?].parse().ok()
+            }
+            AnalysisFormat::Csv => {
+                let fields = line.split(',').collect::<Vec<_>>();
+                if fields.len() != AnalysisResult::CSV_HEADER.split(',').count() {
+                    return None;
+                }
+                fields[0].parse().ok()
+            }
+        })
+        .collect();
+
+    if s.is_empty() && format == AnalysisFormat::Csv {
+        writeln!(file, "{}", AnalysisResult::CSV_HEADER)?;
+    }
+    Ok((file, finished))
+}
+
+/// Reads positions to analyze from a file.
+///
+/// Game records in KIF, KI2 and CSA (and JKF with the `serde` feature) are recognized by
+/// their extensions, and yield every position in the game. Other files contain a SFEN per line,
+/// optionally followed by `moves`, where empty lines and lines starting with `#` are ignored.
+pub fn load_positions<P: AsRef<Path>>(path: P) -> Result<Vec<String>, Error> {
+    let path = path.as_ref();
+    let bytes = fs::read(path)?;
+    let s = TextEncoding::detect(&bytes).decode(&bytes);
+
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let record = match extension {
+        "kif" | "kifu" => Some(GameRecord::from_kif(&s)?),
+        "ki2" | "ki2u" => Some(GameRecord::from_ki2(&s)?),
+        "csa" => Some(GameRecord::from_csa(&s)?),
+        #[cfg(feature = "serde")]
+        "jkf" => Some(GameRecord::from_jkf(&s)?),
+        _ => None,
+    };
+    if let Some(record) = record {
+        let mut pos = Position::from_sfen(&record.start_sfen)?;
+        let mut positions = vec![pos.to_sfen()];
+        for m in &record.moves {
+            pos.make_move(&m.mv)?;
+            positions.push(pos.to_sfen());
+        }
+        return Ok(positions);
+    }
+
+    s.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| {
+            let sfen = l.strip_prefix("position ").unwrap_or(l);
+            let sfen = sfen.strip_prefix("sfen ").unwrap_or(sfen);
+            Position::from_sfen(sfen)?;
+            Ok(sfen.to_string())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::process::testing::{fake_engine, script_engine, temp_dir};
+    use crate::shogi::STARTPOS_SFEN;
+
+    #[cfg(unix)]
+    fn positions() -> Vec<String> {
+        vec![
+            STARTPOS_SFEN.to_string(),
+            format!("{STARTPOS_SFEN} moves 7g7f"),
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2".to_string(),
+        ]
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_and_resume() {
+        let engine = EngineSpec::new("pawn", fake_engine("pawn", &["7g7f"]));
+        let output = temp_dir().join("out.jsonl");
+        let config = AnalysisConfig::new()
+            .limit(AnalysisLimit::Depth(1))
+            .concurrency(2)
+            .output(&output, AnalysisFormat::Jsonl);
+
+        let mut results = Vec::new();
+        Analyzer::new(engine.clone(), config.clone())
+            .run(&positions()[..2], |r| {
+                results.push(r.clone());
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        results.sort_by_key(|r| r.index);
+        assert_eq!(
+            vec![
+                BestMoveParams::MakeMove("7g7f".to_string(), None),
+                BestMoveParams::MakeMove("7g7f".to_string(), None),
+            ],
+            results
+                .iter()
+                .map(|r| r.best_move.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some((0, ScoreKind::CpExact)), results[0].score);
+        assert_eq!(Some(1), results[0].depth);
+
+        // A line cut off by an interruption is discarded.
+        let mut file = OpenOptions::new().append(true).open(&output).unwrap();
+        write!(file, "{{\"index\":2,\"sfen\":\"lnsgk").unwrap();
+        drop(file);
+
+        // Only the new position is analyzed.
+        let mut indices = Vec::new();
+        Analyzer::new(engine, config)
+            .run(&positions(), |r| {
+                indices.push(r.index);
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(vec![2], indices);
+
+        let lines = fs::read_to_string(&output).unwrap();
+        assert_eq!(3, lines.lines().count());
+        assert!(lines.contains(&format!(
+            "{{\"index\":2,\"sfen\":\"{}\",\"bestmove\":\"7g7f\",\"ponder\":null,\
+             \"score_type\":\"cp\",\"score\":0,\"depth\":1,\"nodes\":null,",
+            positions()[2]
+        )));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn limits() {
+        // Reports a PV at depth 1 and waits for `stop`.
+        let engine = EngineSpec::new(
+            "waiter",
+            script_engine(
+                "waiter",
+                "echo 'info depth 1 nodes 100 score cp 10 pv 7g7f'; \
+                 echo 'info depth 2 multipv 2 score cp -50 pv 2g2f'; \
+                 read -r s; echo 'info depth 2 nodes 300 score mate 3 pv 2g2f 3c3d'; \
+                 echo 'bestmove 2g2f ponder 3c3d'",
+            ),
+        );
+
+        for limit in [
+            AnalysisLimit::Time(Duration::from_millis(100)),
+            AnalysisLimit::Depth(1),
+            AnalysisLimit::Nodes(100),
+            // Never reached, so the search is stopped after `max_time`.
+            AnalysisLimit::Depth(10),
+            AnalysisLimit::Nodes(1000),
+        ] {
+            let output = temp_dir().join("out.csv");
+            let config = AnalysisConfig::new()
+                .limit(limit)
+                .max_time(Duration::from_millis(100))
+                .output(&output, AnalysisFormat::Csv);
+            Analyzer::new(engine.clone(), config)
+                .run(&positions()[..1], |_| ControlFlow::Continue(()))
+                .unwrap();
+
+            let lines = fs::read_to_string(&output).unwrap();
+            let lines = lines.lines().collect::<Vec<_>>();
+            assert_eq!(AnalysisResult::CSV_HEADER, lines[0]);
+            assert!(
+                lines[1].starts_with(&format!("0,{STARTPOS_SFEN},2g2f,3c3d,mate,3,2,300,")),
+                "{limit:?}: {}",
+                lines[1]
+            );
+            assert!(lines[1].ends_with(",2g2f 3c3d"), "{}", lines[1]);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn no_legal_moves() {
+        // Would wait for `stop` forever with the depth limit.
+        let engine = EngineSpec::new("waiter", script_engine("waiter", "read -r _"));
+        let sfen = "4k4/4G4/4P4/9/9/9/9/9/4K4 w - 2".to_string();
+
+        let mut results = Vec::new();
+        Analyzer::new(engine, AnalysisConfig::new().limit(AnalysisLimit::Depth(1)))
+            .run(&[sfen], |r| {
+                results.push(r.clone());
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(1, results.len());
+        assert_eq!(BestMoveParams::Resign, results[0].best_move);
+        assert_eq!(None, results[0].score);
+    }
+
+    #[test]
+    fn load() {
+        let dir = std::env::temp_dir().join(format!("usi-rs-load-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("positions.sfen");
+        fs::write(
+            &path,
+            format!(
+                "# comment\n\nsfen {STARTPOS_SFEN}\nposition sfen {STARTPOS_SFEN} moves 7g7f\n"
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                STARTPOS_SFEN.to_string(),
+                format!("{STARTPOS_SFEN} moves 7g7f")
+            ],
+            load_positions(&path).unwrap()
+        );
+
+        fs::write(&path, "9/9/9 b - 1\n").unwrap();
+        assert!(load_positions(&path).is_err());
+
+        let path = dir.join("game.csa");
+        fs::write(&path, "PI\n+\n+7776FU\n-3334FU\n%TORYO\n").unwrap();
+        let positions = load_positions(&path).unwrap();
+        assert_eq!(3, positions.len());
+        assert_eq!(
+            "lnsgkgsnl/1r5b1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL b - 3",
+            positions[2]
+        );
+    }
+}
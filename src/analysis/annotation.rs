@@ -0,0 +1,438 @@
+use std::fmt;
+use std::time::Duration;
+
+use super::analyzer::{json_string, multipv, search};
+use super::AnalysisLimit;
+use crate::error::Error;
+use crate::game::{score_to_cp, EnginePlayer, GameRecord, MoveRecord, MATE_SCORE};
+use crate::protocol::{GuiCommand, InfoParams, ScoreKind};
+use crate::shogi::{Color, Move, Position};
+
+/// Represents how bad a move is, judged by how much the score dropped from the best move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MoveQuality {
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl fmt::Display for MoveQuality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MoveQuality::Inaccuracy => write!(f, "inaccuracy"),
+            MoveQuality::Mistake => write!(f, "mistake"),
+            MoveQuality::Blunder => write!(f, "blunder"),
+        }
+    }
+}
+
+/// Represents settings of `Annotator`.
+#[derive(Clone, Debug)]
+pub struct AnnotationConfig {
+    limit: AnalysisLimit,
+    multipv: usize,
+    thresholds: [i32; 3],
+    ready_timeout: Duration,
+    max_time: Duration,
+}
+
+impl AnnotationConfig {
+    pub fn new() -> Self {
+        AnnotationConfig::default()
+    }
+
+    #[must_use]
+    pub fn limit(mut self, limit: AnalysisLimit) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Sets the number of candidate moves searched in each position, sent as `MultiPV` option.
+    #[must_use]
+    pub fn multipv(mut self, n: usize) -> Self {
+        self.multipv = n.max(1);
+        self
+    }
+
+    /// Sets the score drops in centipawns from which moves are classified as
+    /// inaccuracies, mistakes and blunders.
+    #[must_use]
+    pub fn thresholds(mut self, inaccuracy: i32, mistake: i32, blunder: i32) -> Self {
+        self.thresholds = [inaccuracy, mistake, blunder];
+        self
+    }
+
+    /// Sets how long to wait for `readyok`, and for `bestmove` after `stop`.
+    #[must_use]
+    pub fn ready_timeout(mut self, t: Duration) -> Self {
+        self.ready_timeout = t;
+        self
+    }
+
+    /// Sets the longest time to search a position with depth or nodes limits.
+    #[must_use]
+    pub fn max_time(mut self, t: Duration) -> Self {
+        self.max_time = t;
+        self
+    }
+}
+
+impl Default for AnnotationConfig {
+    fn default() -> Self {
+        AnnotationConfig {
+            limit: AnalysisLimit::Time(Duration::from_secs(1)),
+            multipv: 3,
+            thresholds: [100, 300, 600],
+            ready_timeout: Duration::from_secs(30),
+            max_time: Duration::from_secs(600),
+        }
+    }
+}
+
+/// Represents the engine's judgement on a move played in the game.
+/// Scores are in centipawns from the point of view of the player who moved.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MoveAnnotation {
+    /// The best move suggested by the engine, with its line.
+    pub pv: Vec<Move>,
+    pub best_score: Option<i32>,
+    /// The score of the played move, taken from the candidate moves if included,
+    /// or from the search on the next position otherwise.
+    pub played_score: Option<i32>,
+    pub quality: Option<MoveQuality>,
+}
+
+impl MoveAnnotation {
+    /// Returns how much the played move lost compared to the best move.
+    pub fn score_drop(&self) -> Option<i32> {
+        Some((self.best_score? - self.played_score?).max(0))
+    }
+}
+
+/// Represents the result of `Annotator::annotate`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Annotation {
+    /// The game with engine comments on each move. The best lines are added as variations
+    /// to mistakes and blunders.
+    pub record: GameRecord,
+    /// Annotations of each move in the main line.
+    pub moves: Vec<MoveAnnotation>,
+    /// Scores of each position from the start, in centipawns from the point of view of black,
+    /// to draw an evaluation graph.
+    pub evaluations: Vec<Option<i32>>,
+}
+
+impl Annotation {
+    /// Returns the annotations and the evaluation graph as JSON.
+    pub fn to_json(&self) -> String {
+        let number = |v: Option<i32>| v.map_or("null".to_string(), |v| v.to_string());
+
+        let moves = self
+            .moves
+            .iter()
+            .zip(&self.record.moves)
+            .enumerate()
+            .map(|(i, (a, m))| {
+                format!(
+                    "{{\"ply\":{},\"move\":{},\"best\":{},\"best_score\":{},\
+                     \"played_score\":{},\"drop\":{},\"quality\":{},\"pv\":[{}]}}",
+                    i + 1,
+                    json_string(&m.mv.to_string()),
+                    a.pv.first()
+                        .map_or("null".to_string(), |m| json_string(&m.to_string())),
+                    number(a.best_score),
+                    number(a.played_score),
+                    number(a.score_drop()),
+                    a.quality
+                        .map_or("null".to_string(), |q| json_string(&q.to_string())),
+                    a.pv.iter()
+                        .map(|m| json_string(&m.to_string()))
+                        .collect::<Vec<_>>()
+                        .join(","),
+                )
+            })
+            .collect::<Vec<_>>();
+        let evaluations = self
+            .evaluations
+            .iter()
+            .map(|v| number(*v))
+            .collect::<Vec<_>>();
+
+        format!(
+            "{{\"moves\":[{}],\"evaluations\":[{}]}}",
+            moves.join(","),
+            evaluations.join(",")
+        )
+    }
+}
+
+/// Candidate moves of a position, ordered from the best.
+/// Scores are in centipawns from the point of view of the side to move.
+#[derive(Default)]
+struct Candidates(Vec<(i32, Vec<String>)>);
+
+impl Candidates {
+    fn update(&mut self, params: &[InfoParams]) {
+        let mut score = None;
+        let mut pv = None;
+        for p in params {
+            match p {
+                InfoParams::Score(v, kind) => score = score_to_cp(&(*v, kind.clone())),
+                InfoParams::Pv(moves) => pv = Some(moves),
+                _ => {}
+            }
+        }
+
+        if let (Some(score), Some(pv)) = (score, pv) {
+            let i = multipv(params) - 1;
+            if self.0.len() <= i {
+                self.0.resize(i + 1, (0, Vec::new()));
+            }
+            self.0[i] = (score, pv.clone());
+        }
+    }
+
+    fn best_score(&self) -> Option<i32> {
+        self.0.first().map(|(score, _)| *score)
+    }
+
+    fn score_of(&self, m: &Move) -> Option<i32> {
+        let m = m.to_string();
+        self.0
+            .iter()
+            .find(|(_, pv)| pv.first() == Some(&m))
+            .map(|(score, _)| *score)
+    }
+}
+
+/// `Annotator` reviews a game with an engine, finding moves which lost much of the score.
+///
+/// # Examples
+/// ```no_run
+/// use usi::{AnalysisLimit, AnnotationConfig, Annotator, EngineSpec, GameRecord};
+///
+/// let record = GameRecord::from_kif(&std::fs::read_to_string("game.kifu").unwrap()).unwrap();
+/// let mut engine = EngineSpec::new("engine", "/path/to/engine").spawn().unwrap();
+///
+/// let config = AnnotationConfig::new().limit(AnalysisLimit::Depth(12)).multipv(3);
+/// let annotation = Annotator::new(config).annotate(&mut engine, &record).unwrap();
+/// std::fs::write("annotated.kifu", annotation.record.to_kif()).unwrap();
+/// std::fs::write("annotation.json", annotation.to_json()).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Annotator {
+    config: AnnotationConfig,
+}
+
+impl Annotator {
+    pub fn new(config: AnnotationConfig) -> Annotator {
+        Annotator { config }
+    }
+
+    /// Searches every position in the main line of the game, and annotates each move.
+    pub fn annotate(
+        &self,
+        player: &mut EnginePlayer,
+        record: &GameRecord,
+    ) -> Result<Annotation, Error> {
+        player.set_option("MultiPV", Some(&self.config.multipv.to_string()))?;
+        player.prepare(self.config.ready_timeout)?;
+        player.send_command(&GuiCommand::UsiNewGame)?;
+
+        let mut pos = Position::from_sfen(&record.start_sfen)?;
+        let mut positions = vec![pos.clone()];
+        for m in &record.moves {
+            pos.make_move(&m.mv)?;
+            positions.push(pos.clone());
+        }
+
+        let mut candidates = Vec::with_capacity(positions.len());
+        for (n, pos) in positions.iter().enumerate() {
+            let mut c = Candidates::default();
+            if pos.legal_moves().is_empty() {
+                c.0.push((-MATE_SCORE, Vec::new()));
+            } else {
+                search(
+                    player,
+                    record.position_command(n),
+                    self.config.limit,
+                    self.config.max_time,
+                    self.config.ready_timeout,
+                    |params| c.update(params),
+                )?;
+            }
+            candidates.push(c);
+        }
+
+        let mut annotated = record.clone();
+        let mut moves = Vec::with_capacity(record.moves.len());
+        for (n, m) in annotated.moves.iter_mut().enumerate() {
+            let (before, after) = (&candidates[n], &candidates[n + 1]);
+            let best_score = before.best_score();
+            let played_score = before
+                .score_of(&m.mv)
+                .or_else(|| after.best_score().map(|v| -v));
+            let pv = legal_line(&positions[n], before.0.first().map_or(&[][..], |c| &c.1));
+
+            let mut a = MoveAnnotation {
+                pv,
+                best_score,
+                played_score,
+                quality: None,
+            };
+            a.quality = a.score_drop().and_then(|drop| self.classify(drop));
+            annotate_move(m, &a);
+            moves.push(a);
+        }
+
+        let evaluations = candidates
+            .iter()
+            .zip(&positions)
+            .map(|(c, pos)| {
+                c.best_score().map(|v| match pos.side_to_move() {
+                    Color::Black => v,
+                    Color::White => -v,
+                })
+            })
+            .collect();
+
+        Ok(Annotation {
+            record: annotated,
+            moves,
+            evaluations,
+        })
+    }
+
+    fn classify(&self, drop: i32) -> Option<MoveQuality> {
+        let [inaccuracy, mistake, blunder] = self.config.thresholds;
+        if drop >= blunder {
+            Some(MoveQuality::Blunder)
+        } else if drop >= mistake {
+            Some(MoveQuality::Mistake)
+        } else if drop >= inaccuracy {
+            Some(MoveQuality::Inaccuracy)
+        } else {
+            None
+        }
+    }
+}
+
+/// Parses moves of a PV, stopping at the first illegal move.
+fn legal_line(pos: &Position, pv: &[String]) -> Vec<Move> {
+    let mut pos = pos.clone();
+    pv.iter()
+        .map_while(|m| {
+            let m = m.parse::<Move>().ok()?;
+            pos.make_move(&m).ok()?;
+            Some(m)
+        })
+        .collect()
+}
+
+/// Writes the engine's judgement to the comment, and adds the best line as a variation
+/// if the move was a mistake or worse.
+fn annotate_move(m: &mut MoveRecord, a: &MoveAnnotation) {
+    let score = |v: Option<i32>| v.map_or("-".to_string(), |v| v.to_string());
+    let mut lines = Vec::new();
+    if let Some(quality) = a.quality {
+        lines.push(format!("{quality} (-{})", score(a.score_drop())));
+    }
+    lines.push(format!("played: {}", score(a.played_score)));
+    if !a.pv.is_empty() {
+        let pv = a.pv.iter().map(Move::to_string).collect::<Vec<_>>();
+        lines.push(format!("best: {} ({})", pv.join(" "), score(a.best_score)));
+    }
+
+    let comment = lines.join("\n");
+    m.comment = Some(match m.comment.take() {
+        Some(c) => format!("{c}\n{comment}"),
+        None => comment,
+    });
+    m.score = a.played_score.map(|v| (v, ScoreKind::CpExact));
+
+    if a.quality >= Some(MoveQuality::Mistake) && a.pv.first().is_some_and(|b| *b != m.mv) {
+        m.variations
+            .push(a.pv.iter().map(|mv| MoveRecord::new(*mv)).collect());
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::game::EngineSpec;
+    use crate::process::testing::script_engine;
+    use crate::shogi::STARTPOS_SFEN;
+
+    #[test]
+    fn annotate() {
+        // Suggests 2g2f in every position, and thinks 7g7f loses much.
+        let engine = EngineSpec::new(
+            "reviewer",
+            script_engine(
+                "reviewer",
+                "echo 'info depth 1 multipv 1 score cp 100 pv 2g2f 8c8d'; \
+                 echo 'info depth 1 multipv 2 score cp -500 pv 7g7f'; \
+                 echo 'bestmove 2g2f'",
+            ),
+        );
+        let record = GameRecord {
+            start_sfen: STARTPOS_SFEN.to_string(),
+            moves: ["7g7f", "3c3d", "2g2f"]
+                .iter()
+                .map(|m| MoveRecord::new(m.parse().unwrap()))
+                .collect(),
+            ..Default::default()
+        };
+
+        let mut player = engine.spawn().unwrap();
+        let annotation = Annotator::new(AnnotationConfig::new().limit(AnalysisLimit::Depth(1)))
+            .annotate(&mut player, &record)
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                Some(MoveQuality::Blunder),
+                Some(MoveQuality::Inaccuracy),
+                None
+            ],
+            annotation
+                .moves
+                .iter()
+                .map(|a| a.quality)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some(600), annotation.moves[0].score_drop());
+        // 3c3d is not among the candidates, so the score comes from the next position.
+        assert_eq!(Some(-100), annotation.moves[1].played_score);
+        assert_eq!(
+            vec![Some(100), Some(-100), Some(100), Some(-100)],
+            annotation.evaluations
+        );
+
+        let first = &annotation.record.moves[0];
+        assert_eq!(
+            Some("blunder (-600)\nplayed: -500\nbest: 2g2f 8c8d (100)"),
+            first.comment.as_deref()
+        );
+        assert_eq!(Some((-500, ScoreKind::CpExact)), first.score);
+        assert_eq!(1, first.variations.len());
+        assert_eq!("2g2f", first.variations[0][0].mv.to_string());
+        // An illegal PV for white is dropped.
+        assert!(annotation.moves[1].pv.is_empty());
+        assert!(annotation.record.moves[1].variations.is_empty());
+
+        let json = annotation.to_json();
+        assert!(
+            json.starts_with(
+                "{\"moves\":[{\"ply\":1,\"move\":\"7g7f\",\"best\":\"2g2f\",\"best_score\":100,\
+                 \"played_score\":-500,\"drop\":600,\"quality\":\"blunder\",\
+                 \"pv\":[\"2g2f\",\"8c8d\"]}"
+            ),
+            "{json}"
+        );
+        assert!(
+            json.ends_with("\"evaluations\":[100,-100,100,-100]}"),
+            "{json}"
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::fmt;
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+use crate::game::EnginePlayer;
+use crate::protocol::{CheckmateParams, EngineCommand, GuiCommand, MateParam, ThinkParams};
+use crate::shogi::{Move, Position};
+
+/// Represents the answer of an engine to `go mate`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MateResult {
+    /// Moves from the position to the checkmate, alternating the attacker and the defender.
+    Mate(Vec<Move>),
+    NoMate,
+    Timeout,
+    NotImplemented,
+}
+
+/// Represents why a mate sequence is wrong.
+/// Indices point to moves in the sequence, counted from 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MateViolation {
+    /// The move is illegal.
+    IllegalMove(usize),
+    /// The attacker's move does not give check.
+    NotCheck(usize),
+    /// The defender is not checkmated at the end.
+    NotCheckmate,
+}
+
+impl fmt::Display for MateViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MateViolation::IllegalMove(i) => write!(f, "move #{} is illegal", i + 1),
+            MateViolation::NotCheck(i) => write!(f, "move #{} is not a check", i + 1),
+            MateViolation::NotCheckmate => write!(f, "the sequence does not end in checkmate"),
+        }
+    }
+}
+
+/// Verifies that every attacker's move in the sequence gives check, every move is legal,
+/// and the defender is checkmated after the last move.
+///
+/// # Examples
+///
+/// ```
+/// use usi::{verify_mate, MateViolation, Position};
+///
+/// let pos = Position::from_sfen("4k4/9/4P4/9/9/9/9/9/4K4 b G 1").unwrap();
+/// assert_eq!(Ok(()), verify_mate(&pos, &["G*5b".parse().unwrap()]));
+/// assert_eq!(
+///     Err(MateViolation::NotCheckmate),
+///     verify_mate(&pos, &["G*4b".parse().unwrap()])
+/// );
+/// ```
+pub fn verify_mate(pos: &Position, moves: &[Move]) -> Result<(), MateViolation> {
+    let mut pos = pos.clone();
+    for (i, m) in moves.iter().enumerate() {
+        if !pos.is_legal(m) {
+            return Err(MateViolation::IllegalMove(i));
+        }
+        if i % 2 == 0 && !pos.gives_check(m) {
+            return Err(MateViolation::NotCheck(i));
+        }
+        pos.make_move(m)
+            .expect("the move must be legal after the check above");
+    }
+
+    if moves.len() % 2 == 1 && pos.is_checkmate() {
+        Ok(())
+    } else {
+        Err(MateViolation::NotCheckmate)
+    }
+}
+
+/// Represents the result of checking a tsume problem with `MateSolver::check_problem`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TsumeReport {
+    /// The answer of the engine to the problem.
+    pub result: MateResult,
+    /// Why the engine's mate sequence is wrong, if it is.
+    pub violation: Option<MateViolation>,
+    /// Other first moves which also mate.
+    pub alternatives: Vec<Move>,
+    /// Other first moves which the engine could not decide whether they mate.
+    pub undecided: Vec<Move>,
+}
+
+impl TsumeReport {
+    /// Returns true if the engine found a correct mate sequence.
+    pub fn is_solvable(&self) -> bool {
+        matches!(self.result, MateResult::Mate(_)) && self.violation.is_none()
+    }
+
+    /// Returns whether the first move of the solution is the only one which mates,
+    /// or `None` if it is unknown.
+    pub fn has_unique_first_move(&self) -> Option<bool> {
+        if !self.is_solvable() {
+            None
+        } else if !self.alternatives.is_empty() {
+            Some(false)
+        } else if self.undecided.is_empty() {
+            Some(true)
+        } else {
+            None
+        }
+    }
+}
+
+/// `MateSolver` asks an engine to solve mate problems with `go mate`.
+///
+/// # Examples
+/// ```no_run
+/// use std::time::Duration;
+/// use usi::{EngineSpec, MateParam, MateResult, MateSolver};
+///
+/// let mut engine = EngineSpec::new("engine", "/path/to/engine").spawn().unwrap();
+/// engine.prepare(Duration::from_secs(30)).unwrap();
+///
+/// let solver = MateSolver::new(MateParam::Timeout(Duration::from_secs(10)));
+/// match solver.solve(&mut engine, "4k4/9/4P4/9/9/9/9/9/4K4 b G 1").unwrap() {
+///     MateResult::Mate(moves) => println!("mate in {}", moves.len()),
+///     result => println!("{:?}", result),
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct MateSolver {
+    param: MateParam,
+    ready_timeout: Duration,
+}
+
+impl MateSolver {
+    pub fn new(param: MateParam) -> MateSolver {
+        MateSolver {
+            param,
+            ready_timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Sets how long to wait for `checkmate` beyond the time limit, and after `stop`.
+    #[must_use]
+    pub fn ready_timeout(mut self, t: Duration) -> Self {
+        self.ready_timeout = t;
+        self
+    }
+
+    /// Sends the position in SFEN, optionally followed by `moves`, and waits for `checkmate`.
+    /// The engine should have been prepared with `EnginePlayer::prepare`.
+    ///
+    /// If the engine does not answer within the time limit, it is stopped
+    /// and the result is `MateResult::Timeout` unless it answers otherwise.
+    pub fn solve(&self, player: &mut EnginePlayer, sfen: &str) -> Result<MateResult, Error> {
+        player.send_command(&GuiCommand::Position(sfen.to_string()))?;
+        player.send_command(&GuiCommand::Go(ThinkParams::new().mate(self.param.clone())))?;
+
+        let mut deadline = match self.param {
+            MateParam::Timeout(t) => Some(Instant::now() + t + self.ready_timeout),
+            MateParam::Infinite => None,
+        };
+        let mut stopped = false;
+        loop {
+            let output = match deadline {
+                Some(deadline) => player.recv_deadline(deadline)?,
+                None => Some(player.recv()?),
+            };
+            let output = match output {
+                Some(output) => output,
+                None if stopped => return Err(Error::Timeout),
+                None => {
+                    player.send_command(&GuiCommand::Stop)?;
+                    stopped = true;
+                    deadline = Some(Instant::now() + self.ready_timeout);
+                    continue;
+                }
+            };
+
+            if let Some(EngineCommand::Checkmate(params)) = output.response() {
+                return Ok(match params {
+                    CheckmateParams::Mate(moves) => {
+                        MateResult::Mate(moves.iter().map(|m| m.parse()).collect::<Result<_, _>>()?)
+                    }
+                    CheckmateParams::NoMate => MateResult::NoMate,
+                    CheckmateParams::Timeout => MateResult::Timeout,
+                    CheckmateParams::NotImplemented => MateResult::NotImplemented,
+                });
+            }
+        }
+    }
+
+    /// Solves a tsume problem, verifies the solution, and looks for other first moves
+    /// which also mate.
+    ///
+    /// Each other checking move is tried by asking the engine to solve every reply
+    /// of the defender, so that this takes much longer than `solve`.
+    pub fn check_problem(
+        &self,
+        player: &mut EnginePlayer,
+        sfen: &str,
+    ) -> Result<TsumeReport, Error> {
+        let pos = Position::from_sfen(sfen)?;
+        let result = self.solve(player, sfen)?;
+        let mut report = TsumeReport {
+            violation: match result {
+                MateResult::Mate(ref moves) => verify_mate(&pos, moves).err(),
+                _ => None,
+            },
+            result,
+            alternatives: Vec::new(),
+            undecided: Vec::new(),
+        };
+        let first = match report.result {
+            MateResult::Mate(ref moves) if report.violation.is_none() => moves[0],
+            _ => return Ok(report),
+        };
+
+        let with_moves = |moves: &[Move]| {
+            let moves = moves.iter().map(Move::to_string).collect::<Vec<_>>();
+            match sfen.split_once(" moves ") {
+                Some(_) => format!("{} {}", sfen, moves.join(" ")),
+                None => format!("{} moves {}", sfen, moves.join(" ")),
+            }
+        };
+
+        for m in pos.legal_moves() {
+            if m == first || !pos.gives_check(&m) {
+                continue;
+            }
+            let mut after = pos.clone();
+            after.make_move(&m)?;
+
+            // The move mates unless the defender has a reply which escapes.
+            let mut mates = Some(true);
+            for reply in after.legal_moves() {
+                let mut next = after.clone();
+                next.make_move(&reply)?;
+                match self.solve(player, &with_moves(&[m, reply]))? {
+                    MateResult::Mate(ref moves) if verify_mate(&next, moves).is_ok() => {}
+                    MateResult::NoMate => {
+                        mates = Some(false);
+                        break;
+                    }
+                    // An answer which can't be verified doesn't tell whether the move mates.
+                    MateResult::Mate(_) | MateResult::Timeout | MateResult::NotImplemented => {
+                        mates = None
+                    }
+                }
+            }
+            match mates {
+                Some(true) => report.alternatives.push(m),
+                Some(false) => {}
+                None => report.undecided.push(m),
+            }
+        }
+        Ok(report)
+    }
+
+    /// Checks tsume problems in turn with `check_problem`, and calls `on_report` for each.
+    /// Stops when `on_report` returns `ControlFlow::Break`.
+    pub fn check_problems<F>(
+        &self,
+        player: &mut EnginePlayer,
+        problems: &[String],
+        mut on_report: F,
+    ) -> Result<(), Error>
+    where
+        F: FnMut(usize, &TsumeReport) -> ControlFlow<()>,
+    {
+        player.prepare(self.ready_timeout)?;
+        player.send_command(&GuiCommand::UsiNewGame)?;
+        for (i, sfen) in problems.iter().enumerate() {
+            let report = self.check_problem(player, sfen)?;
+            if on_report(i, &report).is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use crate::game::EngineSpec;
+    #[cfg(unix)]
+    use crate::process::testing::script_engine;
+
+    fn moves(s: &str) -> Vec<Move> {
+        s.split_whitespace().map(|m| m.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn verify() {
+        let pos = Position::from_sfen("4k4/9/4P4/9/9/9/9/9/4K4 b G 1").unwrap();
+        assert_eq!(Ok(()), verify_mate(&pos, &moves("G*5b")));
+
+        let cases = [
+            ("", MateViolation::NotCheckmate),
+            ("G*4b", MateViolation::NotCheckmate),
+            ("G*4b 5a6a", MateViolation::NotCheckmate),
+            ("G*5d", MateViolation::NotCheck(0)),
+            ("G*4b 5a5b", MateViolation::IllegalMove(1)),
+            ("G*5b 5a5b", MateViolation::IllegalMove(1)),
+        ];
+        for (s, violation) in cases {
+            assert_eq!(Err(violation), verify_mate(&pos, &moves(s)), "{s}");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_problems() {
+        let engine = EngineSpec::new(
+            "solver",
+            script_engine(
+                "solver",
+                "case \"$pos\" in \
+                   *K8*moves*) echo 'checkmate 7g7f';; \
+                   *moves*) echo 'checkmate nomate';; \
+                   *4k4*) echo 'checkmate G*5b';; \
+                   *8k/9/9*) echo 'checkmate notimplemented';; \
+                   *8k*) echo 'checkmate G*1b';; \
+                   *) echo 'checkmate 7g7f';; \
+                 esac",
+            ),
+        );
+        let problems = [
+            // Only G*5b mates.
+            "sfen 4k4/9/4P4/9/9/9/9/9/4K4 b G 1",
+            // G*1b, G*2b, 1c1b+, 2c1b, 2c2b and 3d2b+ all mate.
+            "sfen 8k/9/7GP/6N2/9/9/9/9/4K4 b G 1",
+            "sfen 8k/9/9/9/9/9/9/9/4K4 b G 1",
+            "sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+            // Answers after other checks can't be verified.
+            "sfen 4k4/9/4P4/9/9/9/9/9/K8 b G 1",
+        ]
+        .iter()
+        .map(|s| s.strip_prefix("sfen ").unwrap().to_string())
+        .collect::<Vec<_>>();
+
+        let mut reports = Vec::new();
+        let mut player = engine.spawn().unwrap();
+        MateSolver::new(MateParam::Timeout(Duration::from_secs(1)))
+            .check_problems(&mut player, &problems, |_, report| {
+                reports.push(report.clone());
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+
+        assert_eq!(
+            TsumeReport {
+                result: MateResult::Mate(moves("G*5b")),
+                violation: None,
+                alternatives: Vec::new(),
+                undecided: Vec::new(),
+            },
+            reports[0]
+        );
+        assert_eq!(Some(true), reports[0].has_unique_first_move());
+
+        assert!(reports[1].is_solvable());
+        let mut alternatives = reports[1]
+            .alternatives
+            .iter()
+            .map(Move::to_string)
+            .collect::<Vec<_>>();
+        alternatives.sort();
+        assert_eq!(vec!["1c1b+", "2c1b", "2c2b", "3d2b+", "G*2b"], alternatives);
+        assert_eq!(Some(false), reports[1].has_unique_first_move());
+
+        assert_eq!(MateResult::NotImplemented, reports[2].result);
+        assert_eq!(None, reports[2].has_unique_first_move());
+
+        assert_eq!(Some(MateViolation::NotCheck(0)), reports[3].violation);
+        assert!(!reports[3].is_solvable());
+
+        assert!(reports[4].is_solvable());
+        assert!(reports[4].alternatives.is_empty());
+        assert!(!reports[4].undecided.is_empty());
+        assert_eq!(None, reports[4].has_unique_first_move());
+    }
+}
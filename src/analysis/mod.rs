@@ -0,0 +1,9 @@
+mod analyzer;
+mod annotation;
+mod mate;
+
+pub use self::analyzer::{
+    load_positions, AnalysisConfig, AnalysisFormat, AnalysisLimit, AnalysisResult, Analyzer,
+};
+pub use self::annotation::{Annotation, AnnotationConfig, Annotator, MoveAnnotation, MoveQuality};
+pub use self::mate::{verify_mate, MateResult, MateSolver, MateViolation, TsumeReport};
@@ -0,0 +1,151 @@
+//! Analyzes positions with a USI engine, and writes the results as JSONL or CSV.
+//!
+//! ```text
+//! usage: usi-analyze [options] <engine> <input>...
+//!
+//!   --depth <n>            stop each search at the depth
+//!   --nodes <n>            stop each search after the number of nodes
+//!   --time <ms>            stop each search after the time (default: 1000)
+//!   --jobs <n>             number of engine processes (default: 1)
+//!   --option <name=value>  option for the engine, can be repeated
+//!   --output <path>        file to append results to, skipping positions already in it
+//!                          (default: standard output)
+//!   --format <jsonl|csv>   output format (default: by the extension of the output, or jsonl)
+//! ```
+//!
+//! Inputs are files containing a SFEN per line, or game records in KIF, KI2 or CSA
+//! whose positions are all analyzed.
+use std::env;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+use usi::{
+    load_positions, AnalysisConfig, AnalysisFormat, AnalysisLimit, AnalysisResult, Analyzer,
+    EngineSpec, Error,
+};
+
+const USAGE: &str = "usage: usi-analyze [--depth <n>] [--nodes <n>] [--time <ms>] [--jobs <n>] \
+                     [--option <name=value>]... [--output <path>] [--format <jsonl|csv>] \
+                     <engine> <input>...";
+
+struct Args {
+    engine: EngineSpec,
+    inputs: Vec<PathBuf>,
+    config: AnalysisConfig,
+    output: Option<PathBuf>,
+    format: AnalysisFormat,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut config = AnalysisConfig::new();
+    let mut options = Vec::new();
+    let mut output = None;
+    let mut format = None;
+    let mut paths = Vec::new();
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--depth" => config = config.limit(AnalysisLimit::Depth(parse_number(&value()?)?)),
+            "--nodes" => config = config.limit(AnalysisLimit::Nodes(parse_number(&value()?)?)),
+            "--time" => {
+                let ms = parse_number(&value()?)?;
+                config = config.limit(AnalysisLimit::Time(Duration::from_millis(ms as u64)));
+            }
+            "--jobs" => config = config.concurrency(parse_number(&value()?)? as usize),
+            "--option" => {
+                let v = value()?;
+                let (name, value) = v
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected <name=value>, got {v}"))?;
+                options.push((name.to_string(), value.to_string()));
+            }
+            "--output" => output = Some(PathBuf::from(value()?)),
+            "--format" => {
+                format = Some(match value()?.as_str() {
+                    "jsonl" => AnalysisFormat::Jsonl,
+                    "csv" => AnalysisFormat::Csv,
+                    v => return Err(format!("expected jsonl or csv, got {v}")),
+                })
+            }
+            _ if arg.starts_with("--") => return Err(USAGE.to_string()),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    if paths.len() < 2 {
+        return Err(USAGE.to_string());
+    }
+    let inputs = paths.split_off(1);
+    let mut engine = EngineSpec::new("engine", &paths[0]);
+    for (name, value) in options {
+        engine = engine.option(&name, &value);
+    }
+
+    let format = format
+        .or_else(|| output.as_ref().and_then(AnalysisFormat::from_path))
+        .unwrap_or(AnalysisFormat::Jsonl);
+    if let Some(ref path) = output {
+        config = config.output(path, format);
+    }
+
+    Ok(Args {
+        engine,
+        inputs,
+        config,
+        output,
+        format,
+    })
+}
+
+fn parse_number(s: &str) -> Result<i32, String> {
+    s.parse()
+        .ok()
+        .filter(|n| *n > 0)
+        .ok_or_else(|| format!("invalid number: {s}"))
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(2);
+    });
+
+    if let Err(e) = run(args) {
+        eprintln!("usi-analyze: {e}");
+        process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let mut positions = Vec::new();
+    for input in &args.inputs {
+        positions.extend(load_positions(input)?);
+    }
+
+    if args.output.is_none() && args.format == AnalysisFormat::Csv {
+        println!("{}", AnalysisResult::CSV_HEADER);
+    }
+
+    // Results are written to the output file by the analyzer, so only report progress then.
+    let total = positions.len();
+    let mut done = 0;
+    Analyzer::new(args.engine, args.config).run(&positions, |result| {
+        done += 1;
+        match (&args.output, args.format) {
+            (Some(_), _) => eprintln!(
+                "analyzed #{} ({done} in this run, {total} in total)",
+                result.index
+            ),
+            (None, AnalysisFormat::Jsonl) => println!("{}", result.to_json()),
+            (None, AnalysisFormat::Csv) => println!("{}", result.to_csv()),
+        }
+        ControlFlow::Continue(())
+    })
+}
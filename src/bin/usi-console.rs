@@ -0,0 +1,347 @@
+//! An interactive console to talk to a USI engine by hand.
+//!
+//! `usi-console` spawns the engine, performs the `usi` handshake and prints available options.
+//! Each line entered at the prompt is parsed as a `GuiCommand` and sent to the engine,
+//! while outputs from the engine are printed as they arrive with `info` lines pretty-printed.
+//! Lines which fail to parse, or deviate from the spec, are marked with `!!` and a diagnostic.
+//!
+//! ```text
+//! usage: usi-console <engine> [<working_dir>]
+//! ```
+use std::env;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, ExternalPrinter, Helper};
+use usi::{
+    EngineCommand, EngineInfo, Error, GuiCommand, InfoParams, OptionKind, ScoreKind,
+    UsiEngineHandler,
+};
+
+const USAGE: &str = "usage: usi-console <engine> [<working_dir>]";
+const HISTORY_FILE: &str = ".usi_console_history";
+
+const COMMANDS: &[&str] = &[
+    "gameover",
+    "go",
+    "isready",
+    "ponderhit",
+    "position",
+    "quit",
+    "setoption",
+    "stop",
+    "usi",
+    "usinewgame",
+];
+
+/// Provides completions for command names and their parameters.
+struct ConsoleHelper {
+    option_names: Vec<String>,
+}
+
+impl ConsoleHelper {
+    fn candidates(&self, words: &[&str]) -> Vec<&str> {
+        match words {
+            [] => COMMANDS.to_vec(),
+            ["go", ..] => vec![
+                "ponder",
+                "btime",
+                "wtime",
+                "byoyomi",
+                "binc",
+                "winc",
+                "movetime",
+                "rtime",
+                "depth",
+                "nodes",
+                "infinite",
+                "mate",
+                "searchmoves",
+            ],
+            ["gameover"] => vec!["win", "lose", "draw"],
+            ["position"] => vec!["startpos", "sfen"],
+            ["position", _, ..] => vec!["moves"],
+            ["setoption"] => vec!["name"],
+            ["setoption", "name"] => self.option_names.iter().map(String::as_str).collect(),
+            ["setoption", "name", _] => vec!["value"],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl Completer for ConsoleHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let start = line.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        let words = line[..start].split_whitespace().collect::<Vec<_>>();
+        let prefix = &line[start..];
+
+        let pairs = self
+            .candidates(&words)
+            .into_iter()
+            .filter(|c| c.starts_with(prefix))
+            .map(|c| Pair {
+                display: c.to_string(),
+                replacement: format!("{c} "),
+            })
+            .collect();
+        Ok((start, pairs))
+    }
+}
+
+impl Hinter for ConsoleHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ConsoleHelper {}
+
+impl Validator for ConsoleHelper {}
+
+impl Helper for ConsoleHelper {}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let (engine, working_dir) = match (args.next(), args.next(), args.next()) {
+        (Some(engine), dir, None) if !engine.starts_with('-') => {
+            let engine = PathBuf::from(engine);
+            let dir = dir
+                .map(PathBuf::from)
+                .or_else(|| engine.parent().map(Path::to_path_buf))
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or_else(|| PathBuf::from("."));
+            (engine, dir)
+        }
+        _ => {
+            eprintln!("{USAGE}");
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&engine, &working_dir) {
+        eprintln!("usi-console: {e}");
+        process::exit(1);
+    }
+}
+
+fn run(engine: &Path, working_dir: &Path) -> Result<(), Error> {
+    let mut handler = UsiEngineHandler::spawn(engine, working_dir)?;
+    let info = handler.get_info()?;
+    print_info(&info);
+
+    let helper = ConsoleHelper {
+        option_names: info
+            .option_params()
+            .iter()
+            .map(|p| p.name.clone())
+            .collect(),
+    };
+    let mut editor: Editor<ConsoleHelper, DefaultHistory> =
+        Editor::new().map_err(readline_error)?;
+    editor.set_helper(Some(helper));
+    let history = env::var_os("HOME").map(|home| PathBuf::from(home).join(HISTORY_FILE));
+    if let Some(ref path) = history {
+        let _ = editor.load_history(path);
+    }
+
+    // The external printer keeps the prompt intact, but is only available on terminals.
+    let mut print: Box<dyn FnMut(String) + Send> = match editor.create_external_printer() {
+        Ok(mut printer) => Box::new(move |msg| {
+            let _ = printer.print(msg);
+        }),
+        Err(_) => Box::new(|msg| println!("{msg}")),
+    };
+    handler.listen(move |output| -> Result<(), Error> {
+        let msg = match output.response() {
+            Some(EngineCommand::Info(params)) => format_info(params),
+            Some(EngineCommand::Unknown) => {
+                format!("?? unknown command: {}", output.raw_str().trim_end())
+            }
+            Some(EngineCommand::Unparsed { error }) => format!("!! failed to parse: {error}"),
+            Some(_) => format!("<< {}", output.raw_str().trim_end()),
+            None => {
+                print("-- engine exited".to_string());
+                process::exit(0);
+            }
+        };
+        print(msg);
+        // Deviations tolerated by the lenient reader are shown as well.
+        for warning in output.warnings() {
+            print(format!("!! warning: {warning}"));
+        }
+        Ok(())
+    })?;
+
+    loop {
+        let line = match editor.readline("usi> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(readline_error(e)),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let _ = editor.add_history_entry(line.as_str());
+
+        match GuiCommand::parse(&line) {
+            Ok(cmd) => {
+                handler.send_command(&cmd)?;
+                if cmd == GuiCommand::Quit {
+                    break;
+                }
+            }
+            Err(e) => println!("!! failed to parse `{}`: {}", line.trim(), e),
+        }
+    }
+
+    if let Some(ref path) = history {
+        let _ = editor.save_history(path);
+    }
+    // Give the engine a chance to flush its last outputs.
+    std::thread::sleep(Duration::from_millis(100));
+    Ok(())
+}
+
+fn readline_error(e: ReadlineError) -> Error {
+    Error::HandlerError(Box::new(e))
+}
+
+fn print_info(info: &EngineInfo) {
+    println!("name:   {}", info.name());
+    println!("author: {}", info.author());
+
+    let rows = info
+        .option_params()
+        .iter()
+        .map(|p| {
+            let (kind, default, range) = match &p.value {
+                OptionKind::Check { default } => {
+                    ("check", default.map(|v| v.to_string()), String::new())
+                }
+                OptionKind::Spin { default, min, max } => (
+                    "spin",
+                    default.map(|v| v.to_string()),
+                    format!(
+                        "{}..{}",
+                        min.map(|v| v.to_string()).unwrap_or_default(),
+                        max.map(|v| v.to_string()).unwrap_or_default()
+                    ),
+                ),
+                OptionKind::Combo { default, vars } => ("combo", default.clone(), vars.join("|")),
+                OptionKind::Button { default } => ("button", default.clone(), String::new()),
+                OptionKind::String { default } => ("string", default.clone(), String::new()),
+                OptionKind::Filename { default } => ("filename", default.clone(), String::new()),
+            };
+            [
+                p.name.clone(),
+                kind.to_string(),
+                default.unwrap_or_default(),
+                range,
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    let header = ["NAME", "TYPE", "DEFAULT", "RANGE"].map(String::from);
+    let mut widths = header.clone().map(|h| h.len());
+    for row in &rows {
+        for (w, col) in widths.iter_mut().zip(row) {
+            *w = (*w).max(col.chars().count());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(&rows) {
+        let mut line = String::new();
+        for (w, col) in widths.iter().zip(row) {
+            let _ = write!(line, "{col:<w$}  ");
+        }
+        println!("{}", line.trim_end());
+    }
+}
+
+fn format_info(params: &[InfoParams]) -> String {
+    let mut line = String::from("info");
+
+    for p in params {
+        let _ = match p {
+            InfoParams::Depth(d, Some(sd)) => write!(line, "  depth {d}/{sd}"),
+            InfoParams::Depth(d, None) => write!(line, "  depth {d}"),
+            InfoParams::Score(v, kind) => {
+                let score = match kind {
+                    ScoreKind::CpExact => format!("{v:+}cp"),
+                    ScoreKind::CpLowerbound => format!(">={v:+}cp"),
+                    ScoreKind::CpUpperbound => format!("<={v:+}cp"),
+                    ScoreKind::MateExact => format!("mate {v:+}"),
+                    ScoreKind::MateSignOnly => {
+                        format!("mate {}", if *v > 0 { "+" } else { "-" })
+                    }
+                    ScoreKind::MateLowerbound => format!("mate >={v:+}"),
+                    ScoreKind::MateUpperbound => format!("mate <={v:+}"),
+                };
+                write!(line, "  score {score}")
+            }
+            InfoParams::Nodes(n) => write!(line, "  nodes {n}"),
+            InfoParams::Nps(n) => write!(line, "  nps {n}"),
+            InfoParams::Time(t) => write!(line, "  time {:.3}s", t.as_secs_f64()),
+            InfoParams::HashFull(h) => write!(line, "  hash {:.1}%", *h as f64 / 10.0),
+            InfoParams::MultiPv(n) => write!(line, "  multipv {n}"),
+            InfoParams::CurrMove(m) => write!(line, "  currmove {m}"),
+            InfoParams::Pv(pv) => write!(line, "\n     pv {}", pv.join(" ")),
+            InfoParams::Text(s) => write!(line, "\n     string {s}"),
+        };
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format() {
+        let cmd = EngineCommand::parse(
+            "info depth 3 seldepth 5 time 1141 nodes 135125 score cp -1521 pv 3a3b L*4h 4c4d",
+        )
+        .unwrap();
+        let EngineCommand::Info(params) = cmd else {
+            unreachable!()
+        };
+
+        assert_eq!(
+            "info  depth 3/5  time 1.141s  nodes 135125  score -1521cp\n     pv 3a3b L*4h 4c4d",
+            format_info(&params)
+        );
+    }
+
+    #[test]
+    fn complete() {
+        let helper = ConsoleHelper {
+            option_names: vec!["USI_Hash".to_string(), "USI_Ponder".to_string()],
+        };
+
+        assert_eq!(COMMANDS.to_vec(), helper.candidates(&[]));
+        assert_eq!(
+            vec!["USI_Hash", "USI_Ponder"],
+            helper.candidates(&["setoption", "name"])
+        );
+        assert!(helper
+            .candidates(&["go", "depth", "10"])
+            .contains(&"searchmoves"));
+        assert!(helper.candidates(&["usi"]).is_empty());
+    }
+}
@@ -0,0 +1,267 @@
+//! Plays games between two USI engines, alternating colors after each game.
+//!
+//! ```text
+//! usage: usi-match [options] <engine1> <engine2>
+//!
+//!   --games <n>            number of games to play (default: 2)
+//!   --time <ms>            main time for each player (default: 0)
+//!   --byoyomi <ms>         byoyomi for each move (default: 1000)
+//!   --inc <ms>             Fischer increment for each move, instead of byoyomi
+//!   --movetime <ms>        fixed time for each move, instead of the above
+//!   --margin <ms>          extra time tolerated before losing on time (default: 1000)
+//!   --max-moves <n>        moves until the game is declared a draw (default: 256)
+//!   --sfen <sfen>          start position (default: the initial position)
+//!   --option1 <name=value> option for engine1, can be repeated
+//!   --option2 <name=value> option for engine2, can be repeated
+//!   --resign <cp,plies>    adjudicate a loss when both engines agree on the score
+//!   --draw <moves,cp,plies>
+//!                          adjudicate a draw when scores stay close to zero after <moves>
+//!   --impasse <27|24>      validate declarations of win by the 27-point or 24-point rule
+//!   --sprt <elo0,elo1[,alpha,beta]>
+//!                          stop as soon as SPRT accepts a hypothesis about engine1
+//!                          (default alpha and beta: 0.05)
+//! ```
+use std::env;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
+
+use usi::{
+    Color, DrawAdjudicator, EloEstimate, EnginePlayer, Error, ImpasseAdjudicator, ImpasseRule,
+    Match, MatchConfig, Pentanomial, ResignAdjudicator, Score, Sprt, SprtResult, TimeControl,
+    UsiEngineHandler,
+};
+
+const USAGE: &str = "usage: usi-match [--games <n>] [--time <ms>] [--byoyomi <ms>] \
+                     [--inc <ms>] [--movetime <ms>] [--margin <ms>] [--max-moves <n>] \
+                     [--sfen <sfen>] [--option1 <name=value>]... [--option2 <name=value>]... \
+                     [--resign <cp,plies>] [--draw <moves,cp,plies>] [--impasse <27|24>] \
+                     [--sprt <elo0,elo1[,alpha,beta]>] <engine1> <engine2>";
+
+struct Args {
+    games: usize,
+    config: MatchConfig,
+    engines: Vec<PathBuf>,
+    options: [Vec<(String, String)>; 2],
+    sprt: Option<Sprt>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        games: 2,
+        config: MatchConfig::new(),
+        engines: Vec::new(),
+        options: [Vec::new(), Vec::new()],
+        sprt: None,
+    };
+
+    let mut time = Duration::ZERO;
+    let mut byoyomi = Duration::from_secs(1);
+    let mut increment = None;
+    let mut movetime = None;
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--games" => args.games = parse_number(&value()?)?,
+            "--time" => time = parse_ms(&value()?)?,
+            "--byoyomi" => byoyomi = parse_ms(&value()?)?,
+            "--inc" => increment = Some(parse_ms(&value()?)?),
+            "--movetime" => movetime = Some(parse_ms(&value()?)?),
+            "--margin" => args.config = args.config.margin(parse_ms(&value()?)?),
+            "--max-moves" => args.config = args.config.max_moves(parse_number(&value()?)?),
+            "--sfen" => args.config = args.config.start_sfen(&value()?),
+            "--option1" | "--option2" => {
+                let v = value()?;
+                let (name, value) = v
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected <name=value>, got {v}"))?;
+                let i = if arg == "--option1" { 0 } else { 1 };
+                args.options[i].push((name.to_string(), value.to_string()));
+            }
+            "--resign" => {
+                let [score, plies] = parse_numbers(&value()?)?;
+                args.config = args
+                    .config
+                    .adjudicator(ResignAdjudicator::new(score as i32, plies));
+            }
+            "--draw" => {
+                let [moves, score, plies] = parse_numbers(&value()?)?;
+                args.config =
+                    args.config
+                        .adjudicator(DrawAdjudicator::new(moves, score as i32, plies));
+            }
+            "--impasse" => {
+                let rule = match value()?.as_str() {
+                    "27" => ImpasseRule::Point27,
+                    "24" => ImpasseRule::Point24,
+                    v => return Err(format!("expected 27 or 24, got {v}")),
+                };
+                args.config = args.config.adjudicator(ImpasseAdjudicator::new(rule));
+            }
+            "--sprt" => args.sprt = Some(parse_sprt(&value()?)?),
+            _ if arg.starts_with("--") => return Err(USAGE.to_string()),
+            _ => args.engines.push(PathBuf::from(arg)),
+        }
+    }
+
+    if args.engines.len() != 2 {
+        return Err(USAGE.to_string());
+    }
+
+    let time_control = match (movetime, increment) {
+        (Some(t), _) => TimeControl::FixedPerMove(t),
+        (None, Some(increment)) => TimeControl::Fischer { time, increment },
+        (None, None) => TimeControl::Byoyomi { time, byoyomi },
+    };
+    args.config = args.config.time_control(time_control);
+    Ok(args)
+}
+
+fn parse_number(s: &str) -> Result<usize, String> {
+    s.parse().map_err(|_| format!("invalid number: {s}"))
+}
+
+fn parse_numbers<const N: usize>(s: &str) -> Result<[usize; N], String> {
+    s.split(',')
+        .map(parse_number)
+        .collect::<Result<Vec<_>, _>>()?
+        .try_into()
+        .map_err(|_| format!("expected {N} comma-separated numbers, got {s}"))
+}
+
+fn parse_sprt(s: &str) -> Result<Sprt, String> {
+    let v = s
+        .split(',')
+        .map(|v| v.parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| format!("invalid SPRT parameters: {s}"))?;
+    match v[..] {
+        [elo0, elo1] => Ok(Sprt::new(elo0, elo1, 0.05, 0.05)),
+        [elo0, elo1, alpha, beta] => Ok(Sprt::new(elo0, elo1, alpha, beta)),
+        _ => Err(format!("expected <elo0,elo1[,alpha,beta]>, got {s}")),
+    }
+}
+
+fn parse_ms(s: &str) -> Result<Duration, String> {
+    s.parse()
+        .map(Duration::from_millis)
+        .map_err(|_| format!("invalid milliseconds: {s}"))
+}
+
+fn spawn(engine: &Path, options: &[(String, String)]) -> Result<EnginePlayer, Error> {
+    let dir = engine
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let mut player = EnginePlayer::new(UsiEngineHandler::spawn(engine, dir)?)?;
+    for (name, value) in options {
+        player.set_option(name, Some(value))?;
+    }
+    Ok(player)
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(2);
+    });
+
+    if let Err(e) = run(args) {
+        eprintln!("usi-match: {e}");
+        process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let mut players = [
+        spawn(&args.engines[0], &args.options[0])?,
+        spawn(&args.engines[1], &args.options[1])?,
+    ];
+    let runner = Match::new(args.config);
+
+    // Results from the point of view of engine1.
+    // Each two consecutive games start from the same position with colors swapped.
+    let mut score = Score::default();
+    let mut pairs = Pentanomial::new();
+    let mut first_points = 0.0;
+    for game in 0..args.games {
+        let [ref mut first, ref mut second] = players;
+        let (black, white, first_color) = if game % 2 == 0 {
+            (first, second, Color::Black)
+        } else {
+            (second, first, Color::White)
+        };
+
+        let record = runner.play(black, white)?;
+        let result = record.result.expect("a finished game must have a result");
+        let points = match result.winner {
+            Some(c) if c == first_color => {
+                score.wins += 1;
+                1.0
+            }
+            Some(_) => {
+                score.losses += 1;
+                0.0
+            }
+            None => {
+                score.draws += 1;
+                0.5
+            }
+        };
+
+        let moves = record
+            .moves
+            .iter()
+            .map(|m| m.mv.to_string())
+            .collect::<Vec<_>>();
+        println!(
+            "game {}: {} (b) vs {} (w): {} in {} moves",
+            game + 1,
+            record.black_name,
+            record.white_name,
+            result,
+            record.moves.len()
+        );
+        println!(
+            "  position sfen {} moves {}",
+            record.start_sfen,
+            moves.join(" ")
+        );
+
+        if game % 2 == 0 {
+            first_points = points;
+            continue;
+        }
+        pairs.add_pair(first_points, points);
+        if let Some(ref sprt) = args.sprt {
+            if let ControlFlow::Break(result) = check_sprt(sprt, &pairs) {
+                println!("SPRT: {result:?}");
+                break;
+            }
+        }
+    }
+
+    println!("{} vs {}: {}", players[0].name(), players[1].name(), score);
+    if let Some(elo) = EloEstimate::new(&score) {
+        println!("Elo: {elo}");
+    }
+    if pairs.pairs() > 0 {
+        println!("pentanomial: {pairs}");
+    }
+    Ok(())
+}
+
+fn check_sprt(sprt: &Sprt, pairs: &Pentanomial) -> ControlFlow<SprtResult> {
+    let (lower, upper) = sprt.bounds();
+    println!("  LLR {:.2} ({:.2}, {:.2})", sprt.llr(pairs), lower, upper);
+    match sprt.test(pairs) {
+        SprtResult::Continue => ControlFlow::Continue(()),
+        result => ControlFlow::Break(result),
+    }
+}
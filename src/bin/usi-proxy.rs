@@ -0,0 +1,285 @@
+//! A logging proxy placed between a shogi GUI and a USI engine.
+//!
+//! The GUI launches `usi-proxy` in place of the engine. The proxy spawns the real engine,
+//! passes all traffic through in both directions and logs every line with its parsed
+//! `GuiCommand`/`EngineCommand` value and the elapsed time since startup.
+//!
+//! As GUIs usually launch engines without arguments, the proxy is configured by a file
+//! located by `--config <path>`, the `USI_PROXY_CONFIG` environment variable, or
+//! `usi-proxy.conf` placed next to the executable, in this order.
+//!
+//! ```text
+//! # Path to the real engine and its working directory (defaults to the engine's directory).
+//! engine /path/to/engine
+//! workdir /path/to
+//! # Log file (defaults to stderr).
+//! log /tmp/usi-proxy.log
+//! # Replace the value of `setoption` commands sent from the GUI.
+//! override USI_Hash 1024
+//! # Send additional `setoption` commands before the first `isready`.
+//! inject Threads 4
+//! ```
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use usi::{EngineOutput, Error, GuiCommand, UsiEngineHandler};
+
+const USAGE: &str = "usage: usi-proxy [--config <file>] [--log <file>] [<engine>]";
+
+/// How long to wait for the engine to exit by itself after `quit`.
+const QUIT_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Default)]
+struct Config {
+    engine: Option<PathBuf>,
+    working_dir: Option<PathBuf>,
+    log: Option<PathBuf>,
+    overrides: HashMap<String, String>,
+    injections: Vec<(String, Option<String>)>,
+}
+
+impl Config {
+    fn parse(s: &str) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            match key {
+                "engine" => config.engine = Some(PathBuf::from(rest)),
+                "workdir" => config.working_dir = Some(PathBuf::from(rest)),
+                "log" => config.log = Some(PathBuf::from(rest)),
+                "override" => match rest.split_once(char::is_whitespace) {
+                    Some((name, value)) => {
+                        config
+                            .overrides
+                            .insert(name.to_string(), value.trim().to_string());
+                    }
+                    None => return Err(format!("line {}: missing override value", i + 1)),
+                },
+                "inject" if !rest.is_empty() => {
+                    let (name, value) = match rest.split_once(char::is_whitespace) {
+                        Some((name, value)) => (name, Some(value.trim().to_string())),
+                        None => (rest, None),
+                    };
+                    config.injections.push((name.to_string(), value));
+                }
+                _ => return Err(format!("line {}: unknown directive `{}`", i + 1, line)),
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn load() -> Result<Config, String> {
+        let mut args = env::args().skip(1);
+        let mut config_path = env::var_os("USI_PROXY_CONFIG").map(PathBuf::from);
+        let mut log = None;
+        let mut engine = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => config_path = args.next().map(PathBuf::from),
+                "--log" => log = args.next().map(PathBuf::from),
+                "-h" | "--help" => return Err(USAGE.to_string()),
+                _ if engine.is_none() => engine = Some(PathBuf::from(arg)),
+                _ => return Err(USAGE.to_string()),
+            }
+        }
+
+        if config_path.is_none() {
+            config_path = env::current_exe()
+                .ok()
+                .and_then(|p| p.parent().map(|d| d.join("usi-proxy.conf")))
+                .filter(|p| p.exists());
+        }
+
+        let mut config = match config_path {
+            Some(p) => {
+                let s = fs::read_to_string(&p)
+                    .map_err(|e| format!("failed to read {}: {}", p.display(), e))?;
+                Config::parse(&s)?
+            }
+            None => Config::default(),
+        };
+
+        if engine.is_some() {
+            config.engine = engine;
+        }
+        if log.is_some() {
+            config.log = log;
+        }
+
+        Ok(config)
+    }
+}
+
+/// Writes timestamped traffic lines to the log destination.
+struct Logger {
+    start: Instant,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Logger {
+    fn log(&self, at: Instant, direction: &str, raw: &str, parsed: &dyn std::fmt::Debug) {
+        let elapsed = at.saturating_duration_since(self.start);
+        let mut out = self.out.lock().unwrap();
+        let _ = writeln!(
+            out,
+            "[{:>10.3}] {} {} | {:?}",
+            elapsed.as_secs_f64(),
+            direction,
+            raw.trim_end(),
+            parsed
+        );
+        let _ = out.flush();
+    }
+}
+
+fn main() {
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(2);
+    });
+
+    if let Err(e) = run(config) {
+        eprintln!("usi-proxy: {e}");
+        process::exit(1);
+    }
+}
+
+fn run(config: Config) -> Result<(), Error> {
+    let engine = config
+        .engine
+        .clone()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, USAGE))?;
+    let working_dir = config
+        .working_dir
+        .clone()
+        .or_else(|| engine.parent().map(Path::to_path_buf))
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| PathBuf::from("."));
+
+    let out: Box<dyn Write + Send> = match config.log {
+        Some(ref p) => Box::new(File::create(p)?),
+        None => Box::new(io::stderr()),
+    };
+    let logger = Arc::new(Logger {
+        start: Instant::now(),
+        out: Mutex::new(out),
+    });
+
+    let mut handler = UsiEngineHandler::spawn(&engine, working_dir)?;
+
+    let engine_logger = Arc::clone(&logger);
+    handler.listen(move |output: &EngineOutput| -> Result<(), io::Error> {
+        if output.response().is_none() && output.raw_str().is_empty() {
+            engine_logger.log(*output.timestamp(), "<", "", &"engine exited");
+            process::exit(0);
+        }
+
+        engine_logger.log(
+            *output.timestamp(),
+            "<",
+            output.raw_str(),
+            output.response(),
+        );
+
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(output.raw_str().as_bytes())?;
+        stdout.flush()
+    })?;
+
+    let mut injected = config.injections.is_empty();
+    let mut quit = false;
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        // The line is passed through as is, and the parsed command is only used for logging
+        // and overriding options, so that commands this crate does not know still reach the engine.
+        let cmd = GuiCommand::parse(&line);
+        match cmd {
+            Ok(ref cmd) => logger.log(Instant::now(), ">", &line, cmd),
+            Err(ref e) => logger.log(Instant::now(), ">", &line, e),
+        }
+
+        if !injected && matches!(cmd, Ok(GuiCommand::IsReady)) {
+            for (name, value) in &config.injections {
+                let inject = GuiCommand::SetOption(name.clone(), value.clone());
+                logger.log(Instant::now(), "+", &inject.to_string(), &"injected");
+                handler.send_command(&inject)?;
+            }
+            injected = true;
+        }
+
+        match cmd {
+            Ok(GuiCommand::SetOption(ref name, _)) if config.overrides.contains_key(name) => {
+                let value = config.overrides[name].clone();
+                let overridden = GuiCommand::SetOption(name.clone(), Some(value));
+                logger.log(Instant::now(), "~", &overridden.to_string(), &"overridden");
+                handler.send_command(&overridden)?;
+            }
+            _ => handler.send_raw(&line)?,
+        }
+        if matches!(cmd, Ok(GuiCommand::Quit)) {
+            quit = true;
+            break;
+        }
+    }
+
+    // The listener thread terminates the proxy once the engine closes its output,
+    // which may happen before the process is reaped here.
+    if quit {
+        handler.wait_timeout(QUIT_GRACE_PERIOD)?;
+    } else {
+        handler.quit(QUIT_GRACE_PERIOD)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config = Config::parse(
+            "# comment\n\
+             engine /opt/engine/YaneuraOu\n\
+             \n\
+             log /tmp/proxy.log\n\
+             override USI_Hash 1024\n\
+             inject EvalDir eval files\n\
+             inject ResetLearning\n",
+        )
+        .unwrap();
+
+        assert_eq!(Some(PathBuf::from("/opt/engine/YaneuraOu")), config.engine);
+        assert_eq!(Some(PathBuf::from("/tmp/proxy.log")), config.log);
+        assert_eq!(Some(&"1024".to_string()), config.overrides.get("USI_Hash"));
+        assert_eq!(
+            vec![
+                ("EvalDir".to_string(), Some("eval files".to_string())),
+                ("ResetLearning".to_string(), None),
+            ],
+            config.injections
+        );
+
+        assert!(Config::parse("override USI_Hash").is_err());
+        assert!(Config::parse("foo bar").is_err());
+    }
+}
@@ -0,0 +1,128 @@
+//! Checks tsume problems with a USI engine for solvability and uniqueness of the first move.
+//!
+//! ```text
+//! usage: usi-tsume [options] <engine> <input>...
+//!
+//!   --time <ms>            time limit of `go mate` for each search (default: infinite)
+//!   --option <name=value>  option for the engine, can be repeated
+//! ```
+//!
+//! Inputs are files containing a SFEN per line.
+use std::env;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+
+use usi::{load_positions, EngineSpec, Error, MateParam, MateResult, MateSolver, Move};
+
+const USAGE: &str = "usage: usi-tsume [--time <ms>] [--option <name=value>]... <engine> <input>...";
+
+struct Args {
+    engine: EngineSpec,
+    inputs: Vec<PathBuf>,
+    param: MateParam,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut param = MateParam::Infinite;
+    let mut options = Vec::new();
+    let mut paths = Vec::new();
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| format!("missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--time" => {
+                let v = value()?;
+                let ms = v
+                    .parse()
+                    .map_err(|_| format!("invalid milliseconds: {v}"))?;
+                param = MateParam::Timeout(Duration::from_millis(ms));
+            }
+            "--option" => {
+                let v = value()?;
+                let (name, value) = v
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected <name=value>, got {v}"))?;
+                options.push((name.to_string(), value.to_string()));
+            }
+            _ if arg.starts_with("--") => return Err(USAGE.to_string()),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    if paths.len() < 2 {
+        return Err(USAGE.to_string());
+    }
+    let inputs = paths.split_off(1);
+    let mut engine = EngineSpec::new("engine", &paths[0]);
+    for (name, value) in options {
+        engine = engine.option(&name, &value);
+    }
+    Ok(Args {
+        engine,
+        inputs,
+        param,
+    })
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(2);
+    });
+
+    if let Err(e) = run(args) {
+        eprintln!("usi-tsume: {e}");
+        process::exit(1);
+    }
+}
+
+fn join(moves: &[Move]) -> String {
+    moves
+        .iter()
+        .map(Move::to_string)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    let mut problems = Vec::new();
+    for input in &args.inputs {
+        problems.extend(load_positions(input)?);
+    }
+
+    let mut player = args.engine.spawn()?;
+    MateSolver::new(args.param).check_problems(&mut player, &problems, |i, report| {
+        let result = match (&report.result, report.violation) {
+            (MateResult::Mate(moves), None) => {
+                format!("mate in {}: {}", moves.len(), join(moves))
+            }
+            (MateResult::Mate(moves), Some(v)) => {
+                format!("invalid solution ({v}): {}", join(moves))
+            }
+            (MateResult::NoMate, _) => "no mate".to_string(),
+            (MateResult::Timeout, _) => "timeout".to_string(),
+            (MateResult::NotImplemented, _) => "not implemented".to_string(),
+        };
+        match report.has_unique_first_move() {
+            Some(true) => println!("#{}: {result}; unique", i + 1),
+            Some(false) => println!(
+                "#{}: {result}; also mated by {}",
+                i + 1,
+                join(&report.alternatives)
+            ),
+            None if report.is_solvable() => println!(
+                "#{}: {result}; undecided: {}",
+                i + 1,
+                join(&report.undecided)
+            ),
+            None => println!("#{}: {result}", i + 1),
+        }
+        ControlFlow::Continue(())
+    })
+}
@@ -0,0 +1,690 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::summary::parse_move_message;
+use super::GameSummary;
+use crate::error::Error;
+use crate::game::{
+    Adjudication, EnginePlayer, GameClock, GameEndReason, GameRecord, GameResult, MoveRecord,
+    TimeControl,
+};
+use crate::kifu::csa::format_move;
+use crate::protocol::{
+    BestMoveParams, EngineCommand, GuiCommand, InfoParams, ScoreKind, ThinkParams,
+};
+use crate::shogi::{Color, Move, Position};
+
+/// Interval to check messages from the server while the engine is thinking.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Represents settings of `CsaClient`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CsaConfig {
+    margin: Duration,
+    keepalive: Duration,
+    ready_timeout: Duration,
+    evaluation_comments: bool,
+}
+
+impl CsaConfig {
+    pub fn new() -> Self {
+        CsaConfig::default()
+    }
+
+    /// Sets the time subtracted from the time given to the engine, to absorb network latency.
+    #[must_use]
+    pub fn margin(mut self, t: Duration) -> Self {
+        self.margin = t;
+        self
+    }
+
+    /// Sets the interval of empty lines sent to keep the connection alive. Zero disables them.
+    #[must_use]
+    pub fn keepalive(mut self, t: Duration) -> Self {
+        self.keepalive = t;
+        self
+    }
+
+    /// Sets how long to wait for `readyok` before starting a game.
+    #[must_use]
+    pub fn ready_timeout(mut self, t: Duration) -> Self {
+        self.ready_timeout = t;
+        self
+    }
+
+    /// Sends the score and the principal variation of the engine with each move,
+    /// as supported by Floodgate.
+    #[must_use]
+    pub fn evaluation_comments(mut self, enabled: bool) -> Self {
+        self.evaluation_comments = enabled;
+        self
+    }
+}
+
+impl Default for CsaConfig {
+    fn default() -> Self {
+        CsaConfig {
+            margin: Duration::from_secs(1),
+            keepalive: Duration::from_secs(30),
+            ready_timeout: Duration::from_secs(30),
+            evaluation_comments: false,
+        }
+    }
+}
+
+/// `CsaClient` connects a USI engine to a server speaking the CSA protocol, such as Floodgate.
+///
+/// Messages from the server are read on a background thread, so that the client can watch
+/// both the server and the engine while the engine is thinking.
+///
+/// # Examples
+/// ```no_run
+/// use usi::{CsaClient, CsaConfig, EnginePlayer, UsiEngineHandler};
+///
+/// let mut engine = EnginePlayer::new(UsiEngineHandler::spawn("/path/to/engine", "/path/to").unwrap()).unwrap();
+/// let mut client = CsaClient::connect("wdoor.c.u-tokyo.ac.jp:4081", CsaConfig::new()).unwrap();
+/// client.login("my-engine", "floodgate-300-10F,password").unwrap();
+/// loop {
+///     let summary = client.wait_game().unwrap();
+///     if client.agree(&summary).unwrap() {
+///         let record = client.play(&summary, &mut engine).unwrap();
+///         println!("{}", record.to_csa());
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct CsaClient {
+    stream: TcpStream,
+    receiver: Receiver<String>,
+    pending: VecDeque<String>,
+    config: CsaConfig,
+    last_sent: Instant,
+}
+
+impl CsaClient {
+    /// Connects to the server.
+    pub fn connect<A: ToSocketAddrs>(addr: A, config: CsaConfig) -> Result<CsaClient, Error> {
+        let stream = TcpStream::connect(addr)?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let (sender, receiver) = mpsc::channel();
+
+        thread::spawn(move || {
+            for line in reader.lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if sender.send(line.trim_end().to_string()).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(CsaClient {
+            stream,
+            receiver,
+            pending: VecDeque::new(),
+            config,
+            last_sent: Instant::now(),
+        })
+    }
+
+    /// Sends a line to the server.
+    pub fn send(&mut self, line: &str) -> Result<(), Error> {
+        self.stream.write_all(format!("{line}\n").as_bytes())?;
+        self.stream.flush()?;
+        self.last_sent = Instant::now();
+        Ok(())
+    }
+
+    /// Logs in to the server.
+    pub fn login(&mut self, name: &str, password: &str) -> Result<(), Error> {
+        self.send(&format!("LOGIN {name} {password}"))?;
+        let line = self.recv()?;
+        match line.strip_prefix("LOGIN:") {
+            Some(rest) if rest.ends_with(" OK") => Ok(()),
+            _ => Err(Error::LoginRejected(line)),
+        }
+    }
+
+    /// Logs out from the server.
+    pub fn logout(&mut self) -> Result<(), Error> {
+        self.send("LOGOUT")?;
+        loop {
+            if self.recv()?.starts_with("LOGOUT:") {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Waits for the next game proposed by the server.
+    pub fn wait_game(&mut self) -> Result<GameSummary, Error> {
+        while self.recv()? != "BEGIN Game_Summary" {}
+
+        let mut lines = vec!["BEGIN Game_Summary".to_string()];
+        loop {
+            let line = self.recv()?;
+            let end = line == "END Game_Summary";
+            lines.push(line);
+            if end {
+                return GameSummary::parse(&lines.join("\n"));
+            }
+        }
+    }
+
+    /// Accepts the game, and waits until it starts.
+    /// Returns false if the game was rejected by the opponent.
+    pub fn agree(&mut self, summary: &GameSummary) -> Result<bool, Error> {
+        self.send(&format!("AGREE {}", summary.game_id))?;
+        loop {
+            let line = self.recv()?;
+            if line.starts_with("START:") {
+                return Ok(true);
+            }
+            if line.starts_with("REJECT:") {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Declines the game.
+    pub fn reject(&mut self, summary: &GameSummary) -> Result<(), Error> {
+        self.send(&format!("REJECT {}", summary.game_id))?;
+        loop {
+            if self.recv()?.starts_with("REJECT:") {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Plays the game with the engine until the server announces the result,
+    /// and returns its record.
+    ///
+    /// Time is charged as reported by the server, so the engine is given the same remaining time
+    /// as the server counts, minus the margin.
+    pub fn play(
+        &mut self,
+        summary: &GameSummary,
+        engine: &mut EnginePlayer,
+    ) -> Result<GameRecord, Error> {
+        let mut pos = Position::from_sfen(&summary.start_sfen)?;
+        let mut clock = GameClock::new(summary.time_control);
+        let mut record = GameRecord {
+            black_name: summary.black_name.clone(),
+            white_name: summary.white_name.clone(),
+            time_control: Some(summary.time_control),
+            start_sfen: pos.to_sfen(),
+            ..Default::default()
+        };
+        for m in &summary.moves {
+            clock.consume(pos.side_to_move(), m.elapsed.unwrap_or_default());
+            pos.make_move(&m.mv)?;
+            record.moves.push(m.clone());
+        }
+
+        engine.prepare(self.config.ready_timeout)?;
+        engine.send_command(&GuiCommand::UsiNewGame)?;
+
+        let mut reason = None;
+        let mut thinking = false;
+        let mut score = None;
+        let result = loop {
+            if !thinking && reason.is_none() && pos.side_to_move() == summary.color {
+                thinking = true;
+                if let Some(search) = self.think(engine, &record, &pos, &clock)? {
+                    let message = self.move_message(&pos, &search);
+                    self.send(&message)?;
+                    score = search.score;
+                }
+            }
+
+            let line = self.recv()?;
+            if let Some(tag) = line.strip_prefix('#') {
+                let (winner, default_reason) = match tag {
+                    "WIN" => (Some(summary.color), GameEndReason::Resign),
+                    "LOSE" => (Some(summary.color.flip()), GameEndReason::Resign),
+                    "DRAW" => (None, GameEndReason::Repetition),
+                    "CHUDAN" | "CENSORED" => (None, GameEndReason::Disconnected),
+                    _ => {
+                        reason = Some(tag.to_string());
+                        continue;
+                    }
+                };
+                break GameResult {
+                    winner,
+                    reason: reason
+                        .as_deref()
+                        .map_or(default_reason, |r| end_reason(r, winner.is_some())),
+                };
+            }
+            if !line.starts_with(['+', '-']) {
+                continue;
+            }
+
+            let side = pos.side_to_move();
+            let (mv, elapsed) = parse_move_message(&pos, &line, summary.time_unit)
+                .ok_or_else(|| Error::UnexpectedServerMessage(line.clone()))?;
+            pos.make_move(&mv)?;
+            clock.consume(side, elapsed.unwrap_or_default());
+            record.moves.push(MoveRecord {
+                elapsed,
+                score: if side == summary.color {
+                    score.take()
+                } else {
+                    None
+                },
+                ..MoveRecord::new(mv)
+            });
+            thinking = false;
+        };
+
+        engine.send_command(&GuiCommand::GameOver(result.game_over_kind(summary.color)))?;
+        record.result = Some(result);
+        Ok(record)
+    }
+
+    /// Lets the engine think on the current position, and waits for `bestmove`
+    /// while keeping messages from the server.
+    /// Returns `None` if the game ended before the engine answered.
+    fn think(
+        &mut self,
+        engine: &mut EnginePlayer,
+        record: &GameRecord,
+        pos: &Position,
+        clock: &GameClock,
+    ) -> Result<Option<Search>, Error> {
+        engine.send_command(&record.position_command(record.moves.len()))?;
+        engine.send_command(&GuiCommand::Go(think_params(
+            clock,
+            pos.side_to_move(),
+            self.config.margin,
+        )))?;
+
+        let mut score = None;
+        let mut pv = Vec::new();
+        let mut stopped: Option<Instant> = None;
+        loop {
+            if stopped.is_none() {
+                while let Ok(line) = self.receiver.try_recv() {
+                    let end = line.starts_with('#');
+                    self.pending.push_back(line);
+                    if end {
+                        engine.send_command(&GuiCommand::Stop)?;
+                        stopped = Some(Instant::now());
+                    }
+                }
+                self.keepalive()?;
+            }
+
+            let deadline = match stopped {
+                Some(at) => at + self.config.ready_timeout,
+                None => Instant::now() + POLL_INTERVAL,
+            };
+            let output = match engine.recv_deadline(deadline)? {
+                Some(output) => output,
+                None if stopped.is_some() => return Ok(None),
+                None => continue,
+            };
+
+            match output.response() {
+                Some(EngineCommand::Info(params)) => {
+                    for p in params {
+                        match p {
+                            InfoParams::Score(v, kind) => score = Some((*v, kind.clone())),
+                            InfoParams::Pv(moves) => pv = moves.clone(),
+                            _ => {}
+                        }
+                    }
+                }
+                Some(EngineCommand::BestMove(_)) if stopped.is_some() => return Ok(None),
+                Some(EngineCommand::BestMove(best_move)) => {
+                    return Ok(Some(Search {
+                        best_move: best_move.clone(),
+                        score,
+                        pv,
+                    }));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Returns the message to send the result of the search.
+    fn move_message(&self, pos: &Position, search: &Search) -> String {
+        let m = match search.best_move {
+            BestMoveParams::Resign => return "%TORYO".to_string(),
+            BestMoveParams::Win => return "%KACHI".to_string(),
+            BestMoveParams::MakeMove(ref m, _) => m,
+        };
+        // An unparsable move is sent as is, to be judged as illegal by the server.
+        let m = match m.parse::<Move>() {
+            Ok(m) if pos.is_legal(&m) => m,
+            _ => return m.clone(),
+        };
+
+        let mut message = format_move(pos, &m);
+        let score = MoveRecord {
+            score: search.score.clone(),
+            ..MoveRecord::new(m)
+        }
+        .score_cp();
+        if let (true, Some(score)) = (self.config.evaluation_comments, score) {
+            // Scores are written from the point of view of black.
+            let score = match pos.side_to_move() {
+                Color::Black => score,
+                Color::White => -score,
+            };
+            message.push_str(&format!(",'* {score}"));
+
+            let mut pos = pos.clone();
+            for m in &search.pv {
+                match m.parse::<Move>() {
+                    Ok(m) if pos.is_legal(&m) => {
+                        message.push(' ');
+                        message.push_str(&format_move(&pos, &m));
+                        pos.make_move(&m).expect("the move must be legal");
+                    }
+                    _ => break,
+                }
+            }
+        }
+        message
+    }
+
+    /// Returns the next line from the server except empty lines for keepalive,
+    /// sending keepalive while waiting.
+    fn recv(&mut self) -> Result<String, Error> {
+        if let Some(line) = self.pending.pop_front() {
+            return Ok(line);
+        }
+        loop {
+            let timeout = if self.config.keepalive.is_zero() {
+                Duration::MAX
+            } else {
+                (self.last_sent + self.config.keepalive).saturating_duration_since(Instant::now())
+            };
+            match self.receiver.recv_timeout(timeout) {
+                Ok(line) if line.is_empty() => {}
+                Ok(line) => return Ok(line),
+                Err(RecvTimeoutError::Timeout) => self.keepalive()?,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+                }
+            }
+        }
+    }
+
+    /// Sends an empty line if nothing was sent for the keepalive interval.
+    fn keepalive(&mut self) -> Result<(), Error> {
+        if !self.config.keepalive.is_zero() && self.last_sent.elapsed() >= self.config.keepalive {
+            self.send("")?;
+        }
+        Ok(())
+    }
+}
+
+/// Represents an outcome of a single `go` command.
+struct Search {
+    best_move: BestMoveParams,
+    score: Option<(i32, ScoreKind)>,
+    pv: Vec<String>,
+}
+
+/// Returns the parameters of `go` command, with the margin subtracted for the side to move.
+fn think_params(clock: &GameClock, side: Color, margin: Duration) -> ThinkParams {
+    let params = clock.think_params();
+    // The part of the margin which byoyomi cannot cover is taken from the main time.
+    let (params, margin) = match *clock.control() {
+        TimeControl::Byoyomi { byoyomi, .. } => (
+            params.byoyomi(byoyomi.saturating_sub(margin)),
+            margin.saturating_sub(byoyomi),
+        ),
+        TimeControl::FixedPerMove(t) => return params.byoyomi(t.saturating_sub(margin)),
+        TimeControl::Fischer { .. } => (params, margin),
+    };
+    let remaining = clock.remaining(side).saturating_sub(margin);
+    match side {
+        Color::Black => params.btime(remaining),
+        Color::White => params.wtime(remaining),
+    }
+}
+
+/// Returns the reason of the game end announced by the server, such as `#RESIGN`.
+fn end_reason(tag: &str, decisive: bool) -> GameEndReason {
+    match tag {
+        "RESIGN" => GameEndReason::Resign,
+        "TIME_UP" => GameEndReason::Timeout,
+        "ILLEGAL_MOVE" => GameEndReason::IllegalMove,
+        "SENNICHITE" => GameEndReason::Repetition,
+        "OUTE_SENNICHITE" => GameEndReason::PerpetualCheck,
+        "JISHOGI" if decisive => GameEndReason::DeclareWin,
+        "JISHOGI" => GameEndReason::Adjudicated(Adjudication::Impasse),
+        "MAX_MOVES" => GameEndReason::MaxMoves,
+        _ => GameEndReason::Disconnected,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::process::testing::{spawn_fake_engine, spawn_silent_engine};
+
+    enum Step {
+        Send(&'static str),
+        Expect(&'static str),
+    }
+    use Step::*;
+
+    /// Runs a stand-in CSA server which follows the script, and returns lines from the client.
+    fn serve(script: Vec<Step>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+            let mut received = Vec::new();
+            for step in script {
+                match step {
+                    Send(line) => writeln!(stream, "{line}").unwrap(),
+                    Expect(prefix) => {
+                        let line = lines
+                            .by_ref()
+                            .map(Result::unwrap)
+                            .find(|l| !l.is_empty())
+                            .unwrap();
+                        assert!(line.starts_with(prefix), "{line}");
+                        received.push(line);
+                    }
+                }
+            }
+            received
+        });
+        (addr, handle)
+    }
+
+    fn summary(id: &'static str, turn: &'static str, moves: &'static str) -> Vec<Step> {
+        [
+            "BEGIN Game_Summary",
+            "Protocol_Version:1.2",
+            id,
+            "Name+:alice",
+            "Name-:bob",
+            turn,
+            "BEGIN Time",
+            "Time_Unit:1sec",
+            "Total_Time:60",
+            "Byoyomi:10",
+            "END Time",
+            "BEGIN Position",
+            "PI",
+            "+",
+            moves,
+            "END Position",
+            "END Game_Summary",
+        ]
+        .into_iter()
+        .filter(|l| !l.is_empty())
+        .map(Send)
+        .collect()
+    }
+
+    #[test]
+    fn margin() {
+        let secs = Duration::from_secs;
+        let cases = [
+            (
+                TimeControl::Byoyomi {
+                    time: secs(60),
+                    byoyomi: secs(10),
+                },
+                ThinkParams::new()
+                    .btime(secs(60))
+                    .wtime(secs(60))
+                    .byoyomi(secs(9)),
+            ),
+            // Sudden death takes the margin from the main time.
+            (
+                TimeControl::Byoyomi {
+                    time: secs(60),
+                    byoyomi: Duration::ZERO,
+                },
+                ThinkParams::new()
+                    .btime(secs(59))
+                    .wtime(secs(60))
+                    .byoyomi(Duration::ZERO),
+            ),
+            (
+                TimeControl::Fischer {
+                    time: secs(60),
+                    increment: secs(5),
+                },
+                ThinkParams::new()
+                    .btime(secs(59))
+                    .wtime(secs(60))
+                    .binc(secs(5))
+                    .winc(secs(5)),
+            ),
+            (
+                TimeControl::FixedPerMove(secs(10)),
+                ThinkParams::new()
+                    .btime(Duration::ZERO)
+                    .wtime(Duration::ZERO)
+                    .byoyomi(secs(9)),
+            ),
+        ];
+
+        for (control, params) in cases {
+            let clock = GameClock::new(control);
+            assert_eq!(
+                params,
+                think_params(&clock, Color::Black, secs(1)),
+                "{control:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn play() {
+        let mut script = vec![Expect("LOGIN alice pass"), Send("LOGIN:alice OK")];
+        script.extend(summary("Game_ID:g1", "Your_Turn:+", ""));
+        script.extend([
+            Expect("AGREE g1"),
+            Send("START:g1"),
+            Expect("+7776FU,'* 0"),
+            Send("+7776FU,T1"),
+            Send("-3334FU,T2"),
+            Expect("+2726FU"),
+            Send("+2726FU,T1"),
+            Send("%TORYO,T3"),
+            Send("#RESIGN"),
+            Send("#WIN"),
+        ]);
+        // A resumed game where the engine resigns.
+        script.extend(summary("Game_ID:g2", "Your_Turn:-", "+7776FU,T3"));
+        script.extend([
+            Expect("AGREE g2"),
+            Send("START:g2"),
+            Expect("%TORYO"),
+            Send("%TORYO,T0"),
+            Send("#RESIGN"),
+            Send("#LOSE"),
+        ]);
+        // The game ends while the engine is thinking.
+        script.extend(summary("Game_ID:g3", "Your_Turn:+", ""));
+        script.extend([
+            Expect("AGREE g3"),
+            Send("START:g3"),
+            Send("#TIME_UP"),
+            Send("#LOSE"),
+            Expect("LOGOUT"),
+            Send("LOGOUT:completed"),
+        ]);
+        let (addr, server) = serve(script);
+
+        let config = CsaConfig::new()
+            .evaluation_comments(true)
+            .ready_timeout(Duration::from_millis(500));
+        let mut client = CsaClient::connect(addr, config).unwrap();
+        client.login("alice", "pass").unwrap();
+
+        let mut engine = EnginePlayer::new(spawn_fake_engine("alice", &["7g7f", "2g2f"])).unwrap();
+        let summary = client.wait_game().unwrap();
+        assert!(client.agree(&summary).unwrap());
+        let record = client.play(&summary, &mut engine).unwrap();
+        assert_eq!(
+            vec!["7g7f", "3c3d", "2g2f"],
+            record
+                .moves
+                .iter()
+                .map(|m| m.mv.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some((0, ScoreKind::CpExact)), record.moves[0].score);
+        assert_eq!(None, record.moves[1].score);
+        assert_eq!(Some(Duration::from_secs(2)), record.moves[1].elapsed);
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: GameEndReason::Resign,
+            }),
+            record.result
+        );
+
+        let mut engine = EnginePlayer::new(spawn_fake_engine("resigner", &[])).unwrap();
+        let summary = client.wait_game().unwrap();
+        assert!(client.agree(&summary).unwrap());
+        let record = client.play(&summary, &mut engine).unwrap();
+        assert_eq!(1, record.moves.len());
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: GameEndReason::Resign,
+            }),
+            record.result
+        );
+
+        let mut engine = EnginePlayer::new(spawn_silent_engine()).unwrap();
+        let summary = client.wait_game().unwrap();
+        assert!(client.agree(&summary).unwrap());
+        let record = client.play(&summary, &mut engine).unwrap();
+        assert!(record.moves.is_empty());
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::White),
+                reason: GameEndReason::Timeout,
+            }),
+            record.result
+        );
+
+        client.logout().unwrap();
+        let received = server.join().unwrap();
+        assert_eq!("+7776FU,'* 0", received[2]);
+        assert_eq!("+2726FU,'* 0", received[3]);
+    }
+}
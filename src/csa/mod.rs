@@ -0,0 +1,5 @@
+mod client;
+mod summary;
+
+pub use self::client::{CsaClient, CsaConfig};
+pub use self::summary::GameSummary;
@@ -0,0 +1,302 @@
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::game::{MoveRecord, TimeControl};
+use crate::kifu::csa::{parse_move, time_control, Setup};
+use crate::shogi::{Color, Move, Position};
+
+/// Represents `Game_Summary` sent by a CSA server to propose a game.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GameSummary {
+    pub game_id: String,
+    pub black_name: String,
+    pub white_name: String,
+    /// The color this client plays.
+    pub color: Color,
+    pub time_control: TimeControl,
+    /// The shortest time charged for a move.
+    pub least_time_per_move: Duration,
+    /// The unit of the time spent in move messages, given by `Time_Unit`.
+    pub time_unit: Duration,
+    /// The number of moves after which the game is a draw, if limited.
+    pub max_moves: Option<usize>,
+    /// SFEN of the position where the game started.
+    pub start_sfen: String,
+    /// Moves already played, such as when the game is resumed.
+    pub moves: Vec<MoveRecord>,
+}
+
+impl GameSummary {
+    /// Parses the lines from `BEGIN Game_Summary` to `END Game_Summary`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use usi::{Color, GameSummary};
+    ///
+    /// let summary = GameSummary::parse(
+    ///     "BEGIN Game_Summary\nGame_ID:g1\nName+:alice\nName-:bob\nYour_Turn:-\n\
+    ///      BEGIN Position\nPI\n+\n+7776FU,T3\nEND Position\nEND Game_Summary\n",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(Color::White, summary.color);
+    /// assert_eq!("7g7f", summary.moves[0].mv.to_string());
+    /// ```
+    pub fn parse(s: &str) -> Result<GameSummary, Error> {
+        let err = |line: &str| Error::UnexpectedServerMessage(line.to_string());
+        let mut game_id = String::new();
+        let mut names = [None, None];
+        let mut color = None;
+        let mut max_moves = None;
+        let mut time = Time::default();
+        let mut setup = Setup::new();
+        let mut pos: Option<Position> = None;
+        let mut start_sfen = String::new();
+        let mut moves = Vec::new();
+        let mut block = "";
+
+        for line in s.lines().map(str::trim_end) {
+            if let Some(name) = line.strip_prefix("BEGIN ") {
+                block = name;
+                continue;
+            }
+            if line.starts_with("END ") {
+                block = "";
+                continue;
+            }
+
+            if block == "Position" {
+                if line.starts_with('P') {
+                    setup.parse(line).ok_or_else(|| err(line))?;
+                    continue;
+                }
+                match pos.as_mut() {
+                    None => {
+                        let side = match line {
+                            "+" => Color::Black,
+                            "-" => Color::White,
+                            _ => return Err(err(line)),
+                        };
+                        let start = Position::from_sfen(&setup.to_sfen(side))?;
+                        start_sfen = start.to_sfen();
+                        pos = Some(start);
+                    }
+                    Some(current) => {
+                        let (mv, elapsed) = parse_move_message(current, line, time.unit)
+                            .ok_or_else(|| err(line))?;
+                        current.make_move(&mv)?;
+                        moves.push(MoveRecord {
+                            elapsed,
+                            ..MoveRecord::new(mv)
+                        });
+                    }
+                }
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            match (block, key) {
+                // Time controls of each player are not supported.
+                ("Time+" | "Time-", _) => {}
+                ("Time", _) => time.set(key, value).ok_or_else(|| err(line))?,
+                (_, "Game_ID") => game_id = value.to_string(),
+                (_, "Name+") => names[0] = Some(value.to_string()),
+                (_, "Name-") => names[1] = Some(value.to_string()),
+                (_, "Your_Turn") => {
+                    color = Some(match value {
+                        "+" => Color::Black,
+                        "-" => Color::White,
+                        _ => return Err(err(line)),
+                    })
+                }
+                (_, "Max_Moves") => {
+                    let n = value.parse().map_err(|_| err(line))?;
+                    max_moves = (n > 0).then_some(n);
+                }
+                _ => {}
+            }
+        }
+
+        let [black_name, white_name] = names;
+        Ok(GameSummary {
+            game_id,
+            black_name: black_name.ok_or_else(|| err("missing Name+"))?,
+            white_name: white_name.ok_or_else(|| err("missing Name-"))?,
+            color: color.ok_or_else(|| err("missing Your_Turn"))?,
+            time_control: time.control(),
+            least_time_per_move: time.unit * time.least,
+            time_unit: time.unit,
+            max_moves,
+            start_sfen: if pos.is_some() {
+                start_sfen
+            } else {
+                Position::startpos().to_sfen()
+            },
+            moves,
+        })
+    }
+}
+
+/// Parses a move message such as `+7776FU,T3`, and returns the move and the time spent,
+/// which is counted in `unit`.
+pub(crate) fn parse_move_message(
+    pos: &Position,
+    s: &str,
+    unit: Duration,
+) -> Option<(Move, Option<Duration>)> {
+    let mut fields = s.split(',');
+    let mv = parse_move(pos, fields.next()?)?;
+    let mut elapsed = None;
+    for field in fields {
+        if let Some(t) = field.strip_prefix('T') {
+            elapsed = Some(unit.checked_mul(t.parse().ok()?)?);
+        }
+    }
+    Some((mv, elapsed))
+}
+
+/// Values in `BEGIN Time` block, counted in `unit`.
+struct Time {
+    unit: Duration,
+    total: u32,
+    byoyomi: u32,
+    increment: u32,
+    least: u32,
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Time {
+            unit: Duration::from_secs(1),
+            total: 0,
+            byoyomi: 0,
+            increment: 0,
+            least: 0,
+        }
+    }
+}
+
+impl Time {
+    fn set(&mut self, key: &str, value: &str) -> Option<()> {
+        match key {
+            "Time_Unit" => {
+                let digits = value
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(value.len());
+                let n = value[..digits].parse::<u64>().ok()?;
+                self.unit = match &value[digits..] {
+                    "sec" => Duration::from_secs(n),
+                    "min" => Duration::from_secs(n * 60),
+                    "msec" => Duration::from_millis(n),
+                    _ => return None,
+                };
+            }
+            "Total_Time" => self.total = value.parse().ok()?,
+            "Byoyomi" => self.byoyomi = value.parse().ok()?,
+            "Increment" => self.increment = value.parse().ok()?,
+            "Least_Time_Per_Move" => self.least = value.parse().ok()?,
+            _ => {}
+        }
+        Some(())
+    }
+
+    fn control(&self) -> TimeControl {
+        time_control(
+            self.unit * self.total,
+            self.unit * self.byoyomi,
+            self.unit * self.increment,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let summary = GameSummary::parse(
+            "\
+BEGIN Game_Summary
+Protocol_Version:1.2
+Protocol_Mode:Server
+Format:Shogi 1.0
+Declaration:Jishogi 1.1
+Game_ID:wdoor+floodgate-300-10F+alice+bob+20240102100000
+Name+:alice
+Name-:bob
+Your_Turn:+
+Rematch_On_Draw:NO
+To_Move:-
+Max_Moves:256
+BEGIN Time
+Time_Unit:1sec
+Total_Time:300
+Byoyomi:0
+Increment:10
+Least_Time_Per_Move:0
+END Time
+BEGIN Position
+P1-KY-KE-GI-KI-OU-KI-GI-KE-KY
+P2 * -HI *  *  *  *  * -KA *
+P3-FU-FU-FU-FU-FU-FU-FU-FU-FU
+P4 *  *  *  *  *  *  *  *  *
+P5 *  *  *  *  *  *  *  *  *
+P6 *  *  *  *  *  *  *  *  *
+P7+FU+FU+FU+FU+FU+FU+FU+FU+FU
+P8 * +KA *  *  *  *  * +HI *
+P9+KY+KE+GI+KI+OU+KI+GI+KE+KY
+P+
+P-
++
++2726FU,T12
+END Position
+END Game_Summary
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            "wdoor+floodgate-300-10F+alice+bob+20240102100000",
+            summary.game_id
+        );
+        assert_eq!("alice", summary.black_name);
+        assert_eq!("bob", summary.white_name);
+        assert_eq!(Color::Black, summary.color);
+        assert_eq!(Some(256), summary.max_moves);
+        assert_eq!(
+            TimeControl::Fischer {
+                time: Duration::from_secs(300),
+                increment: Duration::from_secs(10),
+            },
+            summary.time_control
+        );
+        assert_eq!(Position::startpos().to_sfen(), summary.start_sfen);
+        assert_eq!(1, summary.moves.len());
+        assert_eq!("2g2f", summary.moves[0].mv.to_string());
+        assert_eq!(Some(Duration::from_secs(12)), summary.moves[0].elapsed);
+        assert_eq!(Duration::from_secs(1), summary.time_unit);
+
+        // Time spent is counted in `Time_Unit`.
+        let summary = GameSummary::parse(
+            "Name+:a\nName-:b\nYour_Turn:-\n\
+             BEGIN Time\nTime_Unit:1msec\nTotal_Time:600000\nByoyomi:10000\nEND Time\n\
+             BEGIN Position\nPI\n+\n+7776FU,T1500\nEND Position\n",
+        )
+        .unwrap();
+        assert_eq!(Duration::from_millis(1), summary.time_unit);
+        assert_eq!(Some(Duration::from_millis(1500)), summary.moves[0].elapsed);
+
+        let ng_cases = [
+            "Name+:a\nName-:b\n",
+            "Name+:a\nName-:b\nYour_Turn:x\n",
+            "Name+:a\nName-:b\nYour_Turn:+\nBEGIN Position\nPI\n+\n-3334FU\nEND Position\n",
+            "Name+:a\nName-:b\nYour_Turn:+\nBEGIN Time\nTime_Unit:1hour\nEND Time\n",
+        ];
+        for s in ng_cases {
+            assert!(GameSummary::parse(s).is_err(), "{s}");
+        }
+    }
+}
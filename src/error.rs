@@ -1,3 +1,5 @@
+use std::fmt;
+
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -5,15 +7,162 @@ pub enum Error {
     #[error("illegal USI command syntax")]
     IllegalSyntax,
 
-    #[error("illegal USI command syntax")]
+    #[error("illegal number format: {0}")]
     IllegalNumberFormat(#[from] std::num::ParseIntError),
 
+    #[error(transparent)]
+    IllegalEngineCommand(#[from] ParseError),
+
+    #[error("illegal SFEN string: {0}")]
+    IllegalSfen(String),
+
+    #[error("illegal parameters of go command: {0}")]
+    IllegalThinkParams(String),
+
+    #[error("illegal move: {0}")]
+    IllegalMove(String),
+
+    #[error("illegal game record: {0}")]
+    IllegalRecord(String),
+
+    #[error("the server rejected the login: {0}")]
+    LoginRejected(String),
+
+    #[error("unexpected message from the server: {0}")]
+    UnexpectedServerMessage(String),
+
     #[error("the engine already started listening")]
     IllegalOperation,
 
+    #[error("the engine sent bestmove without an active search")]
+    UnexpectedBestMove,
+
+    #[error("the engine sent bestmove twice for search session {0}")]
+    DuplicateBestMove(u64),
+
+    #[error("timed out waiting for a response from the engine")]
+    Timeout,
+
     #[error("IO error occurred when communicating with the engine")]
     EngineIo(#[from] std::io::Error),
 
     #[error("An error occurred inside the external handler")]
     HandlerError(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
+
+/// Represents a failure to parse a command sent from the engine, pointing to the offending token.
+///
+/// `Display` renders it as a diagnostic with a caret under the token:
+///
+/// ```
+/// use usi::{EngineCommand, Error};
+///
+/// match EngineCommand::parse("info depth 10 nodes many") {
+///     Err(Error::IllegalEngineCommand(e)) => {
+///         assert_eq!(Some(4), e.token_index());
+///         assert_eq!(Some("nodes"), e.field());
+///         assert_eq!(
+///             "expected an integer for `nodes` in `info` command, found `many`\n\
+///              info depth 10 nodes many\n\
+///              \x20                   ^^^^",
+///             e.to_string()
+///         );
+///     }
+///     r => unreachable!("unexpected {r:?}"),
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ParseError {
+    line: String,
+    token: Option<usize>,
+    offset: usize,
+    command: Option<&'static str>,
+    field: Option<&'static str>,
+    expected: &'static str,
+}
+
+impl ParseError {
+    /// Creates an error at the token starting from `offset`, or at the end of the line if
+    /// `token` is `None`.
+    pub(crate) fn new(
+        line: &str,
+        token: Option<(usize, usize)>,
+        command: Option<&'static str>,
+        field: Option<&'static str>,
+        expected: &'static str,
+    ) -> ParseError {
+        let line = line.trim_end_matches(['\r', '\n']);
+        ParseError {
+            line: line.to_string(),
+            token: token.map(|(index, _)| index),
+            offset: token.map_or(line.len(), |(_, offset)| offset),
+            command,
+            field,
+            expected,
+        }
+    }
+
+    /// Returns the line which failed to be parsed, without the trailing newline.
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// Returns the index of the offending token, counting whitespace-separated tokens from zero.
+    /// `None` if the line ended before an expected token.
+    pub fn token_index(&self) -> Option<usize> {
+        self.token
+    }
+
+    /// Returns the byte offset of the offending token in the line.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the offending token.
+    pub fn found(&self) -> Option<&str> {
+        self.token
+            .and_then(|_| self.line[self.offset..].split_whitespace().next())
+    }
+
+    /// Returns the command being parsed, such as `info`.
+    pub fn command(&self) -> Option<&str> {
+        self.command
+    }
+
+    /// Returns the field being parsed, such as `depth` of `info` command.
+    pub fn field(&self) -> Option<&str> {
+        self.field
+    }
+
+    /// Returns a description of what was expected at the offending token.
+    pub fn expected(&self) -> &str {
+        self.expected
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {}", self.expected)?;
+        if let Some(field) = self.field {
+            write!(f, " for `{field}`")?;
+        }
+        if let Some(command) = self.command {
+            write!(f, " in `{command}` command")?;
+        }
+        match self.found() {
+            Some(token) => write!(f, ", found `{token}`")?,
+            None => write!(f, ", found the end of the line")?,
+        }
+
+        // Keep tabs in the indent, so that the caret is aligned with the line above.
+        let indent = self.line[..self.offset]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let width = self.found().map_or(1, |t| t.chars().count());
+        write!(f, "\n{}\n{indent}{}", self.line, "^".repeat(width))
+    }
+}
+
+impl std::error::Error for ParseError {}
@@ -0,0 +1,343 @@
+use std::fmt;
+
+use super::{GameEndReason, GameRecord, GameResult};
+use crate::shogi::{Color, PieceType, Position, Square};
+
+/// Represents why a game was adjudicated instead of played to the end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Adjudication {
+    /// Both engines agreed that one side is losing.
+    ResignScore,
+    /// Both engines kept reporting scores close to zero.
+    DrawScore,
+    /// A declaration of win by entering king was judged as a draw.
+    Impasse,
+}
+
+impl fmt::Display for Adjudication {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Adjudication::ResignScore => write!(f, "resign score"),
+            Adjudication::DrawScore => write!(f, "draw score"),
+            Adjudication::Impasse => write!(f, "impasse"),
+        }
+    }
+}
+
+/// `Adjudicator` decides the result of a game before it reaches the end.
+pub trait Adjudicator: fmt::Debug + Send + Sync {
+    /// Called after each move with the current position and the record so far.
+    /// Returns a result to end the game.
+    fn adjudicate(&self, pos: &Position, record: &GameRecord) -> Option<GameResult>;
+
+    /// Judges `bestmove win` by the side to move.
+    /// Returns `None` if this adjudicator does not judge declarations.
+    fn judge_declaration(&self, _pos: &Position) -> Option<GameResult> {
+        None
+    }
+}
+
+/// Returns the color of the player who made the `i`-th move of the record.
+fn mover(record: &GameRecord, i: usize) -> Color {
+    let first = match record.start_sfen.split_whitespace().nth(1) {
+        Some("w") => Color::White,
+        _ => Color::Black,
+    };
+    if i.is_multiple_of(2) {
+        first
+    } else {
+        first.flip()
+    }
+}
+
+/// Ends the game when both engines report a score beyond the threshold for the losing side
+/// in each of the last `plies` moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ResignAdjudicator {
+    score: i32,
+    plies: usize,
+}
+
+impl ResignAdjudicator {
+    pub fn new(score: i32, plies: usize) -> ResignAdjudicator {
+        ResignAdjudicator {
+            score,
+            plies: plies.max(1),
+        }
+    }
+}
+
+impl Adjudicator for ResignAdjudicator {
+    fn adjudicate(&self, _pos: &Position, record: &GameRecord) -> Option<GameResult> {
+        let n = record.moves.len();
+        if n < self.plies {
+            return None;
+        }
+
+        let last = record.moves[n - 1].score_cp()?;
+        let loser = if last <= -self.score {
+            mover(record, n - 1)
+        } else if last >= self.score {
+            mover(record, n - 1).flip()
+        } else {
+            return None;
+        };
+
+        let agreed = (n - self.plies..n).all(|i| match record.moves[i].score_cp() {
+            Some(v) if mover(record, i) == loser => v <= -self.score,
+            Some(v) => v >= self.score,
+            None => false,
+        });
+
+        agreed.then_some(GameResult {
+            winner: Some(loser.flip()),
+            reason: GameEndReason::Adjudicated(Adjudication::ResignScore),
+        })
+    }
+}
+
+/// Declares a draw when the absolute scores of both engines stay within the threshold
+/// for the last `plies` moves, once the game reaches `move_number` moves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct DrawAdjudicator {
+    move_number: usize,
+    score: i32,
+    plies: usize,
+}
+
+impl DrawAdjudicator {
+    pub fn new(move_number: usize, score: i32, plies: usize) -> DrawAdjudicator {
+        DrawAdjudicator {
+            move_number,
+            score,
+            plies: plies.max(1),
+        }
+    }
+}
+
+impl Adjudicator for DrawAdjudicator {
+    fn adjudicate(&self, _pos: &Position, record: &GameRecord) -> Option<GameResult> {
+        let n = record.moves.len();
+        if n < self.move_number || n < self.plies {
+            return None;
+        }
+
+        let drawish = record.moves[n - self.plies..]
+            .iter()
+            .all(|m| matches!(m.score_cp(), Some(v) if v.abs() <= self.score));
+
+        drawish.then_some(GameResult {
+            winner: None,
+            reason: GameEndReason::Adjudicated(Adjudication::DrawScore),
+        })
+    }
+}
+
+/// Represents a rule of declaration by entering king.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImpasseRule {
+    /// The declaration wins with 28 points for black and 27 points for white.
+    Point27,
+    /// The declaration wins with 31 points, and draws with 24 to 30 points.
+    Point24,
+}
+
+/// Validates `bestmove win` according to the rule of entering king.
+///
+/// The declaring player must not be in check, must have the king in the promotion zone
+/// together with at least 10 other pieces, and must have enough points,
+/// counting 5 points for a rook or bishop and 1 point for others in the zone and in hand.
+/// An invalid declaration loses the game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ImpasseAdjudicator {
+    rule: ImpasseRule,
+}
+
+impl ImpasseAdjudicator {
+    pub fn new(rule: ImpasseRule) -> ImpasseAdjudicator {
+        ImpasseAdjudicator { rule }
+    }
+}
+
+/// Returns the declaration points and the number of pieces other than the king in the zone.
+fn declaration_points(pos: &Position, color: Color) -> (u32, u32) {
+    let value = |pt: PieceType| match pt.unpromote() {
+        PieceType::Rook | PieceType::Bishop => 5,
+        _ => 1,
+    };
+
+    let mut points = 0;
+    let mut pieces = 0;
+    for sq in Square::iter().filter(|sq| sq.in_promotion_zone(color)) {
+        match pos.piece_at(sq) {
+            Some(p) if p.color == color && p.piece_type != PieceType::King => {
+                points += value(p.piece_type);
+                pieces += 1;
+            }
+            _ => {}
+        }
+    }
+    for pt in PieceType::HAND_TYPES {
+        points += value(pt) * pos.hand(color, pt) as u32;
+    }
+    (points, pieces)
+}
+
+impl Adjudicator for ImpasseAdjudicator {
+    fn adjudicate(&self, _pos: &Position, _record: &GameRecord) -> Option<GameResult> {
+        None
+    }
+
+    fn judge_declaration(&self, pos: &Position) -> Option<GameResult> {
+        let side = pos.side_to_move();
+        let lose = GameResult {
+            winner: Some(side.flip()),
+            reason: GameEndReason::IllegalMove,
+        };
+
+        let entered = pos
+            .king_square(side)
+            .is_some_and(|sq| sq.in_promotion_zone(side));
+        let (points, pieces) = declaration_points(pos, side);
+        if !entered || pieces < 10 || pos.in_check(side) {
+            return Some(lose);
+        }
+
+        let (win, draw) = match (self.rule, side) {
+            (ImpasseRule::Point27, Color::Black) => (28, 28),
+            (ImpasseRule::Point27, Color::White) => (27, 27),
+            (ImpasseRule::Point24, _) => (31, 24),
+        };
+        Some(if points >= win {
+            GameResult {
+                winner: Some(side),
+                reason: GameEndReason::DeclareWin,
+            }
+        } else if points >= draw {
+            GameResult {
+                winner: None,
+                reason: GameEndReason::Adjudicated(Adjudication::Impasse),
+            }
+        } else {
+            lose
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::MoveRecord;
+    use crate::protocol::ScoreKind;
+    use crate::shogi::{Move, STARTPOS_SFEN};
+
+    fn record(scores: &[i32]) -> GameRecord {
+        let moves = ["7g7f", "3c3d", "2g2f", "8c8d", "2f2e", "8d8e"];
+        GameRecord {
+            start_sfen: STARTPOS_SFEN.to_string(),
+            moves: scores
+                .iter()
+                .zip(moves)
+                .map(|(v, m)| MoveRecord {
+                    score: Some((*v, ScoreKind::CpExact)),
+                    ..MoveRecord::new(m.parse::<Move>().unwrap())
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn resign() {
+        let adj = ResignAdjudicator::new(1000, 4);
+        let pos = Position::startpos();
+
+        let white_wins = Some(GameResult {
+            winner: Some(Color::White),
+            reason: GameEndReason::Adjudicated(Adjudication::ResignScore),
+        });
+        assert_eq!(
+            white_wins,
+            adj.adjudicate(&pos, &record(&[0, 0, -1200, 1500, -1000, 1000]))
+        );
+        assert_eq!(
+            None,
+            adj.adjudicate(&pos, &record(&[0, 0, -1200, 1500, -999, 1000]))
+        );
+        assert_eq!(None, adj.adjudicate(&pos, &record(&[-1200, 1500, -1000])));
+
+        // Mate scores are beyond any threshold.
+        let mut r = record(&[-1200, 1500, -1000, 0]);
+        r.moves[3].score = Some((5, ScoreKind::MateExact));
+        assert_eq!(white_wins, adj.adjudicate(&pos, &r));
+    }
+
+    #[test]
+    fn draw() {
+        let adj = DrawAdjudicator::new(4, 10, 3);
+        let pos = Position::startpos();
+
+        let drawn = Some(GameResult {
+            winner: None,
+            reason: GameEndReason::Adjudicated(Adjudication::DrawScore),
+        });
+        assert_eq!(drawn, adj.adjudicate(&pos, &record(&[500, 0, 10, -10])));
+        assert_eq!(None, adj.adjudicate(&pos, &record(&[0, 0, 0])));
+        assert_eq!(None, adj.adjudicate(&pos, &record(&[0, 0, 11, 0])));
+    }
+
+    #[test]
+    fn declaration() {
+        let cases = [
+            // 28 points for black: R, B and 8 pieces in the zone, 10 pawns in hand.
+            (
+                "LNSGKGSN1/9/RB6L/9/9/9/9/6k2/9 b 10P 1",
+                ImpasseRule::Point27,
+                Some(Color::Black),
+                GameEndReason::DeclareWin,
+            ),
+            (
+                "LNSGKGSN1/9/RB6L/9/9/9/9/6k2/9 b 9P 1",
+                ImpasseRule::Point27,
+                Some(Color::White),
+                GameEndReason::IllegalMove,
+            ),
+            (
+                "LNSGKGSN1/9/RB6L/9/9/9/9/6k2/9 b 9P 1",
+                ImpasseRule::Point24,
+                None,
+                GameEndReason::Adjudicated(Adjudication::Impasse),
+            ),
+            // Only 9 pieces in the zone.
+            (
+                "LNSGKGS2/9/RB6L/9/9/9/9/6k2/9 b 10P 1",
+                ImpasseRule::Point24,
+                Some(Color::White),
+                GameEndReason::IllegalMove,
+            ),
+            // The king is not in the zone.
+            (
+                "LNSG1GSNL/9/RB7/4K4/9/9/9/6k2/9 b 10P 1",
+                ImpasseRule::Point27,
+                Some(Color::White),
+                GameEndReason::IllegalMove,
+            ),
+            // In check.
+            (
+                "LNSGKGSNL/4r4/RB7/9/9/9/9/6k2/9 b 10P 1",
+                ImpasseRule::Point27,
+                Some(Color::White),
+                GameEndReason::IllegalMove,
+            ),
+        ];
+
+        for (sfen, rule, winner, reason) in cases {
+            let pos = Position::from_sfen(sfen).unwrap();
+            assert_eq!(
+                Some(GameResult { winner, reason }),
+                ImpasseAdjudicator::new(rule).judge_declaration(&pos),
+                "{sfen}"
+            );
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::time::{Duration, Instant};
+
+use crate::protocol::ThinkParams;
+use crate::shogi::Color;
+
+/// Represents a time control of a game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TimeControl {
+    /// Main time followed by a fixed time for each move once the main time runs out.
+    Byoyomi { time: Duration, byoyomi: Duration },
+    /// Main time with an increment added after each move.
+    Fischer { time: Duration, increment: Duration },
+    /// Fixed time for each move.
+    FixedPerMove(Duration),
+}
+
+impl TimeControl {
+    fn main_time(&self) -> Duration {
+        match *self {
+            TimeControl::Byoyomi { time, .. } => time,
+            TimeControl::Fischer { time, .. } => time,
+            TimeControl::FixedPerMove(_) => Duration::ZERO,
+        }
+    }
+}
+
+/// Represents a player who ran out of time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TimeForfeit {
+    pub color: Color,
+    pub elapsed: Duration,
+    pub allowed: Duration,
+}
+
+/// `GameClock` keeps the remaining time of both players.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, Instant};
+/// use usi::{Color, GameClock, TimeControl};
+///
+/// let mut clock = GameClock::new(TimeControl::Byoyomi {
+///     time: Duration::from_secs(60),
+///     byoyomi: Duration::from_secs(10),
+/// });
+/// assert_eq!(
+///     "go btime 60000 wtime 60000 byoyomi 10000",
+///     format!("go{}", clock.think_params())
+/// );
+///
+/// let start = Instant::now();
+/// clock.start_at(Color::Black, start);
+/// assert!(clock.stop_at(start + Duration::from_secs(65)).is_ok());
+/// assert_eq!(Duration::ZERO, clock.remaining(Color::Black));
+///
+/// clock.start_at(Color::White, start);
+/// assert!(clock.stop_at(start + Duration::from_secs(71)).is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct GameClock {
+    control: TimeControl,
+    remaining: [Duration; 2],
+    margin: Duration,
+    running: Option<(Color, Instant)>,
+}
+
+impl GameClock {
+    pub fn new(control: TimeControl) -> GameClock {
+        GameClock {
+            control,
+            remaining: [control.main_time(); 2],
+            margin: Duration::ZERO,
+            running: None,
+        }
+    }
+
+    /// Sets the extra time tolerated to absorb network and process overhead.
+    #[must_use]
+    pub fn margin(mut self, t: Duration) -> Self {
+        self.margin = t;
+        self
+    }
+
+    /// Returns the time control.
+    pub fn control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    /// Returns the remaining main time of the player.
+    pub fn remaining(&self, color: Color) -> Duration {
+        self.remaining[color.index()]
+    }
+
+    /// Returns the longest time the player can think on the next move without margin.
+    pub fn allowed(&self, color: Color) -> Duration {
+        let remaining = self.remaining(color);
+        match self.control {
+            TimeControl::Byoyomi { byoyomi, .. } => remaining + byoyomi,
+            TimeControl::Fischer { .. } => remaining,
+            TimeControl::FixedPerMove(t) => t,
+        }
+    }
+
+    /// Returns the parameters of `go` command for the next move.
+    pub fn think_params(&self) -> ThinkParams {
+        let params = ThinkParams::new()
+            .btime(self.remaining(Color::Black))
+            .wtime(self.remaining(Color::White));
+
+        match self.control {
+            TimeControl::Byoyomi { byoyomi, .. } => params.byoyomi(byoyomi),
+            TimeControl::Fischer { increment, .. } => params.binc(increment).winc(increment),
+            TimeControl::FixedPerMove(t) => params.byoyomi(t),
+        }
+    }
+
+    /// Starts the clock of the player now.
+    pub fn start(&mut self, color: Color) {
+        self.start_at(color, Instant::now());
+    }
+
+    /// Starts the clock of the player at the given time, usually when `go` is sent.
+    pub fn start_at(&mut self, color: Color, at: Instant) {
+        self.running = Some((color, at));
+    }
+
+    /// Returns the time when the running player forfeits, including the margin.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.running
+            .map(|(color, at)| at + self.allowed(color) + self.margin)
+    }
+
+    /// Stops the clock at the given time, usually `EngineOutput::timestamp` of `bestmove`,
+    /// and returns the time spent on the move.
+    /// Returns `TimeForfeit` if the player exceeded the allowed time.
+    /// Does nothing if no clock is running.
+    pub fn stop_at(&mut self, at: Instant) -> Result<Duration, TimeForfeit> {
+        let (color, start) = match self.running.take() {
+            Some(v) => v,
+            None => return Ok(Duration::ZERO),
+        };
+
+        let elapsed = at.saturating_duration_since(start);
+        let allowed = self.allowed(color);
+        if elapsed > allowed + self.margin {
+            return Err(TimeForfeit {
+                color,
+                elapsed,
+                allowed,
+            });
+        }
+
+        self.consume(color, elapsed);
+        Ok(elapsed)
+    }
+
+    /// Charges the player for a move which took `elapsed`, measured elsewhere such as by a server.
+    pub fn consume(&mut self, color: Color, elapsed: Duration) {
+        let remaining = &mut self.remaining[color.index()];
+        *remaining = remaining.saturating_sub(elapsed);
+        if let TimeControl::Fischer { increment, .. } = self.control {
+            *remaining += increment;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEC: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn byoyomi() {
+        let mut clock = GameClock::new(TimeControl::Byoyomi {
+            time: 10 * SEC,
+            byoyomi: 5 * SEC,
+        })
+        .margin(SEC);
+        let t = Instant::now();
+
+        clock.start_at(Color::Black, t);
+        assert_eq!(Some(t + 16 * SEC), clock.deadline());
+        assert_eq!(Ok(4 * SEC), clock.stop_at(t + 4 * SEC));
+        assert_eq!(6 * SEC, clock.remaining(Color::Black));
+        assert_eq!(10 * SEC, clock.remaining(Color::White));
+
+        // Byoyomi is not accumulated, but the margin is tolerated.
+        clock.start_at(Color::Black, t);
+        assert_eq!(Ok(12 * SEC), clock.stop_at(t + 12 * SEC));
+        assert_eq!(Duration::ZERO, clock.remaining(Color::Black));
+
+        clock.start_at(Color::Black, t);
+        assert_eq!(
+            Err(TimeForfeit {
+                color: Color::Black,
+                elapsed: 7 * SEC,
+                allowed: 5 * SEC,
+            }),
+            clock.stop_at(t + 7 * SEC)
+        );
+    }
+
+    #[test]
+    fn fischer() {
+        let mut clock = GameClock::new(TimeControl::Fischer {
+            time: 10 * SEC,
+            increment: 2 * SEC,
+        });
+        let t = Instant::now();
+
+        assert_eq!(
+            "go btime 10000 wtime 10000 binc 2000 winc 2000",
+            format!("go{}", clock.think_params())
+        );
+
+        clock.start_at(Color::White, t);
+        assert_eq!(Ok(3 * SEC), clock.stop_at(t + 3 * SEC));
+        assert_eq!(9 * SEC, clock.remaining(Color::White));
+
+        clock.start_at(Color::White, t);
+        assert!(clock.stop_at(t + 10 * SEC).is_err());
+        assert_eq!(Ok(Duration::ZERO), clock.stop_at(t));
+    }
+
+    #[test]
+    fn fixed_per_move() {
+        let mut clock = GameClock::new(TimeControl::FixedPerMove(3 * SEC));
+        let t = Instant::now();
+
+        assert_eq!(
+            "go btime 0 wtime 0 byoyomi 3000",
+            format!("go{}", clock.think_params())
+        );
+
+        clock.start_at(Color::Black, t);
+        assert_eq!(Ok(3 * SEC), clock.stop_at(t + 3 * SEC));
+        clock.start_at(Color::Black, t);
+        assert!(clock.stop_at(t + 4 * SEC).is_err());
+    }
+}
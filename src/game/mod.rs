@@ -0,0 +1,21 @@
+mod adjudication;
+mod clock;
+mod player;
+mod pool;
+mod record;
+mod runner;
+mod tournament;
+
+pub use self::adjudication::{
+    Adjudication, Adjudicator, DrawAdjudicator, ImpasseAdjudicator, ImpasseRule, ResignAdjudicator,
+};
+pub use self::clock::{GameClock, TimeControl, TimeForfeit};
+pub use self::player::EnginePlayer;
+pub use self::pool::{EnginePool, EnginePoolConfig, PooledEngine};
+pub(crate) use self::record::{score_to_cp, MATE_SCORE};
+pub use self::record::{GameEndReason, GameRecord, GameResult, MoveRecord};
+pub use self::runner::{Match, MatchConfig};
+pub use self::tournament::{
+    load_openings, Crosstable, EngineSpec, Schedule, Score, Tournament, TournamentConfig,
+    TournamentGame,
+};
@@ -0,0 +1,122 @@
+use std::io;
+use std::process::ExitStatus;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use crate::error::Error;
+#[cfg(target_os = "linux")]
+use crate::process::ResourceUsage;
+use crate::process::{EngineInfo, EngineOutput, UsiEngineHandler};
+use crate::protocol::{BestMoveParams, EngineCommand, GuiCommand};
+
+/// `EnginePlayer` wraps `UsiEngineHandler` to communicate with the engine synchronously.
+///
+/// Outputs from the engine are forwarded to a channel by `UsiEngineHandler::listen`,
+/// so that a game loop can wait for a specific response with a timeout.
+#[derive(Debug)]
+pub struct EnginePlayer {
+    handler: UsiEngineHandler,
+    info: EngineInfo,
+    receiver: Receiver<EngineOutput>,
+}
+
+impl EnginePlayer {
+    /// Requests metadata of the engine and starts listening to its outputs.
+    pub fn new(mut handler: UsiEngineHandler) -> Result<EnginePlayer, Error> {
+        let info = handler.get_info()?;
+        let (sender, receiver) = mpsc::channel();
+
+        handler.listen(move |output: &EngineOutput| -> Result<(), Error> {
+            if output.response().is_none() && output.raw_str().is_empty() {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            // The receiver may already be dropped, which is not an error.
+            let _ = sender.send(output.clone());
+            Ok(())
+        })?;
+
+        Ok(EnginePlayer {
+            handler,
+            info,
+            receiver,
+        })
+    }
+
+    /// Returns the engine information received in the handshake.
+    pub fn info(&self) -> &EngineInfo {
+        &self.info
+    }
+
+    /// Returns the engine name.
+    pub fn name(&self) -> &str {
+        self.info.name()
+    }
+
+    /// Sends a command to the engine.
+    pub fn send_command(&mut self, command: &GuiCommand) -> Result<(), Error> {
+        self.handler.send_command(command)
+    }
+
+    /// Sends `setoption` command to the engine.
+    pub fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), Error> {
+        self.send_command(&GuiCommand::SetOption(
+            name.to_string(),
+            value.map(str::to_string),
+        ))
+    }
+
+    /// Stops the latest search, and waits until its `bestmove` is received.
+    /// See `UsiEngineHandler::stop_and_wait` for details.
+    pub fn stop_and_wait(&mut self, timeout: Duration) -> Result<BestMoveParams, Error> {
+        self.handler.stop_and_wait(timeout)
+    }
+
+    /// Returns the exit status if the engine process has exited, without blocking.
+    pub fn try_wait(&mut self) -> Result<Option<ExitStatus>, Error> {
+        self.handler.try_wait()
+    }
+
+    /// Sends `quit` command and waits until the engine exits, killing it on timeout.
+    pub fn quit(&mut self, timeout: Duration) -> Result<ExitStatus, Error> {
+        self.handler.quit(timeout)
+    }
+
+    /// Returns resources consumed by the engine after it exited.
+    #[cfg(target_os = "linux")]
+    pub fn resource_usage(&self) -> Option<ResourceUsage> {
+        self.handler.resource_usage()
+    }
+
+    /// Sends `isready` command and waits until `readyok` is received.
+    /// Outputs received before `readyok` are discarded.
+    pub fn prepare(&mut self, timeout: Duration) -> Result<(), Error> {
+        self.send_command(&GuiCommand::IsReady)?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let output = self.recv_deadline(deadline)?.ok_or(Error::Timeout)?;
+            if let Some(EngineCommand::ReadyOk) = output.response() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Waits for the next output.
+    pub fn recv(&mut self) -> Result<EngineOutput, Error> {
+        self.receiver
+            .recv()
+            .map_err(|_| io::Error::from(io::ErrorKind::UnexpectedEof).into())
+    }
+
+    /// Waits for the next output until the deadline. Returns `None` on timeout.
+    pub fn recv_deadline(&mut self, deadline: Instant) -> Result<Option<EngineOutput>, Error> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.receiver.recv_timeout(timeout) {
+            Ok(output) => Ok(Some(output)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => {
+                Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+            }
+        }
+    }
+}
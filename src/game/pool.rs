@@ -0,0 +1,353 @@
+use std::collections::VecDeque;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+use super::player::EnginePlayer;
+use super::tournament::EngineSpec;
+use crate::error::Error;
+
+/// Represents settings of `EnginePool`.
+#[derive(Clone, Debug)]
+pub struct EnginePoolConfig {
+    size: usize,
+    ready_timeout: Duration,
+}
+
+impl EnginePoolConfig {
+    pub fn new() -> Self {
+        EnginePoolConfig::default()
+    }
+
+    /// Sets the number of engine processes kept in the pool.
+    #[must_use]
+    pub fn size(mut self, n: usize) -> Self {
+        self.size = n.max(1);
+        self
+    }
+
+    /// Sets how long to wait for `readyok`, or for `bestmove` of a search stopped on return,
+    /// before an engine is regarded as dead.
+    #[must_use]
+    pub fn ready_timeout(mut self, t: Duration) -> Self {
+        self.ready_timeout = t;
+        self
+    }
+}
+
+impl Default for EnginePoolConfig {
+    fn default() -> Self {
+        EnginePoolConfig {
+            size: 1,
+            ready_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// `EnginePool` keeps engines of the same spec initialized, and lends them out.
+///
+/// Engines are checked with `isready` whenever they are lent, which also discards outputs left
+/// by the previous borrower. A search left running by the borrower is stopped when the engine
+/// is returned, and its `bestmove` is awaited so that it does not reach the next borrower.
+/// An engine which has exited or does not answer in time is killed, and a new process is
+/// spawned and configured in its place.
+///
+/// # Examples
+///
+/// ```no_run
+/// use usi::{EnginePool, EnginePoolConfig, EngineSpec, GuiCommand};
+///
+/// let spec = EngineSpec::new("engine", "/path/to/usi_engine").option("USI_Hash", "256");
+/// let pool = EnginePool::new(spec, EnginePoolConfig::new().size(4)).unwrap();
+///
+/// let mut engine = pool.get().unwrap();
+/// engine.send_command(&GuiCommand::UsiNewGame).unwrap();
+/// // The engine returns to the pool when `engine` is dropped.
+/// ```
+#[derive(Debug)]
+pub struct EnginePool {
+    spec: EngineSpec,
+    config: EnginePoolConfig,
+    // Idle engines, with `None` in place of an engine which failed to respawn.
+    idle: Mutex<VecDeque<Option<EnginePlayer>>>,
+    available: Condvar,
+    respawns: AtomicUsize,
+}
+
+impl EnginePool {
+    /// Spawns and prepares all engines of the pool.
+    pub fn new(spec: EngineSpec, config: EnginePoolConfig) -> Result<EnginePool, Error> {
+        let mut idle = VecDeque::with_capacity(config.size);
+        for _ in 0..config.size {
+            idle.push_back(Some(spawn(&spec, config.ready_timeout)?));
+        }
+
+        Ok(EnginePool {
+            spec,
+            config,
+            idle: Mutex::new(idle),
+            available: Condvar::new(),
+            respawns: AtomicUsize::new(0),
+        })
+    }
+
+    /// Returns the number of engines respawned so far.
+    pub fn respawn_count(&self) -> usize {
+        self.respawns.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of engines not lent out.
+    pub fn idle_count(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Borrows an engine, waiting until one is returned if all of them are in use.
+    pub fn get(&self) -> Result<PooledEngine<'_>, Error> {
+        let mut idle = self.lock();
+        let slot = loop {
+            match idle.pop_back() {
+                Some(slot) => break slot,
+                None => idle = self.available.wait(idle).unwrap_or_else(|e| e.into_inner()),
+            }
+        };
+        drop(idle);
+        self.checkout(slot)
+    }
+
+    /// Borrows an engine if any of them is idle.
+    pub fn try_get(&self) -> Option<Result<PooledEngine<'_>, Error>> {
+        let slot = self.lock().pop_back()?;
+        Some(self.checkout(slot))
+    }
+
+    /// Pings idle engines with `isready`, and respawns the ones which failed.
+    /// Returns the number of engines respawned.
+    pub fn check_health(&self) -> Result<usize, Error> {
+        let mut respawned = 0;
+        for _ in 0..self.idle_count() {
+            let slot = match self.lock().pop_front() {
+                Some(slot) => slot,
+                None => break,
+            };
+            if let Some(player) = slot.and_then(|p| self.alive(p)) {
+                self.put(Some(player));
+                continue;
+            }
+
+            let player = self.respawn();
+            respawned += 1;
+            match player {
+                Ok(player) => self.put(Some(player)),
+                Err(e) => {
+                    self.put(None);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(respawned)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<Option<EnginePlayer>>> {
+        self.idle.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn put(&self, slot: Option<EnginePlayer>) {
+        self.lock().push_back(slot);
+        self.available.notify_one();
+    }
+
+    fn checkout(&self, slot: Option<EnginePlayer>) -> Result<PooledEngine<'_>, Error> {
+        let player = match slot.and_then(|p| self.alive(p)) {
+            Some(player) => Ok(player),
+            None => self.respawn(),
+        };
+
+        match player {
+            Ok(player) => Ok(PooledEngine {
+                pool: self,
+                player: Some(player),
+            }),
+            Err(e) => {
+                // Keep the slot, so that spawning is retried by the next borrower.
+                self.put(None);
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns the engine back if it is running and answers `isready` in time.
+    fn alive(&self, mut player: EnginePlayer) -> Option<EnginePlayer> {
+        let ok = matches!(player.try_wait(), Ok(None))
+            && player.prepare(self.config.ready_timeout).is_ok();
+        ok.then_some(player)
+    }
+
+    fn respawn(&self) -> Result<EnginePlayer, Error> {
+        self.respawns.fetch_add(1, Ordering::SeqCst);
+        spawn(&self.spec, self.config.ready_timeout)
+    }
+}
+
+fn spawn(spec: &EngineSpec, ready_timeout: Duration) -> Result<EnginePlayer, Error> {
+    let mut player = spec.spawn()?;
+    player.prepare(ready_timeout)?;
+    Ok(player)
+}
+
+/// An engine borrowed from `EnginePool`, which is returned to the pool when dropped.
+/// Dropping blocks until a pending search is stopped, at most for the pool's `ready_timeout`.
+#[derive(Debug)]
+pub struct PooledEngine<'a> {
+    pool: &'a EnginePool,
+    player: Option<EnginePlayer>,
+}
+
+impl PooledEngine<'_> {
+    /// Kills the engine instead of returning it to the pool, e.g. after it violated
+    /// the protocol. A new process is spawned when the pool lends it next time.
+    pub fn discard(mut self) {
+        self.player = None;
+    }
+}
+
+impl Deref for PooledEngine<'_> {
+    type Target = EnginePlayer;
+
+    fn deref(&self) -> &EnginePlayer {
+        self.player.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledEngine<'_> {
+    fn deref_mut(&mut self) -> &mut EnginePlayer {
+        self.player.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledEngine<'_> {
+    fn drop(&mut self) {
+        let timeout = self.pool.config.ready_timeout;
+        let player = self.player.take().and_then(|mut p| {
+            let idle = matches!(p.try_wait(), Ok(None))
+                && match p.stop_and_wait(timeout) {
+                    // `IllegalOperation` means the engine has never searched.
+                    Ok(_) | Err(Error::IllegalOperation) => true,
+                    Err(_) => false,
+                };
+            idle.then_some(p)
+        });
+        self.pool.put(player);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::thread;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::process::testing::{fake_engine, script_engine};
+    use crate::protocol::{GuiCommand, ThinkParams};
+
+    fn wait_exit(player: &mut EnginePlayer) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while player.try_wait().unwrap().is_none() {
+            assert!(Instant::now() < deadline, "the engine did not exit");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn lend() {
+        let spec = EngineSpec::new("fake", fake_engine("fake", &["7g7f"]));
+        let pool = EnginePool::new(spec, EnginePoolConfig::new().size(2)).unwrap();
+        assert_eq!(2, pool.idle_count());
+
+        let a = pool.get().unwrap();
+        let b = pool.try_get().unwrap().unwrap();
+        assert!(pool.try_get().is_none());
+        assert_eq!("fake", b.name());
+
+        drop(a);
+        assert_eq!(1, pool.idle_count());
+        b.discard();
+        assert_eq!(2, pool.idle_count());
+
+        // The discarded engine is respawned when lent.
+        let a = pool.get().unwrap();
+        let b = pool.get().unwrap();
+        assert_eq!(1, pool.respawn_count());
+        drop((a, b));
+        assert_eq!(0, pool.check_health().unwrap());
+    }
+
+    #[test]
+    fn respawn() {
+        // The engine crashes shortly after asked to search.
+        let spec = EngineSpec::new("crash", script_engine("crash", "(sleep 0.1; kill $$) &"));
+        let pool = EnginePool::new(spec, EnginePoolConfig::new()).unwrap();
+
+        // Crashed while idle.
+        let mut engine = pool.get().unwrap();
+        engine
+            .send_command(&GuiCommand::Go(ThinkParams::new()))
+            .unwrap();
+        drop(engine);
+        thread::sleep(Duration::from_millis(500));
+        assert_eq!(1, pool.check_health().unwrap());
+        assert_eq!(1, pool.respawn_count());
+
+        // Crashed while lent.
+        let mut engine = pool.get().unwrap();
+        engine
+            .send_command(&GuiCommand::Go(ThinkParams::new()))
+            .unwrap();
+        wait_exit(&mut engine);
+        drop(engine);
+
+        let mut engine = pool.get().unwrap();
+        assert_eq!(2, pool.respawn_count());
+        assert!(engine.try_wait().unwrap().is_none());
+        engine.prepare(Duration::from_secs(5)).unwrap();
+    }
+
+    #[test]
+    fn stop_on_return() {
+        // Answers `isready` while searching, and ignores `stop`.
+        let spec = EngineSpec::new(
+            "late",
+            script_engine("late", "(sleep 0.2; echo 'bestmove 7g7f') &"),
+        );
+        let pool = EnginePool::new(spec, EnginePoolConfig::new()).unwrap();
+
+        let mut engine = pool.get().unwrap();
+        engine
+            .send_command(&GuiCommand::Go(ThinkParams::new().infinite()))
+            .unwrap();
+        drop(engine);
+
+        // The bestmove of the previous search doesn't reach the next borrower.
+        let mut engine = pool.get().unwrap();
+        let deadline = Instant::now() + Duration::from_millis(400);
+        assert!(engine.recv_deadline(deadline).unwrap().is_none());
+        assert_eq!(0, pool.respawn_count());
+    }
+
+    #[test]
+    fn discard_on_return() {
+        // Never answers `go`.
+        let spec = EngineSpec::new("silent", script_engine("silent", ":"));
+        let config = EnginePoolConfig::new().ready_timeout(Duration::from_millis(200));
+        let pool = EnginePool::new(spec, config).unwrap();
+
+        let mut engine = pool.get().unwrap();
+        engine
+            .send_command(&GuiCommand::Go(ThinkParams::new().infinite()))
+            .unwrap();
+        drop(engine);
+
+        let _engine = pool.get().unwrap();
+        assert_eq!(1, pool.respawn_count());
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt;
+use std::time::Duration;
+
+use super::{Adjudication, TimeControl};
+use crate::protocol::{GameOverKind, GuiCommand, ScoreKind};
+use crate::shogi::{Color, Move};
+
+/// Score in centipawns which mate scores are converted to.
+pub(crate) const MATE_SCORE: i32 = 100_000;
+
+/// Represents why a game ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GameEndReason {
+    Resign,
+    Checkmate,
+    DeclareWin,
+    IllegalMove,
+    Timeout,
+    Repetition,
+    PerpetualCheck,
+    MaxMoves,
+    Disconnected,
+    Adjudicated(Adjudication),
+}
+
+impl fmt::Display for GameEndReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GameEndReason::Resign => write!(f, "resign"),
+            GameEndReason::Checkmate => write!(f, "checkmate"),
+            GameEndReason::DeclareWin => write!(f, "declaration"),
+            GameEndReason::IllegalMove => write!(f, "illegal move"),
+            GameEndReason::Timeout => write!(f, "timeout"),
+            GameEndReason::Repetition => write!(f, "repetition"),
+            GameEndReason::PerpetualCheck => write!(f, "perpetual check"),
+            GameEndReason::MaxMoves => write!(f, "max moves"),
+            GameEndReason::Disconnected => write!(f, "disconnection"),
+            GameEndReason::Adjudicated(a) => write!(f, "adjudication ({a})"),
+        }
+    }
+}
+
+/// Represents an outcome of a game. `winner` is `None` for a draw.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GameResult {
+    pub winner: Option<Color>,
+    pub reason: GameEndReason,
+}
+
+impl GameResult {
+    /// Returns the `gameover` parameter to be sent to the player of the given color.
+    pub fn game_over_kind(&self, color: Color) -> GameOverKind {
+        match self.winner {
+            Some(c) if c == color => GameOverKind::Win,
+            Some(_) => GameOverKind::Lose,
+            None => GameOverKind::Draw,
+        }
+    }
+}
+
+impl fmt::Display for GameResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.winner {
+            Some(Color::Black) => write!(f, "black win by {}", self.reason),
+            Some(Color::White) => write!(f, "white win by {}", self.reason),
+            None => write!(f, "draw by {}", self.reason),
+        }
+    }
+}
+
+/// Represents a move played in a game.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MoveRecord {
+    pub mv: Move,
+    /// Time spent for the move.
+    pub elapsed: Option<Duration>,
+    /// The last score reported by the engine, from the point of view of the player who moved.
+    pub score: Option<(i32, ScoreKind)>,
+    pub comment: Option<String>,
+    /// Alternative lines, each of which starts with a move played instead of this move.
+    pub variations: Vec<Vec<MoveRecord>>,
+}
+
+impl MoveRecord {
+    pub fn new(mv: Move) -> MoveRecord {
+        MoveRecord {
+            mv,
+            elapsed: None,
+            score: None,
+            comment: None,
+            variations: Vec::new(),
+        }
+    }
+
+    /// Returns the score in centipawns, treating mate scores as very large values.
+    pub fn score_cp(&self) -> Option<i32> {
+        score_to_cp(self.score.as_ref()?)
+    }
+}
+
+/// Converts a score into centipawns, treating mate scores as very large values.
+pub(crate) fn score_to_cp(score: &(i32, ScoreKind)) -> Option<i32> {
+    match *score {
+        (v, ScoreKind::CpExact | ScoreKind::CpLowerbound | ScoreKind::CpUpperbound) => Some(v),
+        (v, _) if v > 0 => Some(MATE_SCORE),
+        (v, _) if v < 0 => Some(-MATE_SCORE),
+        _ => None,
+    }
+}
+
+/// Represents a record of a game.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct GameRecord {
+    pub black_name: String,
+    pub white_name: String,
+    /// Other information about the game, such as the event and the date, as written in the source.
+    pub headers: Vec<(String, String)>,
+    pub time_control: Option<TimeControl>,
+    /// SFEN of the position where the game started.
+    pub start_sfen: String,
+    /// Comment on the start position.
+    pub comment: Option<String>,
+    pub moves: Vec<MoveRecord>,
+    pub result: Option<GameResult>,
+}
+
+impl GameRecord {
+    /// Returns the `position` command representing the position after the first `n` moves.
+    pub fn position_command(&self, n: usize) -> GuiCommand {
+        if n == 0 {
+            return GuiCommand::Position(self.start_sfen.clone());
+        }
+
+        let moves = self.moves[..n]
+            .iter()
+            .map(|m| m.mv.to_string())
+            .collect::<Vec<_>>();
+        GuiCommand::Position(format!("{} moves {}", self.start_sfen, moves.join(" ")))
+    }
+}
@@ -0,0 +1,455 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::{
+    Adjudicator, EnginePlayer, GameClock, GameEndReason, GameRecord, GameResult, MoveRecord,
+    TimeControl,
+};
+use crate::error::Error;
+use crate::protocol::{BestMoveParams, EngineCommand, GuiCommand, InfoParams, ScoreKind};
+use crate::shogi::{Color, Move, Position, Repetition, RepetitionTracker, STARTPOS_SFEN};
+
+/// Represents settings of games played by `Match`.
+#[derive(Clone, Debug)]
+pub struct MatchConfig {
+    pub(super) start_sfen: String,
+    time_control: TimeControl,
+    max_moves: usize,
+    margin: Duration,
+    pub(super) ready_timeout: Duration,
+    adjudicators: Vec<Arc<dyn Adjudicator>>,
+}
+
+impl MatchConfig {
+    pub fn new() -> Self {
+        MatchConfig::default()
+    }
+
+    /// Sets the start position in SFEN, optionally followed by `moves`.
+    #[must_use]
+    pub fn start_sfen(mut self, sfen: &str) -> Self {
+        self.start_sfen = sfen.to_string();
+        self
+    }
+
+    #[must_use]
+    pub fn time_control(mut self, tc: TimeControl) -> Self {
+        self.time_control = tc;
+        self
+    }
+
+    /// Sets the number of moves after which the game is declared a draw.
+    #[must_use]
+    pub fn max_moves(mut self, n: usize) -> Self {
+        self.max_moves = n;
+        self
+    }
+
+    /// Sets the extra time allowed before the player loses on time.
+    #[must_use]
+    pub fn margin(mut self, t: Duration) -> Self {
+        self.margin = t;
+        self
+    }
+
+    /// Sets how long to wait for `readyok` before starting a game.
+    #[must_use]
+    pub fn ready_timeout(mut self, t: Duration) -> Self {
+        self.ready_timeout = t;
+        self
+    }
+
+    /// Adds an adjudicator, consulted in the order of addition after each move
+    /// and on declarations of win.
+    #[must_use]
+    pub fn adjudicator<A: Adjudicator + 'static>(mut self, adjudicator: A) -> Self {
+        self.adjudicators.push(Arc::new(adjudicator));
+        self
+    }
+}
+
+impl Default for MatchConfig {
+    fn default() -> Self {
+        MatchConfig {
+            start_sfen: STARTPOS_SFEN.to_string(),
+            time_control: TimeControl::Byoyomi {
+                time: Duration::ZERO,
+                byoyomi: Duration::from_secs(1),
+            },
+            max_moves: 256,
+            margin: Duration::from_secs(1),
+            ready_timeout: Duration::from_secs(30),
+            adjudicators: Vec::new(),
+        }
+    }
+}
+
+/// `Match` plays games between two engines and validates every move.
+///
+/// # Examples
+/// ```no_run
+/// use std::time::Duration;
+/// use usi::{EnginePlayer, Match, MatchConfig, TimeControl, UsiEngineHandler};
+///
+/// let mut black = EnginePlayer::new(UsiEngineHandler::spawn("/path/to/engine1", "/path/to").unwrap()).unwrap();
+/// let mut white = EnginePlayer::new(UsiEngineHandler::spawn("/path/to/engine2", "/path/to").unwrap()).unwrap();
+///
+/// let config = MatchConfig::new().time_control(TimeControl::Byoyomi {
+///     time: Duration::from_secs(60),
+///     byoyomi: Duration::from_secs(1),
+/// });
+/// let record = Match::new(config).play(&mut black, &mut white).unwrap();
+/// if let Some(result) = record.result {
+///     println!("{} in {} moves", result, record.moves.len());
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Match {
+    config: MatchConfig,
+}
+
+impl Match {
+    pub fn new(config: MatchConfig) -> Match {
+        Match { config }
+    }
+
+    /// Plays a game and returns its record.
+    /// Failures of either engine, including ones before the first move, are recorded as a loss
+    /// of the engine. Errors are returned only for an illegal start position.
+    pub fn play(
+        &self,
+        black: &mut EnginePlayer,
+        white: &mut EnginePlayer,
+    ) -> Result<GameRecord, Error> {
+        let mut pos = Position::from_sfen(&self.config.start_sfen)?;
+        let mut record = GameRecord {
+            black_name: black.name().to_string(),
+            white_name: white.name().to_string(),
+            time_control: Some(self.config.time_control),
+            start_sfen: pos.to_sfen(),
+            ..Default::default()
+        };
+
+        let mut failure = None;
+        for (color, player) in [(Color::Black, &mut *black), (Color::White, &mut *white)] {
+            let ready = player
+                .prepare(self.config.ready_timeout)
+                .and_then(|_| player.send_command(&GuiCommand::UsiNewGame));
+            match ready {
+                Ok(()) => {}
+                Err(Error::Timeout) => failure = Some(lose(color, GameEndReason::Timeout)),
+                Err(_) => failure = Some(lose(color, GameEndReason::Disconnected)),
+            }
+            if failure.is_some() {
+                break;
+            }
+        }
+
+        let mut clock = GameClock::new(self.config.time_control).margin(self.config.margin);
+        let mut repetition = RepetitionTracker::new(&pos);
+
+        let result = match failure {
+            Some(result) => result,
+            None => loop {
+                if record.moves.len() >= self.config.max_moves {
+                    break GameResult {
+                        winner: None,
+                        reason: GameEndReason::MaxMoves,
+                    };
+                }
+
+                let side = pos.side_to_move();
+                let player = match side {
+                    Color::Black => &mut *black,
+                    Color::White => &mut *white,
+                };
+
+                let search = match self.think(player, &record, &mut clock, side) {
+                    Ok(Some(search)) => search,
+                    Ok(None) => break lose(side, GameEndReason::Timeout),
+                    Err(_) => break lose(side, GameEndReason::Disconnected),
+                };
+
+                let m = match search.best_move {
+                    BestMoveParams::Resign => break lose(side, GameEndReason::Resign),
+                    BestMoveParams::Win => {
+                        // Declarations are accepted as is unless an adjudicator judges them.
+                        break self
+                            .config
+                            .adjudicators
+                            .iter()
+                            .find_map(|a| a.judge_declaration(&pos))
+                            .unwrap_or(GameResult {
+                                winner: Some(side),
+                                reason: GameEndReason::DeclareWin,
+                            });
+                    }
+                    BestMoveParams::MakeMove(m, _) => m,
+                };
+
+                let m = match m.parse::<Move>() {
+                    Ok(m) if pos.make_move(&m).is_ok() => m,
+                    _ => break lose(side, GameEndReason::IllegalMove),
+                };
+                record.moves.push(MoveRecord {
+                    elapsed: Some(search.elapsed),
+                    score: search.score,
+                    ..MoveRecord::new(m)
+                });
+
+                if pos.legal_moves().is_empty() {
+                    break GameResult {
+                        winner: Some(side),
+                        reason: GameEndReason::Checkmate,
+                    };
+                }
+
+                match repetition.push(&pos) {
+                    Some(Repetition::Draw) => {
+                        break GameResult {
+                            winner: None,
+                            reason: GameEndReason::Repetition,
+                        }
+                    }
+                    Some(Repetition::PerpetualCheck(c)) => {
+                        break lose(c, GameEndReason::PerpetualCheck)
+                    }
+                    None => {}
+                }
+
+                if let Some(result) = self
+                    .config
+                    .adjudicators
+                    .iter()
+                    .find_map(|a| a.adjudicate(&pos, &record))
+                {
+                    break result;
+                }
+            },
+        };
+
+        // Either engine may have gone, but the result is determined anyway.
+        let _ = black.send_command(&GuiCommand::GameOver(result.game_over_kind(Color::Black)));
+        let _ = white.send_command(&GuiCommand::GameOver(result.game_over_kind(Color::White)));
+
+        record.result = Some(result);
+        Ok(record)
+    }
+
+    /// Lets the player think on the current position, and waits for `bestmove`.
+    /// Returns `None` if the player did not respond in time.
+    fn think(
+        &self,
+        player: &mut EnginePlayer,
+        record: &GameRecord,
+        clock: &mut GameClock,
+        side: Color,
+    ) -> Result<Option<Search>, Error> {
+        player.send_command(&record.position_command(record.moves.len()))?;
+        player.send_command(&GuiCommand::Go(clock.think_params()))?;
+
+        clock.start(side);
+        let deadline = clock.deadline().expect("the clock must be running");
+        let mut score = None;
+        loop {
+            let output = match player.recv_deadline(deadline)? {
+                Some(output) => output,
+                None => {
+                    player.send_command(&GuiCommand::Stop)?;
+                    return Ok(None);
+                }
+            };
+
+            match output.response() {
+                Some(EngineCommand::Info(params)) => {
+                    for p in params {
+                        if let InfoParams::Score(v, kind) = p {
+                            score = Some((*v, kind.clone()));
+                        }
+                    }
+                }
+                Some(EngineCommand::BestMove(best_move)) => {
+                    let elapsed = match clock.stop_at(*output.timestamp()) {
+                        Ok(elapsed) => elapsed,
+                        Err(_) => return Ok(None),
+                    };
+
+                    return Ok(Some(Search {
+                        best_move: best_move.clone(),
+                        elapsed,
+                        score,
+                    }));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Represents an outcome of a single `go` command.
+struct Search {
+    best_move: BestMoveParams,
+    elapsed: Duration,
+    score: Option<(i32, ScoreKind)>,
+}
+
+fn lose(color: Color, reason: GameEndReason) -> GameResult {
+    GameResult {
+        winner: Some(color.flip()),
+        reason,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::game::{Adjudication, DrawAdjudicator, ImpasseAdjudicator, ImpasseRule};
+    use crate::process::testing::{spawn_fake_engine, spawn_silent_engine};
+
+    fn play(black_moves: &[&str], white_moves: &[&str], config: MatchConfig) -> GameRecord {
+        let mut black = EnginePlayer::new(spawn_fake_engine("black", black_moves)).unwrap();
+        let mut white = EnginePlayer::new(spawn_fake_engine("white", white_moves)).unwrap();
+        Match::new(config).play(&mut black, &mut white).unwrap()
+    }
+
+    #[test]
+    fn resign() {
+        let record = play(&["7g7f"], &[], MatchConfig::new());
+
+        assert_eq!("black", record.black_name);
+        assert_eq!("white", record.white_name);
+        assert_eq!(1, record.moves.len());
+        assert_eq!(Some((0, ScoreKind::CpExact)), record.moves[0].score);
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: GameEndReason::Resign,
+            }),
+            record.result
+        );
+    }
+
+    #[test]
+    fn illegal_move() {
+        let record = play(&["7g7f", "7f7e"], &["3c3d", "3d3c"], MatchConfig::new());
+
+        assert_eq!(3, record.moves.len());
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: GameEndReason::IllegalMove,
+            }),
+            record.result
+        );
+    }
+
+    #[test]
+    fn checkmate() {
+        let config = MatchConfig::new().start_sfen("4k4/9/4P4/9/9/9/9/9/4K4 b G 1");
+        let record = play(&["G*5b"], &[], config);
+
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: GameEndReason::Checkmate,
+            }),
+            record.result
+        );
+    }
+
+    #[test]
+    fn repetition_and_max_moves() {
+        let record = play(&["2h3h", "3h2h"], &["8b7b", "7b8b"], MatchConfig::new());
+        assert_eq!(12, record.moves.len());
+        assert_eq!(
+            Some(GameResult {
+                winner: None,
+                reason: GameEndReason::Repetition,
+            }),
+            record.result
+        );
+
+        let record = play(
+            &["2h3h", "3h2h"],
+            &["8b7b", "7b8b"],
+            MatchConfig::new().max_moves(5),
+        );
+        assert_eq!(5, record.moves.len());
+        assert_eq!(
+            Some(GameResult {
+                winner: None,
+                reason: GameEndReason::MaxMoves,
+            }),
+            record.result
+        );
+    }
+
+    #[test]
+    fn perpetual_check() {
+        let config = MatchConfig::new().start_sfen("8k/9/9/9/7R1/9/9/9/K8 b - 1");
+        let record = play(&["2e1e", "1e2e"], &["1a2a", "2a1a"], config);
+
+        assert_eq!(12, record.moves.len());
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::White),
+                reason: GameEndReason::PerpetualCheck,
+            }),
+            record.result
+        );
+    }
+
+    #[test]
+    fn adjudication() {
+        let config = MatchConfig::new().adjudicator(DrawAdjudicator::new(4, 0, 4));
+        let record = play(&["2h3h", "3h2h"], &["8b7b", "7b8b"], config);
+        assert_eq!(4, record.moves.len());
+        assert_eq!(
+            Some(GameResult {
+                winner: None,
+                reason: GameEndReason::Adjudicated(Adjudication::DrawScore),
+            }),
+            record.result
+        );
+
+        // Declarations are validated only by adjudicators.
+        let record = play(&["win"], &[], MatchConfig::new());
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: GameEndReason::DeclareWin,
+            }),
+            record.result
+        );
+
+        let config = MatchConfig::new().adjudicator(ImpasseAdjudicator::new(ImpasseRule::Point27));
+        let record = play(&["win"], &[], config);
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::White),
+                reason: GameEndReason::IllegalMove,
+            }),
+            record.result
+        );
+    }
+
+    #[test]
+    fn timeout() {
+        // The engine never responds to `go`, so it loses once the byoyomi and margin pass.
+        let config = MatchConfig::new()
+            .time_control(TimeControl::FixedPerMove(Duration::from_millis(50)))
+            .margin(Duration::from_millis(50));
+        let mut black = EnginePlayer::new(spawn_fake_engine("black", &["7g7f"])).unwrap();
+        let mut white = EnginePlayer::new(spawn_silent_engine()).unwrap();
+        let record = Match::new(config).play(&mut black, &mut white).unwrap();
+
+        assert_eq!(1, record.moves.len());
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: GameEndReason::Timeout,
+            }),
+            record.result
+        );
+    }
+}
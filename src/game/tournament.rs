@@ -0,0 +1,972 @@
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+
+use super::{EnginePlayer, GameEndReason, GameRecord, GameResult, Match, MatchConfig};
+use crate::error::Error;
+use crate::process::UsiEngineHandler;
+#[cfg(target_os = "linux")]
+use crate::process::{ResourceLimits, ResourceUsage};
+use crate::shogi::{Color, Position};
+
+/// Represents how to spawn and configure an engine participating in a tournament.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct EngineSpec {
+    pub name: String,
+    pub path: PathBuf,
+    pub working_dir: PathBuf,
+    pub options: Vec<(String, String)>,
+    // Private, so that code using `EngineSpec` builds the same way on every platform.
+    #[cfg(target_os = "linux")]
+    limits: ResourceLimits,
+}
+
+impl EngineSpec {
+    /// Creates a spec which runs the engine in the directory where it is placed.
+    pub fn new<P: AsRef<Path>>(name: &str, path: P) -> EngineSpec {
+        let path = path.as_ref().to_path_buf();
+        let working_dir = path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf();
+
+        EngineSpec {
+            name: name.to_string(),
+            path,
+            working_dir,
+            options: Vec::new(),
+            #[cfg(target_os = "linux")]
+            limits: ResourceLimits::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn option(mut self, name: &str, value: &str) -> Self {
+        self.options.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Restricts resources of the engine processes.
+    /// In tournaments, CPU affinity is overridden by `TournamentConfig::worker_cpus` if given,
+    /// since all processes of the engine would otherwise be pinned to the same CPUs.
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn limits(mut self, limits: ResourceLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Spawns the engine, performs the handshake and applies options.
+    pub fn spawn(&self) -> Result<EnginePlayer, Error> {
+        #[cfg(target_os = "linux")]
+        let handler =
+            UsiEngineHandler::spawn_with_limits(&self.path, &self.working_dir, &self.limits)?;
+        #[cfg(not(target_os = "linux"))]
+        let handler = UsiEngineHandler::spawn(&self.path, &self.working_dir)?;
+        let mut player = EnginePlayer::new(handler)?;
+        for (name, value) in &self.options {
+            player.set_option(name, Some(value))?;
+        }
+        Ok(player)
+    }
+}
+
+/// Represents which pairs of engines play against each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Schedule {
+    /// Every engine plays against every other engine.
+    RoundRobin,
+    /// The first engine plays against each of the others.
+    Gauntlet,
+}
+
+/// Represents a game scheduled in a tournament.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TournamentGame {
+    /// Sequential number of the game, used to resume the tournament.
+    pub index: usize,
+    /// Index of the engine playing black.
+    pub black: usize,
+    /// Index of the engine playing white.
+    pub white: usize,
+    /// SFEN of the start position, if openings are given.
+    pub opening: Option<String>,
+}
+
+/// Represents settings of a tournament.
+#[derive(Clone, Debug)]
+pub struct TournamentConfig {
+    schedule: Schedule,
+    games_per_pair: usize,
+    concurrency: usize,
+    openings: Vec<String>,
+    match_config: MatchConfig,
+    state_path: Option<PathBuf>,
+    #[cfg(target_os = "linux")]
+    worker_cpus: Vec<Vec<usize>>,
+}
+
+impl TournamentConfig {
+    pub fn new() -> Self {
+        TournamentConfig::default()
+    }
+
+    #[must_use]
+    pub fn schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Sets the number of games played by each pair.
+    /// Each two consecutive games share the same opening with colors swapped.
+    #[must_use]
+    pub fn games_per_pair(mut self, n: usize) -> Self {
+        self.games_per_pair = n;
+        self
+    }
+
+    /// Sets the number of games played at the same time.
+    #[must_use]
+    pub fn concurrency(mut self, n: usize) -> Self {
+        self.concurrency = n.max(1);
+        self
+    }
+
+    /// Sets start positions in SFEN, used in turn.
+    #[must_use]
+    pub fn openings(mut self, openings: Vec<String>) -> Self {
+        self.openings = openings;
+        self
+    }
+
+    /// Sets settings of each game. The start position is overridden by openings if given.
+    #[must_use]
+    pub fn match_config(mut self, config: MatchConfig) -> Self {
+        self.match_config = config;
+        self
+    }
+
+    /// Sets a file to record finished games, so that an interrupted tournament can be resumed.
+    /// On Linux, resources consumed by each engine process are recorded as well.
+    #[must_use]
+    pub fn state_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.state_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Pins engines spawned by the `k`-th worker to `cpus[k]`, reusing the sets in turn if
+    /// there are fewer sets than workers. Both engines of a game share the CPUs of the worker.
+    #[cfg(target_os = "linux")]
+    #[must_use]
+    pub fn worker_cpus(mut self, cpus: Vec<Vec<usize>>) -> Self {
+        self.worker_cpus = cpus;
+        self
+    }
+}
+
+impl Default for TournamentConfig {
+    fn default() -> Self {
+        TournamentConfig {
+            schedule: Schedule::RoundRobin,
+            games_per_pair: 2,
+            concurrency: 1,
+            openings: Vec::new(),
+            match_config: MatchConfig::default(),
+            state_path: None,
+            #[cfg(target_os = "linux")]
+            worker_cpus: Vec::new(),
+        }
+    }
+}
+
+/// Represents numbers of wins, draws and losses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Score {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl Score {
+    /// Returns the number of games.
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Returns points, counting a draw as a half point.
+    pub fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "+{} ={} -{}", self.wins, self.draws, self.losses)
+    }
+}
+
+/// Represents results of a tournament between every pair of engines.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Crosstable {
+    names: Vec<String>,
+    scores: Vec<Vec<Score>>,
+    #[cfg(target_os = "linux")]
+    usage: Vec<Option<ResourceUsage>>,
+}
+
+impl Crosstable {
+    pub fn new(names: Vec<String>) -> Crosstable {
+        let n = names.len();
+        Crosstable {
+            names,
+            scores: vec![vec![Score::default(); n]; n],
+            #[cfg(target_os = "linux")]
+            usage: vec![None; n],
+        }
+    }
+
+    /// Returns names of engines.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Returns the score of engine `i` against engine `j`.
+    pub fn score(&self, i: usize, j: usize) -> Score {
+        self.scores[i][j]
+    }
+
+    /// Returns the total score of engine `i`.
+    pub fn total(&self, i: usize) -> Score {
+        self.scores[i]
+            .iter()
+            .fold(Score::default(), |acc, s| Score {
+                wins: acc.wins + s.wins,
+                draws: acc.draws + s.draws,
+                losses: acc.losses + s.losses,
+            })
+    }
+
+    /// Returns resources consumed by the processes of engine `i` which have exited,
+    /// with the peak RSS of the largest process and the CPU time summed up.
+    #[cfg(target_os = "linux")]
+    pub fn resource_usage(&self, i: usize) -> Option<ResourceUsage> {
+        self.usage[i]
+    }
+
+    /// Records resources consumed by a process of engine `i`.
+    #[cfg(target_os = "linux")]
+    pub fn add_usage(&mut self, i: usize, usage: ResourceUsage) {
+        let total = self.usage[i].get_or_insert_with(ResourceUsage::default);
+        total.peak_rss = total.peak_rss.max(usage.peak_rss);
+        total.user_time += usage.user_time;
+        total.system_time += usage.system_time;
+    }
+
+    /// Records a game between `black` and `white`. `winner` is `None` for a draw.
+    pub fn add(&mut self, black: usize, white: usize, winner: Option<Color>) {
+        match winner {
+            Some(Color::Black) => {
+                self.scores[black][white].wins += 1;
+                self.scores[white][black].losses += 1;
+            }
+            Some(Color::White) => {
+                self.scores[black][white].losses += 1;
+                self.scores[white][black].wins += 1;
+            }
+            None => {
+                self.scores[black][white].draws += 1;
+                self.scores[white][black].draws += 1;
+            }
+        }
+    }
+}
+
+impl fmt::Display for Crosstable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name_width = self.names.iter().map(|n| n.len()).max().unwrap_or(0);
+        let cells = (0..self.names.len())
+            .map(|i| {
+                (0..self.names.len())
+                    .map(|j| {
+                        if i == j {
+                            "-".to_string()
+                        } else {
+                            self.scores[i][j].to_string()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let cell_width = cells.iter().flatten().map(|c| c.len()).max().unwrap_or(0);
+
+        write!(f, "{:>3} {:name_width$}", "#", "")?;
+        for j in 0..self.names.len() {
+            write!(f, "  {:>cell_width$}", j + 1)?;
+        }
+        writeln!(f, "  {:>12}  {:>7}", "total", "points")?;
+
+        for (i, name) in self.names.iter().enumerate() {
+            write!(f, "{:>3} {name:name_width$}", i + 1)?;
+            for cell in &cells[i] {
+                write!(f, "  {cell:>cell_width$}")?;
+            }
+            let total = self.total(i);
+            writeln!(
+                f,
+                "  {:>12}  {:>7}",
+                total.to_string(),
+                format!("{}/{}", total.points(), total.games())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// `Tournament` plays games between multiple engines in parallel.
+///
+/// Each worker thread keeps its own engine processes, and reuses them while the next game
+/// is played by the same engines.
+/// Processes which lost on time or disconnected in a game are respawned for the next game,
+/// and an engine which fails to start loses the game, so that failures never abort the run.
+/// Processes are ended with `quit` when they are replaced or not needed for the next game,
+/// and their resource usage is added to the crosstable on Linux.
+///
+/// # Examples
+/// ```no_run
+/// use std::ops::ControlFlow;
+/// use usi::{EngineSpec, Schedule, Tournament, TournamentConfig};
+///
+/// let engines = vec![
+///     EngineSpec::new("new", "/path/to/new/engine"),
+///     EngineSpec::new("base", "/path/to/base/engine").option("Threads", "1"),
+/// ];
+/// let config = TournamentConfig::new()
+///     .schedule(Schedule::Gauntlet)
+///     .games_per_pair(100)
+///     .concurrency(4)
+///     .state_path("/tmp/gauntlet.state");
+///
+/// let crosstable = Tournament::new(engines, config)
+///     .run(|game, record| {
+///         println!("#{}: {:?}", game.index, record.result);
+///         ControlFlow::Continue(())
+///     })
+///     .unwrap();
+/// println!("{}", crosstable);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Tournament {
+    engines: Vec<EngineSpec>,
+    config: TournamentConfig,
+}
+
+impl Tournament {
+    pub fn new(engines: Vec<EngineSpec>, config: TournamentConfig) -> Tournament {
+        Tournament { engines, config }
+    }
+
+    /// Returns all games in the tournament.
+    pub fn games(&self) -> Vec<TournamentGame> {
+        let n = self.engines.len();
+        let pairs = match self.config.schedule {
+            Schedule::RoundRobin => (0..n)
+                .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+                .collect::<Vec<_>>(),
+            Schedule::Gauntlet => (1..n).map(|j| (0, j)).collect(),
+        };
+
+        let mut games = Vec::new();
+        for (i, j) in pairs {
+            for k in 0..self.config.games_per_pair {
+                let (black, white) = if k % 2 == 0 { (i, j) } else { (j, i) };
+                let opening = if self.config.openings.is_empty() {
+                    None
+                } else {
+                    Some(self.config.openings[(k / 2) % self.config.openings.len()].clone())
+                };
+                games.push(TournamentGame {
+                    index: games.len(),
+                    black,
+                    white,
+                    opening,
+                });
+            }
+        }
+        games
+    }
+
+    /// Plays all games, and calls `on_game` for each finished game in the order of completion.
+    /// Games already recorded in the state file are skipped but counted in the crosstable.
+    ///
+    /// If `on_game` returns `ControlFlow::Break`, no more games are started.
+    /// Games in progress are played to the end and recorded without calling `on_game`.
+    ///
+    /// Returns an error if any of the openings is an illegal position, or the state file
+    /// cannot be read or written. A corrupted state file results in `Error::IllegalRecord`.
+    pub fn run<F>(&self, mut on_game: F) -> Result<Crosstable, Error>
+    where
+        F: FnMut(&TournamentGame, &GameRecord) -> ControlFlow<()>,
+    {
+        for sfen in self
+            .config
+            .openings
+            .iter()
+            .chain([&self.config.match_config.start_sfen])
+        {
+            Position::from_sfen(sfen)?;
+        }
+
+        let games = self.games();
+        let mut crosstable = Crosstable::new(self.engines.iter().map(|e| e.name.clone()).collect());
+
+        let State {
+            finished,
+            #[cfg(target_os = "linux")]
+            usage,
+        } = match self.config.state_path {
+            Some(ref path) => load_state(path, self.engines.len())?,
+            None => State::default(),
+        };
+        for (index, winner) in &finished {
+            if let Some(game) = games.get(*index) {
+                crosstable.add(game.black, game.white, *winner);
+            }
+        }
+        #[cfg(target_os = "linux")]
+        for (i, usage) in usage {
+            crosstable.add_usage(i, usage);
+        }
+        let pending = games
+            .iter()
+            .filter(|g| !finished.contains_key(&g.index))
+            .collect::<Vec<_>>();
+
+        let mut state = match self.config.state_path {
+            Some(ref path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+            None => None,
+        };
+
+        let next = AtomicUsize::new(0);
+        let stop = AtomicBool::new(false);
+        let (sender, receiver) = mpsc::channel();
+
+        thread::scope(|s| -> Result<(), Error> {
+            for slot in 0..self.config.concurrency.min(pending.len()) {
+                let sender = sender.clone();
+                let (next, stop, pending) = (&next, &stop, &pending);
+                s.spawn(move || {
+                    let mut players = HashMap::new();
+                    while !stop.load(Ordering::SeqCst) {
+                        let game = match pending.get(next.fetch_add(1, Ordering::SeqCst)) {
+                            Some(game) => *game,
+                            None => break,
+                        };
+                        // Engines not playing the game are ended, so that a worker runs
+                        // at most two processes at a time.
+                        let idle = players
+                            .keys()
+                            .copied()
+                            .filter(|&i| i != game.black && i != game.white)
+                            .collect::<Vec<_>>();
+                        for i in idle {
+                            let player = players.remove(&i).unwrap();
+                            self.retire(i, player, &sender);
+                        }
+
+                        let (record, retired) = self.play(slot, game, &mut players);
+                        let sent = sender.send(Event::Game(game, record)).is_ok();
+                        for (i, player) in retired {
+                            self.retire(i, player, &sender);
+                        }
+                        if !sent {
+                            break;
+                        }
+                    }
+                    for (i, player) in players {
+                        self.retire(i, player, &sender);
+                    }
+                });
+            }
+            drop(sender);
+
+            for event in receiver {
+                let (game, record) = match event {
+                    Event::Game(game, record) => (game, record),
+                    #[cfg(target_os = "linux")]
+                    Event::Exit(i, usage) => {
+                        crosstable.add_usage(i, usage);
+                        if let Some(ref mut file) = state {
+                            writeln!(
+                                file,
+                                "usage {i} {} {} {}",
+                                usage.peak_rss,
+                                usage.user_time.as_micros(),
+                                usage.system_time.as_micros()
+                            )?;
+                            file.flush()?;
+                        }
+                        continue;
+                    }
+                };
+                let winner = record.result.and_then(|r| r.winner);
+                crosstable.add(game.black, game.white, winner);
+                if let Some(ref mut file) = state {
+                    let reason = record
+                        .result
+                        .map_or(GameEndReason::Disconnected, |r| r.reason);
+                    writeln!(file, "{} {} {}", game.index, winner_str(winner), reason)?;
+                    file.flush()?;
+                }
+                if !stop.load(Ordering::SeqCst) && on_game(game, &record).is_break() {
+                    stop.store(true, Ordering::SeqCst);
+                }
+            }
+            Ok(())
+        })?;
+
+        Ok(crosstable)
+    }
+
+    /// Plays the game on the worker `slot`, and returns its record with the engines which
+    /// should be replaced.
+    fn play(
+        &self,
+        slot: usize,
+        game: &TournamentGame,
+        players: &mut HashMap<usize, EnginePlayer>,
+    ) -> (GameRecord, Vec<(usize, EnginePlayer)>) {
+        let mut config = self.config.match_config.clone();
+        if let Some(ref sfen) = game.opening {
+            config = config.start_sfen(sfen);
+        }
+
+        // An engine which fails to start loses the game.
+        for (color, i) in [(Color::Black, game.black), (Color::White, game.white)] {
+            if let Entry::Vacant(e) = players.entry(i) {
+                match self.spec(i, slot).spawn() {
+                    Ok(player) => {
+                        e.insert(player);
+                    }
+                    Err(_) => {
+                        let record = GameRecord {
+                            black_name: self.engines[game.black].name.clone(),
+                            white_name: self.engines[game.white].name.clone(),
+                            result: Some(GameResult {
+                                winner: Some(color.flip()),
+                                reason: GameEndReason::Disconnected,
+                            }),
+                            ..Default::default()
+                        };
+                        return (record, Vec::new());
+                    }
+                }
+            }
+        }
+
+        // Both engines are borrowed mutably at once, so take them out of the pool during the game.
+        let mut black = players.remove(&game.black).unwrap();
+        let mut white = players.remove(&game.white).unwrap();
+        // Start positions are validated in `run`, so that the game is always played.
+        let mut record = Match::new(config)
+            .play(&mut black, &mut white)
+            .unwrap_or_default();
+        record.black_name = self.engines[game.black].name.clone();
+        record.white_name = self.engines[game.white].name.clone();
+
+        // An engine which lost on time may still be searching, and its late `bestmove` would
+        // leak into the next game, so both engines are respawned after such failures.
+        let reason = record.result.map(|r| r.reason);
+        if matches!(
+            reason,
+            Some(GameEndReason::Timeout | GameEndReason::Disconnected)
+        ) {
+            return (record, vec![(game.black, black), (game.white, white)]);
+        }
+        players.insert(game.black, black);
+        players.insert(game.white, white);
+        (record, Vec::new())
+    }
+
+    /// Returns the spec of engine `i` to spawn on the worker `slot`.
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    fn spec(&self, i: usize, slot: usize) -> EngineSpec {
+        let spec = self.engines[i].clone();
+        #[cfg(target_os = "linux")]
+        if !self.config.worker_cpus.is_empty() {
+            let cpus = &self.config.worker_cpus[slot % self.config.worker_cpus.len()];
+            let limits = spec.limits.clone().cpu_affinity(cpus);
+            return spec.limits(limits);
+        }
+        spec
+    }
+
+    /// Ends the engine with `quit`, and reports resources it consumed.
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+    fn retire(&self, i: usize, mut player: EnginePlayer, sender: &Sender<Event<'_>>) {
+        let _ = player.quit(self.config.match_config.ready_timeout);
+        #[cfg(target_os = "linux")]
+        if let Some(usage) = player.resource_usage() {
+            let _ = sender.send(Event::Exit(i, usage));
+        }
+    }
+}
+
+/// Represents a message from a worker thread to `Tournament::run`.
+enum Event<'a> {
+    Game(&'a TournamentGame, GameRecord),
+    /// An engine process exited, consuming the resources.
+    #[cfg(target_os = "linux")]
+    Exit(usize, ResourceUsage),
+}
+
+/// Represents the contents of the state file.
+#[derive(Default)]
+struct State {
+    /// Indices and winners of finished games.
+    finished: HashMap<usize, Option<Color>>,
+    /// Resources consumed by exited processes, with the index of the engine.
+    #[cfg(target_os = "linux")]
+    usage: Vec<(usize, ResourceUsage)>,
+}
+
+/// Reads start positions from a file containing a SFEN per line.
+/// Empty lines and lines starting with `#` are ignored.
+pub fn load_openings<P: AsRef<Path>>(path: P) -> Result<Vec<String>, Error> {
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.strip_prefix("sfen ").unwrap_or(l).to_string())
+        .collect())
+}
+
+fn winner_str(winner: Option<Color>) -> &'static str {
+    match winner {
+        Some(Color::Black) => "black",
+        Some(Color::White) => "white",
+        None => "draw",
+    }
+}
+
+/// Reads the state file of a tournament between `engines` engines.
+/// A line cut off by an interruption is removed from the file, so that the game is played again.
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+fn load_state(path: &Path, engines: usize) -> Result<State, Error> {
+    let mut file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(State::default()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut s = String::new();
+    file.read_to_string(&mut s)?;
+
+    let complete = s.rfind('\n').map_or(0, |i| i + 1);
+    if complete < s.len() {
+        file.set_len(complete as u64)?;
+        s.truncate(complete);
+    }
+
+    let mut state = State::default();
+    for (n, line) in s.lines().enumerate() {
+        let corrupt = || Error::IllegalRecord(format!("{}:{}: {line}", path.display(), n + 1));
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("usage ") {
+            #[cfg(target_os = "linux")]
+            {
+                let fields = rest
+                    .split_whitespace()
+                    .map(str::parse)
+                    .collect::<Result<Vec<u64>, _>>()
+                    .map_err(|_| corrupt())?;
+                let [i, peak_rss, user, system] = fields[..] else {
+                    return Err(corrupt());
+                };
+                if i as usize >= engines {
+                    return Err(corrupt());
+                }
+                state.usage.push((
+                    i as usize,
+                    ResourceUsage {
+                        peak_rss,
+                        user_time: Duration::from_micros(user),
+                        system_time: Duration::from_micros(system),
+                    },
+                ));
+            }
+            continue;
+        }
+
+        let mut iter = line.split_whitespace();
+        let (index, winner) = match (iter.next(), iter.next()) {
+            (Some(index), Some(winner)) => (index, winner),
+            _ => return Err(corrupt()),
+        };
+        let winner = match winner {
+            "black" => Some(Color::Black),
+            "white" => Some(Color::White),
+            "draw" => None,
+            _ => return Err(corrupt()),
+        };
+        let index = index.parse().map_err(|_| corrupt())?;
+        state.finished.insert(index, winner);
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(unix)]
+    use std::collections::HashSet;
+    #[cfg(unix)]
+    use std::time::Duration;
+
+    #[cfg(unix)]
+    use crate::game::TimeControl;
+    #[cfg(unix)]
+    use crate::process::testing::{fake_engine, script_engine, temp_dir};
+
+    #[cfg(unix)]
+    fn engines() -> Vec<EngineSpec> {
+        vec![
+            EngineSpec::new("resigner", fake_engine("resigner", &[])),
+            EngineSpec::new("pawn", fake_engine("pawn", &["7g7f", "3c3d", "2g2f"])),
+            EngineSpec::new(
+                "rook",
+                fake_engine("rook", &["2h3h", "8b7b", "3h2h", "7b8b"]),
+            ),
+        ]
+    }
+
+    #[test]
+    fn schedule() {
+        let config = TournamentConfig::new()
+            .games_per_pair(4)
+            .openings(vec!["a".to_string(), "b".to_string()]);
+        // Scheduling does not spawn engines.
+        let specs = ["resigner", "pawn", "rook"]
+            .iter()
+            .map(|name| EngineSpec::new(name, name))
+            .collect::<Vec<_>>();
+        let games = Tournament::new(specs.clone(), config.clone()).games();
+        assert_eq!(12, games.len());
+        assert_eq!(
+            vec![(0, 1), (1, 0), (0, 1), (1, 0)],
+            games[..4]
+                .iter()
+                .map(|g| (g.black, g.white))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["a", "a", "b", "b"],
+            games[..4]
+                .iter()
+                .map(|g| g.opening.as_deref().unwrap())
+                .collect::<Vec<_>>()
+        );
+
+        let games = Tournament::new(specs, config.schedule(Schedule::Gauntlet)).games();
+        assert_eq!(8, games.len());
+        assert!(games.iter().all(|g| g.black == 0 || g.white == 0));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn run_and_resume() {
+        let state = temp_dir().join("state");
+        let config = TournamentConfig::new().games_per_pair(2).state_path(&state);
+
+        // Stop after the first game. The next game may have been started in the meantime.
+        let stopped = Tournament::new(engines(), config.clone())
+            .run(|_, _| ControlFlow::Break(()))
+            .unwrap();
+        let played = (0..3).map(|i| stopped.total(i).games()).sum::<u32>() / 2;
+        assert!(played == 1 || played == 2, "{played}");
+
+        let mut indices = HashSet::new();
+        let crosstable = Tournament::new(engines(), config.clone().concurrency(3))
+            .run(|game, record| {
+                assert!(record.result.is_some());
+                indices.insert(game.index);
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+        assert_eq!(6 - played as usize, indices.len());
+        assert!(!indices.contains(&0));
+
+        assert_eq!(
+            Score {
+                wins: 0,
+                draws: 0,
+                losses: 4
+            },
+            crosstable.total(0)
+        );
+        assert_eq!(
+            Score {
+                wins: 2,
+                draws: 0,
+                losses: 0
+            },
+            crosstable.score(1, 0)
+        );
+        assert_eq!(
+            6,
+            (0..3).map(|i| crosstable.total(i).games()).sum::<u32>() / 2
+        );
+
+        // All games are restored from the state file.
+        let resumed = Tournament::new(engines(), config)
+            .run(|_, _| unreachable!())
+            .unwrap();
+        assert_eq!(crosstable, resumed);
+        assert!(resumed.to_string().contains("resigner"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failures() {
+        let engines = vec![
+            EngineSpec::new("pawn", fake_engine("pawn", &["7g7f", "3c3d"])),
+            EngineSpec::new("missing", temp_dir().join("missing")),
+            EngineSpec::new("silent", script_engine("silent", ":")),
+        ];
+        let match_config = MatchConfig::new()
+            .time_control(TimeControl::FixedPerMove(Duration::from_millis(50)))
+            .margin(Duration::from_millis(50));
+        let config = TournamentConfig::new().match_config(match_config);
+
+        let mut reasons = HashMap::new();
+        let crosstable = Tournament::new(engines, config)
+            .run(|game, record| {
+                let result = record.result.unwrap();
+                reasons.insert(game.index, result.reason);
+                ControlFlow::Continue(())
+            })
+            .unwrap();
+
+        assert_eq!(6, reasons.len());
+        assert_eq!(
+            Score {
+                wins: 0,
+                draws: 0,
+                losses: 4
+            },
+            crosstable.total(1)
+        );
+        assert_eq!(
+            Score {
+                wins: 2,
+                draws: 0,
+                losses: 0
+            },
+            crosstable.score(0, 2)
+        );
+        assert!(reasons
+            .values()
+            .all(|r| matches!(r, GameEndReason::Timeout | GameEndReason::Disconnected)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn state_file() {
+        let path = temp_dir().join("state");
+        fs::write(&path, "0 black resign\n\n1 draw max_moves\n2 bl").unwrap();
+        let state = load_state(&path, 2).unwrap();
+        assert_eq!(
+            HashMap::from([(0, Some(Color::Black)), (1, None)]),
+            state.finished
+        );
+        // The line cut off is removed, so that the next line is appended properly.
+        assert_eq!(
+            "0 black resign\n\n1 draw max_moves\n",
+            fs::read_to_string(&path).unwrap()
+        );
+
+        for corrupted in ["0 blue resign\n", "x black resign\n", "0\n"] {
+            fs::write(&path, corrupted).unwrap();
+            match load_state(&path, 2) {
+                Err(Error::IllegalRecord(msg)) => assert!(msg.contains(":1: "), "{msg}"),
+                r => panic!("unexpected {:?}", r.map(|s| s.finished)),
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn idle_engines() {
+        let state = temp_dir().join("state");
+        let config = TournamentConfig::new().state_path(&state);
+        Tournament::new(engines(), config)
+            .run(|_, _| ControlFlow::Continue(()))
+            .unwrap();
+
+        // Games are played as 0-1, 1-0, 0-2, 2-0, 1-2 and 2-1, so that only the second engine
+        // is ended in the middle, and spawned again.
+        let s = fs::read_to_string(&state).unwrap();
+        let exits = (0..3)
+            .map(|i| {
+                s.lines()
+                    .filter(|l| l.starts_with(&format!("usage {i} ")))
+                    .count()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 2, 1], exits);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn resource_usage() {
+        let state = temp_dir().join("state");
+        let config = TournamentConfig::new()
+            .concurrency(2)
+            .worker_cpus(vec![vec![0]])
+            .state_path(&state);
+
+        let crosstable = Tournament::new(engines(), config.clone())
+            .run(|_, _| ControlFlow::Continue(()))
+            .unwrap();
+        for i in 0..3 {
+            let usage = crosstable.resource_usage(i).unwrap();
+            assert!(usage.peak_rss > 0, "{usage:?}");
+        }
+        assert!(fs::read_to_string(&state).unwrap().contains("usage 0 "));
+
+        // Usage is restored from the state file as well.
+        let resumed = Tournament::new(engines(), config)
+            .run(|_, _| unreachable!())
+            .unwrap();
+        assert_eq!(crosstable, resumed);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn worker_cpus() {
+        let config = TournamentConfig::new().worker_cpus(vec![vec![0], vec![1, 2]]);
+        let limits = ResourceLimits::new().memory(1 << 30);
+        let specs = vec![EngineSpec::new("a", "a").limits(limits.clone())];
+        let tournament = Tournament::new(specs, config);
+
+        assert_eq!(
+            limits.clone().cpu_affinity(&[0]),
+            tournament.spec(0, 0).limits
+        );
+        assert_eq!(
+            limits.clone().cpu_affinity(&[1, 2]),
+            tournament.spec(0, 1).limits
+        );
+        assert_eq!(limits.cpu_affinity(&[0]), tournament.spec(0, 2).limits);
+    }
+}
@@ -0,0 +1,724 @@
+use std::time::Duration;
+
+use super::record_error;
+use crate::error::Error;
+use crate::game::{Adjudication, GameEndReason, GameRecord, GameResult, MoveRecord, TimeControl};
+use crate::protocol::ScoreKind;
+use crate::shogi::{Color, Move, Piece, PieceType, Position, Square};
+
+/// Piece codes used in CSA, in the order of `PieceType`.
+const PIECES: [(PieceType, &str); 14] = [
+    (PieceType::King, "OU"),
+    (PieceType::Rook, "HI"),
+    (PieceType::Bishop, "KA"),
+    (PieceType::Gold, "KI"),
+    (PieceType::Silver, "GI"),
+    (PieceType::Knight, "KE"),
+    (PieceType::Lance, "KY"),
+    (PieceType::Pawn, "FU"),
+    (PieceType::Dragon, "RY"),
+    (PieceType::Horse, "UM"),
+    (PieceType::ProSilver, "NG"),
+    (PieceType::ProKnight, "NK"),
+    (PieceType::ProLance, "NY"),
+    (PieceType::ProPawn, "TO"),
+];
+
+/// Number of pieces of each type in `PieceType::HAND_TYPES` in a full set.
+const PIECE_COUNTS: [u8; 7] = [2, 2, 4, 4, 4, 4, 18];
+
+impl GameRecord {
+    /// Parses a game record in CSA format.
+    ///
+    /// Evaluation comments such as `'** 120 3334FU`, which are written from the point of view
+    /// of black, are stored as the score of the preceding move, and other comments starting
+    /// with `'` are attached to the preceding move. Headers starting with `$` are kept
+    /// in `headers` without `$`, except for the time control.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use usi::GameRecord;
+    ///
+    /// let csa = "V2.2\nN+Black\nN-White\nPI\n+\n+7776FU\nT3\n-3334FU\nT1\n%TORYO\n";
+    /// let record = GameRecord::from_csa(csa).unwrap();
+    /// assert_eq!("White", record.white_name);
+    /// assert_eq!("3c3d", record.moves[1].mv.to_string());
+    /// ```
+    pub fn from_csa(s: &str) -> Result<GameRecord, Error> {
+        let mut record = GameRecord::default();
+        let mut setup = Setup::new();
+        let mut pos: Option<Position> = None;
+
+        for (n, line) in s.lines().enumerate() {
+            let line = line.trim_end();
+            if let Some(comment) = line.strip_prefix('\'') {
+                let m = record.moves.last_mut();
+                match (m, parse_evaluation(comment)) {
+                    (Some(m), Some(score)) => {
+                        let mover = pos
+                            .as_ref()
+                            .map_or(Color::Black, |p| p.side_to_move().flip());
+                        let score = match mover {
+                            Color::Black => score,
+                            Color::White => -score,
+                        };
+                        m.score = Some((score, ScoreKind::CpExact));
+                    }
+                    (Some(m), None) => append_comment(&mut m.comment, comment),
+                    (None, _) => append_comment(&mut record.comment, comment),
+                }
+                continue;
+            }
+
+            // Multiple statements can be written in a line separated by commas.
+            for statement in line.split(',') {
+                let err = || record_error(n, statement);
+                if statement.is_empty() || statement.starts_with('V') {
+                    continue;
+                }
+                if let Some(name) = statement.strip_prefix("N+") {
+                    record.black_name = name.to_string();
+                } else if let Some(name) = statement.strip_prefix("N-") {
+                    record.white_name = name.to_string();
+                } else if let Some(header) = statement.strip_prefix('$') {
+                    let (key, value) = header.split_once(':').ok_or_else(err)?;
+                    match parse_time_control(key, value) {
+                        Some(tc) => record.time_control = Some(tc),
+                        None => record.headers.push((key.to_string(), value.to_string())),
+                    }
+                } else if statement.starts_with('P') {
+                    if pos.is_some() {
+                        return Err(err());
+                    }
+                    setup.parse(statement).ok_or_else(err)?;
+                } else if let Some(current) = pos.as_mut() {
+                    if let Some(t) = statement.strip_prefix('T') {
+                        let m = record.moves.last_mut().ok_or_else(err)?;
+                        let secs = t.parse::<f64>().map_err(|_| err())?;
+                        m.elapsed = Duration::try_from_secs_f64(secs).ok();
+                    } else if let Some(special) = statement.strip_prefix('%') {
+                        record.result =
+                            parse_special_move(special, current.side_to_move()).ok_or_else(err)?;
+                    } else {
+                        let m = parse_move(current, statement).ok_or_else(err)?;
+                        current.make_move(&m)?;
+                        record.moves.push(MoveRecord::new(m));
+                    }
+                } else {
+                    let side = match statement {
+                        "+" => Color::Black,
+                        "-" => Color::White,
+                        _ => return Err(err()),
+                    };
+                    let start = Position::from_sfen(&setup.to_sfen(side))?;
+                    record.start_sfen = start.to_sfen();
+                    pos = Some(start);
+                }
+            }
+        }
+
+        if pos.is_none() {
+            return Err(Error::IllegalRecord("missing the side to move".to_string()));
+        }
+        Ok(record)
+    }
+
+    /// Returns the record in CSA format.
+    ///
+    /// Scores of moves are written as evaluation comments from the point of view of black.
+    /// Variations are not written since CSA does not support them.
+    pub fn to_csa(&self) -> String {
+        let mut pos = Position::from_sfen(&self.start_sfen).unwrap_or_default();
+        let mut out = String::from("V2.2\n");
+        out.push_str(&format!("N+{}\nN-{}\n", self.black_name, self.white_name));
+        for (key, value) in &self.headers {
+            out.push_str(&format!("${key}:{value}\n"));
+        }
+        if let Some(ref tc) = self.time_control {
+            out.push_str(&time_control_header(tc));
+            out.push('\n');
+        }
+        write_comment(&mut out, self.comment.as_deref());
+        write_position(&mut out, &pos);
+
+        for m in &self.moves {
+            out.push_str(&format_move(&pos, &m.mv));
+            out.push('\n');
+            if let Some(elapsed) = m.elapsed {
+                out.push_str(&format!("T{}\n", elapsed.as_secs()));
+            }
+            if let Some(score) = m.score_cp() {
+                let score = match pos.side_to_move() {
+                    Color::Black => score,
+                    Color::White => -score,
+                };
+                out.push_str(&format!("'** {score}\n"));
+            }
+            write_comment(&mut out, m.comment.as_deref());
+            pos.make_move(&m.mv)
+                .expect("moves in a record must be legal");
+        }
+
+        if let Some(ref result) = self.result {
+            out.push_str(special_move(result, pos.side_to_move()));
+            out.push('\n');
+        }
+        out
+    }
+}
+
+pub(crate) fn piece_code(pt: PieceType) -> &'static str {
+    PIECES.iter().find(|(p, _)| *p == pt).unwrap().1
+}
+
+pub(crate) fn parse_piece_code(s: &str) -> Option<PieceType> {
+    PIECES.iter().find(|(_, c)| *c == s).map(|(p, _)| *p)
+}
+
+fn parse_square(s: &str) -> Option<Square> {
+    match s.as_bytes() {
+        [f @ b'1'..=b'9', r @ b'1'..=b'9'] => Square::new(f - b'0', r - b'0'),
+        _ => None,
+    }
+}
+
+fn parse_color(c: u8) -> Option<Color> {
+    match c {
+        b'+' => Some(Color::Black),
+        b'-' => Some(Color::White),
+        _ => None,
+    }
+}
+
+fn color_sign(c: Color) -> char {
+    match c {
+        Color::Black => '+',
+        Color::White => '-',
+    }
+}
+
+/// Parses a move such as `+7776FU`, where the piece code is the one after the move.
+pub(crate) fn parse_move(pos: &Position, s: &str) -> Option<Move> {
+    if !s.is_ascii() || s.len() != 7 || parse_color(s.as_bytes()[0])? != pos.side_to_move() {
+        return None;
+    }
+    let to = parse_square(&s[3..5])?;
+    let piece_type = parse_piece_code(&s[5..7])?;
+
+    let m = if &s[1..3] == "00" {
+        Move::Drop { to, piece_type }
+    } else {
+        let from = parse_square(&s[1..3])?;
+        let moved = pos.piece_at(from)?.piece_type;
+        let promote = moved != piece_type;
+        if promote && moved.promote() != Some(piece_type) {
+            return None;
+        }
+        Move::Normal { from, to, promote }
+    };
+    pos.is_legal(&m).then_some(m)
+}
+
+pub(crate) fn format_move(pos: &Position, m: &Move) -> String {
+    let sign = color_sign(pos.side_to_move());
+    match *m {
+        Move::Normal { from, to, promote } => {
+            let pt = pos.piece_at(from).map_or(PieceType::Pawn, |p| p.piece_type);
+            let pt = if promote {
+                pt.promote().unwrap_or(pt)
+            } else {
+                pt
+            };
+            format!(
+                "{sign}{}{}{}{}{}",
+                from.file(),
+                from.rank(),
+                to.file(),
+                to.rank(),
+                piece_code(pt)
+            )
+        }
+        Move::Drop { to, piece_type } => {
+            format!(
+                "{sign}00{}{}{}",
+                to.file(),
+                to.rank(),
+                piece_code(piece_type)
+            )
+        }
+    }
+}
+
+/// Parses an evaluation comment such as `** 120 3334FU`, and returns the score.
+/// Comments starting with a single `*` are ordinary ones.
+fn parse_evaluation(comment: &str) -> Option<i32> {
+    let rest = comment.strip_prefix("**")?;
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+fn append_comment(comment: &mut Option<String>, text: &str) {
+    match comment {
+        Some(c) => {
+            c.push('\n');
+            c.push_str(text);
+        }
+        None => *comment = Some(text.to_string()),
+    }
+}
+
+fn write_comment(out: &mut String, comment: Option<&str>) {
+    if let Some(comment) = comment {
+        for line in comment.lines() {
+            out.push('\'');
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+}
+
+fn seconds(s: &str) -> Option<Duration> {
+    Duration::try_from_secs_f64(s.parse().ok()?).ok()
+}
+
+/// Parses `$TIME_LIMIT:HH:MM+SS` of CSA V2 and `$TIME:total+byoyomi+increment` of CSA V3.
+fn parse_time_control(key: &str, value: &str) -> Option<TimeControl> {
+    let (time, byoyomi, increment) = match key {
+        "TIME_LIMIT" => {
+            let (time, byoyomi) = value.split_once('+')?;
+            let (h, m) = time.split_once(':')?;
+            let time = h.parse::<u64>().ok()? * 3600 + m.parse::<u64>().ok()? * 60;
+            (
+                Duration::from_secs(time),
+                Duration::from_secs(byoyomi.parse().ok()?),
+                Duration::ZERO,
+            )
+        }
+        "TIME" => {
+            let mut iter = value.split('+');
+            let time = seconds(iter.next()?)?;
+            let byoyomi = iter.next().map_or(Some(Duration::ZERO), seconds)?;
+            let increment = iter.next().map_or(Some(Duration::ZERO), seconds)?;
+            (time, byoyomi, increment)
+        }
+        _ => return None,
+    };
+
+    Some(time_control(time, byoyomi, increment))
+}
+
+/// Returns the time control represented by the total time, byoyomi and increment in CSA.
+pub(crate) fn time_control(time: Duration, byoyomi: Duration, increment: Duration) -> TimeControl {
+    if !increment.is_zero() {
+        TimeControl::Fischer { time, increment }
+    } else if time.is_zero() && !byoyomi.is_zero() {
+        TimeControl::FixedPerMove(byoyomi)
+    } else {
+        TimeControl::Byoyomi { time, byoyomi }
+    }
+}
+
+fn time_control_header(tc: &TimeControl) -> String {
+    match *tc {
+        TimeControl::Byoyomi { time, byoyomi }
+            if time.subsec_nanos() == 0
+                && time.as_secs().is_multiple_of(60)
+                && byoyomi.subsec_nanos() == 0 =>
+        {
+            let minutes = time.as_secs() / 60;
+            format!(
+                "$TIME_LIMIT:{:02}:{:02}+{:02}",
+                minutes / 60,
+                minutes % 60,
+                byoyomi.as_secs()
+            )
+        }
+        TimeControl::Byoyomi { time, byoyomi } => {
+            format!("$TIME:{}+{}+0", time.as_secs_f64(), byoyomi.as_secs_f64())
+        }
+        TimeControl::Fischer { time, increment } => {
+            format!("$TIME:{}+0+{}", time.as_secs_f64(), increment.as_secs_f64())
+        }
+        TimeControl::FixedPerMove(byoyomi) => format!("$TIME:0+{}+0", byoyomi.as_secs_f64()),
+    }
+}
+
+/// Parses a special move without `%`, and returns the result.
+/// Returns `Some(None)` for special moves without results, such as `CHUDAN`.
+pub(crate) fn parse_special_move(s: &str, side_to_move: Color) -> Option<Option<GameResult>> {
+    let result = |winner, reason| Some(Some(GameResult { winner, reason }));
+    let (win, lose) = (Some(side_to_move), Some(side_to_move.flip()));
+
+    match s {
+        "TORYO" => result(lose, GameEndReason::Resign),
+        "TSUMI" => result(lose, GameEndReason::Checkmate),
+        "TIME_UP" => result(lose, GameEndReason::Timeout),
+        "ILLEGAL_MOVE" => result(lose, GameEndReason::IllegalMove),
+        "+ILLEGAL_ACTION" => result(Some(Color::White), GameEndReason::IllegalMove),
+        "-ILLEGAL_ACTION" => result(Some(Color::Black), GameEndReason::IllegalMove),
+        "KACHI" => result(win, GameEndReason::DeclareWin),
+        "SENNICHITE" => result(None, GameEndReason::Repetition),
+        "JISHOGI" => result(None, GameEndReason::Adjudicated(Adjudication::Impasse)),
+        "HIKIWAKE" => result(None, GameEndReason::Adjudicated(Adjudication::DrawScore)),
+        "MAX_MOVES" => result(None, GameEndReason::MaxMoves),
+        "CHUDAN" | "MATTA" | "FUZUMI" | "ERROR" => Some(None),
+        _ => None,
+    }
+}
+
+/// Returns the special move representing the result, from the point of view of the side to move.
+pub(crate) fn special_move(result: &GameResult, side_to_move: Color) -> &'static str {
+    match result.reason {
+        GameEndReason::Resign | GameEndReason::Adjudicated(Adjudication::ResignScore) => "%TORYO",
+        GameEndReason::Checkmate => "%TSUMI",
+        GameEndReason::Timeout => "%TIME_UP",
+        GameEndReason::DeclareWin => "%KACHI",
+        GameEndReason::IllegalMove | GameEndReason::PerpetualCheck => match result.winner {
+            Some(c) if c == side_to_move.flip() => "%ILLEGAL_MOVE",
+            Some(Color::Black) => "%-ILLEGAL_ACTION",
+            Some(Color::White) => "%+ILLEGAL_ACTION",
+            None => "%HIKIWAKE",
+        },
+        GameEndReason::Repetition => "%SENNICHITE",
+        GameEndReason::Adjudicated(Adjudication::Impasse) => "%JISHOGI",
+        GameEndReason::Adjudicated(Adjudication::DrawScore) => "%HIKIWAKE",
+        GameEndReason::MaxMoves => "%MAX_MOVES",
+        GameEndReason::Disconnected => "%CHUDAN",
+    }
+}
+
+fn write_position(out: &mut String, pos: &Position) {
+    let startpos = Position::startpos();
+    if pos
+        .to_sfen()
+        .split_whitespace()
+        .take(3)
+        .eq(startpos.to_sfen().split_whitespace().take(3))
+    {
+        out.push_str("PI\n+\n");
+        return;
+    }
+
+    for rank in 1..=9 {
+        out.push_str(&format!("P{rank}"));
+        for file in (1..=9).rev() {
+            match pos.piece_at(Square::new(file, rank).unwrap()) {
+                Some(p) => {
+                    out.push(color_sign(p.color));
+                    out.push_str(piece_code(p.piece_type));
+                }
+                None => out.push_str(" * "),
+            }
+        }
+        out.push('\n');
+    }
+    for color in [Color::Black, Color::White] {
+        let hand = PieceType::HAND_TYPES
+            .iter()
+            .flat_map(|pt| std::iter::repeat_n(*pt, pos.hand(color, *pt) as usize))
+            .map(|pt| format!("00{}", piece_code(pt)))
+            .collect::<String>();
+        if !hand.is_empty() {
+            out.push_str(&format!("P{}{}\n", color_sign(color), hand));
+        }
+    }
+    out.push(color_sign(pos.side_to_move()));
+    out.push('\n');
+}
+
+/// `Setup` collects the start position written in `PI`, `P1` to `P9`, `P+` and `P-` lines.
+pub(crate) struct Setup {
+    /// Indexed by `Square::index`.
+    pub board: [Option<Piece>; 81],
+    /// Indexed by color and hand index.
+    pub hands: [[u8; 7]; 2],
+}
+
+impl Setup {
+    pub(crate) fn new() -> Setup {
+        let mut setup = Setup {
+            board: [None; 81],
+            hands: [[0; 7]; 2],
+        };
+        setup.parse("PI").unwrap();
+        setup
+    }
+
+    /// Applies a line starting with `P`.
+    pub(crate) fn parse(&mut self, line: &str) -> Option<()> {
+        if !line.is_ascii() {
+            return None;
+        }
+        let (kind, body) = line.split_at(2);
+        match kind.as_bytes()[1] {
+            b'I' => {
+                let pos = Position::startpos();
+                for sq in Square::iter() {
+                    self.board[sq.index()] = pos.piece_at(sq);
+                }
+                self.hands = [[0; 7]; 2];
+                // Pieces removed in handicap games, such as `82HI`.
+                for i in (0..body.len()).step_by(4) {
+                    let sq = parse_square(body.get(i..i + 2)?)?;
+                    let pt = parse_piece_code(body.get(i + 2..i + 4)?)?;
+                    if self.board[sq.index()]?.piece_type != pt {
+                        return None;
+                    }
+                    self.board[sq.index()] = None;
+                }
+            }
+            rank @ b'1'..=b'9' => {
+                let rank = rank - b'0';
+                if rank == 1 {
+                    self.board = [None; 81];
+                }
+                for (i, file) in (1..=9).rev().enumerate() {
+                    let cell = body.get(i * 3..i * 3 + 3).unwrap_or(" * ");
+                    let sq = Square::new(file, rank)?;
+                    self.board[sq.index()] = match cell.trim() {
+                        "*" | "" => None,
+                        cell => Some(Piece::new(
+                            parse_piece_code(cell.get(1..)?)?,
+                            parse_color(cell.as_bytes()[0])?,
+                        )),
+                    };
+                }
+                if body.len() > 27 {
+                    return None;
+                }
+            }
+            c @ (b'+' | b'-') => {
+                let color = parse_color(c)?;
+                for i in (0..body.len()).step_by(4) {
+                    let square = body.get(i..i + 2)?;
+                    let code = body.get(i + 2..i + 4)?;
+                    match (square, code) {
+                        ("00", "AL") => self.fill_hand(color),
+                        ("00", code) => {
+                            let idx = parse_piece_code(code)?.hand_index()?;
+                            self.hands[color.index()][idx] += 1;
+                        }
+                        (square, code) => {
+                            let sq = parse_square(square)?;
+                            let pt = parse_piece_code(code)?;
+                            self.board[sq.index()] = Some(Piece::new(pt, color));
+                        }
+                    }
+                }
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+
+    /// Puts all pieces not on the board nor in hands into the hand of `color`.
+    fn fill_hand(&mut self, color: Color) {
+        for (idx, pt) in PieceType::HAND_TYPES.iter().enumerate() {
+            let on_board = self
+                .board
+                .iter()
+                .flatten()
+                .filter(|p| p.piece_type.unpromote() == *pt)
+                .count() as u8;
+            let in_hands = self.hands[0][idx] + self.hands[1][idx];
+            self.hands[color.index()][idx] += PIECE_COUNTS[idx].saturating_sub(on_board + in_hands);
+        }
+    }
+
+    pub(crate) fn to_sfen(&self, side: Color) -> String {
+        let mut sfen = String::new();
+        for rank in 1..=9 {
+            let mut empty = 0;
+            for file in (1..=9).rev() {
+                match self.board[Square::new(file, rank).unwrap().index()] {
+                    Some(p) => {
+                        if empty > 0 {
+                            sfen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        sfen.push_str(&p.to_string());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                sfen.push_str(&empty.to_string());
+            }
+            if rank < 9 {
+                sfen.push('/');
+            }
+        }
+
+        let mut hand = String::new();
+        for color in [Color::Black, Color::White] {
+            for (idx, pt) in PieceType::HAND_TYPES.iter().enumerate() {
+                let n = self.hands[color.index()][idx];
+                if n > 1 {
+                    hand.push_str(&n.to_string());
+                }
+                if n > 0 {
+                    hand.push_str(&Piece::new(*pt, color).to_string());
+                }
+            }
+        }
+        if hand.is_empty() {
+            hand.push('-');
+        }
+
+        format!("{sfen} {side} {hand} 1")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shogi::STARTPOS_SFEN;
+
+    const CSA: &str = "\
+V2.2
+N+Black Engine
+N-White Engine
+$EVENT:floodgate-300-10F
+$START_TIME:2024/01/02 10:00:00
+$TIME_LIMIT:00:10+10
+'対局前のコメント
+P1-KY-KE-GI-KI-OU-KI-GI-KE-KY
+P2 * -HI *  *  *  *  * -KA *
+P3-FU-FU-FU-FU-FU-FU-FU-FU-FU
+P4 *  *  *  *  *  *  *  *  *
+P5 *  *  *  *  *  *  *  *  *
+P6 *  *  *  *  *  *  *  *  *
+P7+FU+FU+FU+FU+FU+FU+FU+FU+FU
+P8 * +KA *  *  *  *  * +HI *
+P9+KY+KE+GI+KI+OU+KI+GI+KE+KY
++
++7776FU,T3
+'** 30 3334FU
+-3334FU
+T1
+'** -45 8822UM
+'角道を開ける
++8822UM
+T10
+'* -45 8822UM
+-3122GI
+T2
++0045KA
+T5
+%TORYO
+";
+
+    #[test]
+    fn parse() {
+        let record = GameRecord::from_csa(CSA).unwrap();
+        assert_eq!("Black Engine", record.black_name);
+        assert_eq!("White Engine", record.white_name);
+        assert_eq!(
+            vec![
+                ("EVENT".to_string(), "floodgate-300-10F".to_string()),
+                ("START_TIME".to_string(), "2024/01/02 10:00:00".to_string()),
+            ],
+            record.headers
+        );
+        assert_eq!(
+            Some(TimeControl::Byoyomi {
+                time: Duration::from_secs(600),
+                byoyomi: Duration::from_secs(10),
+            }),
+            record.time_control
+        );
+        assert_eq!(STARTPOS_SFEN, record.start_sfen);
+        assert_eq!(Some("対局前のコメント"), record.comment.as_deref());
+        assert_eq!(
+            format!("position sfen {STARTPOS_SFEN} moves 7g7f 3c3d 8h2b+ 3a2b B*4e"),
+            record.position_command(5).to_string()
+        );
+        assert_eq!(Some(Duration::from_secs(3)), record.moves[0].elapsed);
+        // Evaluations are converted to the point of view of the player who moved.
+        assert_eq!(Some((30, ScoreKind::CpExact)), record.moves[0].score);
+        assert_eq!(Some((45, ScoreKind::CpExact)), record.moves[1].score);
+        assert_eq!(Some("角道を開ける"), record.moves[1].comment.as_deref());
+        // A single asterisk marks an ordinary comment.
+        assert_eq!(None, record.moves[2].score);
+        assert_eq!(Some("* -45 8822UM"), record.moves[2].comment.as_deref());
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: GameEndReason::Resign,
+            }),
+            record.result
+        );
+
+        let ng_cases = [
+            "PI\n+\n+7775FU\n",
+            "PI\n+\n-3334FU\n",
+            "PI\n+\n+7776KI\n",
+            "PI\n+\nT3\n",
+            "PI\n+\n%UNKNOWN\n",
+            "PI82KA\n+\n",
+            "PI\n",
+        ];
+        for s in ng_cases {
+            assert!(GameRecord::from_csa(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn positions() {
+        let record = GameRecord::from_csa("PI82HI\n-\n").unwrap();
+        assert_eq!(
+            "lnsgkgsnl/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            record.start_sfen
+        );
+
+        let record = GameRecord::from_csa(
+            "P1 *  *  *  *  *  *  *  * -OU\nP2 *  *  *  *  *  *  *  *  * \n\
+             P3 *  *  *  *  *  *  *  *  * \nP4 *  *  *  *  *  *  *  *  * \n\
+             P5 *  *  *  *  *  *  *  *  * \nP6 *  *  *  *  *  *  *  *  * \n\
+             P7 *  *  *  *  *  *  *  *  * \nP8 *  *  *  *  *  *  *  *  * \n\
+             P9+OU *  *  *  *  *  *  *  * \nP+00KI00FU00FU\nP-00AL\n+\n",
+        )
+        .unwrap();
+        assert_eq!(
+            "8k/9/9/9/9/9/9/9/K8 b G2P2r2b3g4s4n4l16p 1",
+            record.start_sfen
+        );
+    }
+
+    #[test]
+    fn roundtrip() {
+        let record = GameRecord::from_csa(CSA).unwrap();
+        let csa = record.to_csa();
+        assert!(csa.contains("\nPI\n+\n+7776FU\nT3\n'** 30\n"), "{csa}");
+        assert!(
+            csa.contains("\n-3334FU\nT1\n'** -45\n'角道を開ける\n"),
+            "{csa}"
+        );
+        assert!(csa.contains("\n$TIME_LIMIT:00:10+10\n"), "{csa}");
+        assert!(csa.ends_with("\n+0045KA\nT5\n%TORYO\n"), "{csa}");
+        assert_eq!(record, GameRecord::from_csa(&csa).unwrap());
+
+        let record = GameRecord {
+            start_sfen:
+                "lr5nl/2g1kg3/p1nps1ppp/2pp5/1p7/2P1P4/PPSP1PPPP/2GB1S3/LN1K1G1NL w RBs2p 1"
+                    .to_string(),
+            time_control: Some(TimeControl::Fischer {
+                time: Duration::from_secs(300),
+                increment: Duration::from_secs(2),
+            }),
+            moves: vec![MoveRecord::new("S*3b".parse().unwrap())],
+            result: Some(GameResult {
+                winner: None,
+                reason: GameEndReason::Repetition,
+            }),
+            ..Default::default()
+        };
+        let csa = record.to_csa();
+        assert!(csa.contains("\n$TIME:300+0+2\n"), "{csa}");
+        assert!(
+            csa.contains("\nP+00HI00KA\nP-00GI00FU00FU\n-\n-0032GI\n"),
+            "{csa}"
+        );
+        assert_eq!(record, GameRecord::from_csa(&csa).unwrap());
+    }
+}
@@ -0,0 +1,599 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+use serde::de::{MapAccess, Visitor};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::csa::{parse_piece_code, parse_special_move, piece_code, special_move, Setup};
+use super::{player_labels, promotion_suffix, HANDICAPS};
+use crate::error::Error;
+use crate::game::{GameRecord, GameResult, MoveRecord};
+use crate::shogi::{Color, Move, Piece, PieceType, Position, Square, STARTPOS_SFEN};
+
+/// Presets of start positions in JKF, and the corresponding names of handicaps.
+const PRESETS: [(&str, &str); 10] = [
+    ("KY", "香落ち"),
+    ("KY_R", "右香落ち"),
+    ("KA", "角落ち"),
+    ("HI", "飛車落ち"),
+    ("HIKY", "飛香落ち"),
+    ("2", "二枚落ち"),
+    ("4", "四枚落ち"),
+    ("6", "六枚落ち"),
+    ("8", "八枚落ち"),
+    ("10", "十枚落ち"),
+];
+
+/// Headers kept in the order written, while JSON objects are unordered in general.
+#[derive(Debug, Default)]
+struct Header(Vec<(String, String)>);
+
+impl Serialize for Header {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (k, v) in &self.0 {
+            map.serialize_entry(k, v)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Header {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Header, D::Error> {
+        struct HeaderVisitor;
+
+        impl<'de> Visitor<'de> for HeaderVisitor {
+            type Value = Header;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a map of strings")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Header, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(Header(entries))
+            }
+        }
+
+        deserializer.deserialize_map(HeaderVisitor)
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Jkf {
+    #[serde(default)]
+    header: Header,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    initial: Option<Initial>,
+    #[serde(default)]
+    moves: Vec<JkfMove>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Initial {
+    preset: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    data: Option<State>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct State {
+    color: u8,
+    /// Indexed by file and rank, both counted from 0.
+    board: Vec<Vec<JkfPiece>>,
+    hands: Vec<BTreeMap<String, u8>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JkfPiece {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct JkfMove {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comments: Option<Vec<String>>,
+    #[serde(rename = "move", default, skip_serializing_if = "Option::is_none")]
+    mv: Option<MoveFormat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    time: Option<Time>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    special: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    forks: Option<Vec<Vec<JkfMove>>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MoveFormat {
+    color: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    from: Option<Place>,
+    to: Place,
+    piece: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    same: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    promote: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    capture: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    relative: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Place {
+    x: u8,
+    y: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Time {
+    now: MinSec,
+    total: HourMinSec,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MinSec {
+    m: u64,
+    s: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HourMinSec {
+    h: u64,
+    m: u64,
+    s: u64,
+}
+
+impl GameRecord {
+    /// Parses a game record in JSON Kifu Format.
+    ///
+    /// Forks are stored as variations, and comments and time spent on moves are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use usi::GameRecord;
+    ///
+    /// let jkf = r#"{
+    ///     "header": {"先手": "Black", "後手": "White"},
+    ///     "moves": [
+    ///         {},
+    ///         {"move": {"color": 0, "from": {"x": 7, "y": 7}, "to": {"x": 7, "y": 6}, "piece": "FU"}},
+    ///         {"special": "TORYO"}
+    ///     ]
+    /// }"#;
+    /// let record = GameRecord::from_jkf(jkf).unwrap();
+    /// assert_eq!("Black", record.black_name);
+    /// assert_eq!("7g7f", record.moves[0].mv.to_string());
+    /// ```
+    pub fn from_jkf(s: &str) -> Result<GameRecord, Error> {
+        let jkf: Jkf = serde_json::from_str(s).map_err(|e| Error::IllegalRecord(e.to_string()))?;
+        let mut record = GameRecord::default();
+
+        for (k, v) in jkf.header.0 {
+            match k.as_str() {
+                "先手" | "下手" => record.black_name = v,
+                "後手" | "上手" => record.white_name = v,
+                _ => record.headers.push((k, v)),
+            }
+        }
+
+        let start = Position::from_sfen(&initial_sfen(jkf.initial.as_ref())?)?;
+        record.start_sfen = start.to_sfen();
+
+        let mut moves = jkf.moves.as_slice();
+        if let Some(first) = moves
+            .first()
+            .filter(|m| m.mv.is_none() && m.special.is_none())
+        {
+            record.comment = first.comments.as_ref().map(|c| c.join("\n"));
+            moves = &moves[1..];
+        }
+        let (moves, result) = read_line(&start, moves)?;
+        record.moves = moves;
+        record.result = result;
+        Ok(record)
+    }
+
+    /// Returns the record in JSON Kifu Format.
+    pub fn to_jkf(&self) -> String {
+        let start = Position::from_sfen(&self.start_sfen).unwrap_or_default();
+        let (black, white) = player_labels(&start);
+        let mut header = vec![
+            (black.to_string(), self.black_name.clone()),
+            (white.to_string(), self.white_name.clone()),
+        ];
+        header.extend(self.headers.iter().cloned());
+
+        let mut moves = vec![JkfMove {
+            comments: self.comment.as_ref().map(|c| comments(c)),
+            ..Default::default()
+        }];
+        let pos = write_line(&mut moves, &start, &self.moves);
+        if let Some(ref result) = self.result {
+            moves.push(JkfMove {
+                special: Some(special_move(result, pos.side_to_move())[1..].to_string()),
+                ..Default::default()
+            });
+        }
+
+        let jkf = Jkf {
+            header: Header(header),
+            initial: Some(initial(&start)),
+            moves,
+        };
+        serde_json::to_string(&jkf).expect("JKF must be serializable")
+    }
+}
+
+fn color_number(c: Color) -> u8 {
+    match c {
+        Color::Black => 0,
+        Color::White => 1,
+    }
+}
+
+fn parse_color_number(n: u8) -> Result<Color, Error> {
+    match n {
+        0 => Ok(Color::Black),
+        1 => Ok(Color::White),
+        _ => Err(Error::IllegalRecord(format!("unknown color {n}"))),
+    }
+}
+
+fn comments(s: &str) -> Vec<String> {
+    s.lines().map(str::to_string).collect()
+}
+
+fn initial_sfen(initial: Option<&Initial>) -> Result<String, Error> {
+    let initial = match initial {
+        Some(initial) => initial,
+        None => return Ok(STARTPOS_SFEN.to_string()),
+    };
+
+    match (initial.preset.as_str(), &initial.data) {
+        ("HIRATE", _) => Ok(STARTPOS_SFEN.to_string()),
+        ("OTHER", Some(state)) => {
+            let unknown = |s: &str| Error::IllegalRecord(format!("unknown piece {s}"));
+            let mut setup = Setup {
+                board: [None; 81],
+                hands: [[0; 7]; 2],
+            };
+            for (x, column) in state.board.iter().enumerate().take(9) {
+                for (y, cell) in column.iter().enumerate().take(9) {
+                    if let (Some(color), Some(kind)) = (cell.color, &cell.kind) {
+                        let pt = parse_piece_code(kind).ok_or_else(|| unknown(kind))?;
+                        let sq = Square::new(x as u8 + 1, y as u8 + 1).unwrap();
+                        setup.board[sq.index()] = Some(Piece::new(pt, parse_color_number(color)?));
+                    }
+                }
+            }
+            for (c, hand) in state.hands.iter().enumerate().take(2) {
+                for (kind, n) in hand {
+                    let idx = parse_piece_code(kind)
+                        .and_then(PieceType::hand_index)
+                        .ok_or_else(|| unknown(kind))?;
+                    setup.hands[c][idx] = *n;
+                }
+            }
+            Ok(setup.to_sfen(parse_color_number(state.color)?))
+        }
+        (preset, _) => PRESETS
+            .iter()
+            .find(|(p, _)| *p == preset)
+            .and_then(|(_, name)| HANDICAPS.iter().find(|(n, _)| n == name))
+            .map(|(_, sfen)| sfen.to_string())
+            .ok_or_else(|| Error::IllegalRecord(format!("unknown preset {preset}"))),
+    }
+}
+
+fn initial(start: &Position) -> Initial {
+    let sfen = start.to_sfen();
+    let preset = if sfen == STARTPOS_SFEN {
+        Some("HIRATE")
+    } else {
+        HANDICAPS
+            .iter()
+            .find(|(_, s)| *s == sfen)
+            .and_then(|(name, _)| PRESETS.iter().find(|(_, n)| n == name))
+            .map(|(p, _)| *p)
+    };
+    if let Some(preset) = preset {
+        return Initial {
+            preset: preset.to_string(),
+            data: None,
+        };
+    }
+
+    let board = (1..=9)
+        .map(|file| {
+            (1..=9)
+                .map(
+                    |rank| match start.piece_at(Square::new(file, rank).unwrap()) {
+                        Some(p) => JkfPiece {
+                            color: Some(color_number(p.color)),
+                            kind: Some(piece_code(p.piece_type).to_string()),
+                        },
+                        None => JkfPiece::default(),
+                    },
+                )
+                .collect()
+        })
+        .collect();
+    let hands = [Color::Black, Color::White]
+        .iter()
+        .map(|c| {
+            PieceType::HAND_TYPES
+                .iter()
+                .map(|pt| (piece_code(*pt).to_string(), start.hand(*c, *pt)))
+                .collect()
+        })
+        .collect();
+    Initial {
+        preset: "OTHER".to_string(),
+        data: Some(State {
+            color: color_number(start.side_to_move()),
+            board,
+            hands,
+        }),
+    }
+}
+
+/// Reads moves from the position, and returns them with the result if a special move ends them.
+fn read_line(
+    start: &Position,
+    moves: &[JkfMove],
+) -> Result<(Vec<MoveRecord>, Option<GameResult>), Error> {
+    let mut pos = start.clone();
+    let mut records: Vec<MoveRecord> = Vec::new();
+    let mut result = None;
+
+    for jm in moves {
+        if let Some(ref special) = jm.special {
+            result = parse_special_move(special, pos.side_to_move())
+                .ok_or_else(|| Error::IllegalRecord(format!("unknown special move {special}")))?;
+            break;
+        }
+        let mf = match jm.mv {
+            Some(ref mf) => mf,
+            None => continue,
+        };
+
+        let illegal = || Error::IllegalMove(format!("{mf:?}"));
+        let to = Square::new(mf.to.x, mf.to.y).ok_or_else(illegal)?;
+        let piece_type = parse_piece_code(&mf.piece).ok_or_else(illegal)?;
+        let m = match mf.from {
+            Some(ref from) => Move::Normal {
+                from: Square::new(from.x, from.y).ok_or_else(illegal)?,
+                to,
+                promote: mf.promote.unwrap_or(false),
+            },
+            None => Move::Drop { to, piece_type },
+        };
+        if parse_color_number(mf.color)? != pos.side_to_move() {
+            return Err(illegal());
+        }
+
+        let mut variations = Vec::new();
+        for fork in jm.forks.iter().flatten() {
+            variations.push(read_line(&pos, fork)?.0);
+        }
+        pos.make_move(&m)?;
+        records.push(MoveRecord {
+            elapsed: jm
+                .time
+                .as_ref()
+                .map(|t| Duration::from_secs(t.now.m * 60 + t.now.s)),
+            comment: jm.comments.as_ref().map(|c| c.join("\n")),
+            variations,
+            ..MoveRecord::new(m)
+        });
+    }
+    Ok((records, result))
+}
+
+/// Writes moves from the position, and returns the position after them.
+fn write_line(out: &mut Vec<JkfMove>, start: &Position, moves: &[MoveRecord]) -> Position {
+    let mut pos = start.clone();
+    let mut prev_to = None;
+    let mut total = [Duration::ZERO; 2];
+
+    for m in moves {
+        let side = pos.side_to_move();
+        let promote = match promotion_suffix(&pos, &m.mv) {
+            "成" => Some(true),
+            "不成" => Some(false),
+            _ => None,
+        };
+        let to = m.mv.to();
+        let (from, piece_type) = match m.mv {
+            Move::Normal { from, .. } => (
+                Some(Place {
+                    x: from.file(),
+                    y: from.rank(),
+                }),
+                pos.piece_at(from).map_or(PieceType::Pawn, |p| p.piece_type),
+            ),
+            Move::Drop { piece_type, .. } => (None, piece_type),
+        };
+        let mf = MoveFormat {
+            color: color_number(side),
+            from,
+            to: Place {
+                x: to.file(),
+                y: to.rank(),
+            },
+            piece: piece_code(piece_type).to_string(),
+            same: (prev_to == Some(to)).then_some(true),
+            promote,
+            capture: pos
+                .piece_at(to)
+                .map(|p| piece_code(p.piece_type).to_string()),
+            relative: None,
+        };
+
+        let time = m.elapsed.map(|elapsed| {
+            let t = &mut total[side.index()];
+            *t += elapsed;
+            let (now, total) = (elapsed.as_secs(), t.as_secs());
+            Time {
+                now: MinSec {
+                    m: now / 60,
+                    s: now % 60,
+                },
+                total: HourMinSec {
+                    h: total / 3600,
+                    m: total / 60 % 60,
+                    s: total % 60,
+                },
+            }
+        });
+
+        let forks = (!m.variations.is_empty()).then(|| {
+            m.variations
+                .iter()
+                .map(|v| {
+                    let mut fork = Vec::new();
+                    write_line(&mut fork, &pos, v);
+                    fork
+                })
+                .collect()
+        });
+
+        out.push(JkfMove {
+            comments: m.comment.as_deref().map(comments),
+            mv: Some(mf),
+            time,
+            special: None,
+            forks,
+        });
+        pos.make_move(&m.mv)
+            .expect("moves in a record must be legal");
+        prev_to = Some(to);
+    }
+    pos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::GameEndReason;
+
+    const JKF: &str = r#"{
+  "header": {"棋戦": "テスト", "先手": "先手太郎", "後手": "後手花子"},
+  "moves": [
+    {"comments": ["対局前のコメント"]},
+    {
+      "move": {"color": 0, "from": {"x": 7, "y": 7}, "to": {"x": 7, "y": 6}, "piece": "FU"},
+      "time": {"now": {"m": 0, "s": 3}, "total": {"h": 0, "m": 0, "s": 3}},
+      "comments": ["角道を開ける"]
+    },
+    {
+      "move": {"color": 1, "from": {"x": 3, "y": 3}, "to": {"x": 3, "y": 4}, "piece": "FU"},
+      "forks": [
+        [
+          {"move": {"color": 1, "from": {"x": 8, "y": 3}, "to": {"x": 8, "y": 4}, "piece": "FU"}},
+          {"move": {"color": 0, "from": {"x": 2, "y": 7}, "to": {"x": 2, "y": 6}, "piece": "FU"}}
+        ]
+      ]
+    },
+    {
+      "move": {"color": 0, "from": {"x": 8, "y": 8}, "to": {"x": 2, "y": 2}, "piece": "KA",
+               "promote": true, "capture": "KA"}
+    },
+    {
+      "move": {"color": 1, "from": {"x": 3, "y": 1}, "to": {"x": 2, "y": 2}, "piece": "GI",
+               "same": true, "capture": "UM"}
+    },
+    {"move": {"color": 0, "to": {"x": 4, "y": 5}, "piece": "KA"}},
+    {"special": "TORYO"}
+  ]
+}"#;
+
+    #[test]
+    fn parse() {
+        let record = GameRecord::from_jkf(JKF).unwrap();
+        assert_eq!("先手太郎", record.black_name);
+        assert_eq!("後手花子", record.white_name);
+        assert_eq!(
+            vec![("棋戦".to_string(), "テスト".to_string())],
+            record.headers
+        );
+        assert_eq!(Some("対局前のコメント"), record.comment.as_deref());
+        assert_eq!(
+            format!("position sfen {STARTPOS_SFEN} moves 7g7f 3c3d 8h2b+ 3a2b B*4e"),
+            record.position_command(5).to_string()
+        );
+        assert_eq!(Some(Duration::from_secs(3)), record.moves[0].elapsed);
+        assert_eq!(Some("角道を開ける"), record.moves[0].comment.as_deref());
+        assert_eq!(
+            vec!["8c8d", "2g2f"],
+            record.moves[1].variations[0]
+                .iter()
+                .map(|m| m.mv.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: GameEndReason::Resign,
+            }),
+            record.result
+        );
+
+        let ng_cases = [
+            "{",
+            r#"{"initial": {"preset": "UNKNOWN"}}"#,
+            r#"{"moves": [{}, {"move": {"color": 1, "from": {"x": 7, "y": 7}, "to": {"x": 7, "y": 6}, "piece": "FU"}}]}"#,
+            r#"{"moves": [{}, {"move": {"color": 0, "from": {"x": 7, "y": 7}, "to": {"x": 7, "y": 5}, "piece": "FU"}}]}"#,
+        ];
+        for s in ng_cases {
+            assert!(GameRecord::from_jkf(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let record = GameRecord::from_jkf(JKF).unwrap();
+        let jkf = record.to_jkf();
+        assert!(
+            jkf.starts_with(
+                r#"{"header":{"先手":"先手太郎","後手":"後手花子","棋戦":"テスト"},"initial":{"preset":"HIRATE"}"#
+            ),
+            "{jkf}"
+        );
+        assert!(
+            jkf.contains(r#"{"move":{"color":1,"from":{"x":3,"y":1},"to":{"x":2,"y":2},"piece":"GI","same":true,"capture":"UM"}}"#),
+            "{jkf}"
+        );
+        assert_eq!(record, GameRecord::from_jkf(&jkf).unwrap());
+
+        let record = GameRecord {
+            start_sfen:
+                "lr5nl/2g1kg3/p1nps1ppp/2pp5/1p7/2P1P4/PPSP1PPPP/2GB1S3/LN1K1G1NL w RBs2p 1"
+                    .to_string(),
+            moves: vec![MoveRecord::new("S*3b".parse().unwrap())],
+            ..Default::default()
+        };
+        let jkf = record.to_jkf();
+        assert!(jkf.contains(r#""preset":"OTHER""#), "{jkf}");
+        assert_eq!(record, GameRecord::from_jkf(&jkf).unwrap());
+
+        let record = GameRecord {
+            start_sfen: "lnsgkgsnl/1r7/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1".to_string(),
+            ..Default::default()
+        };
+        let jkf = record.to_jkf();
+        assert!(jkf.contains(r#""initial":{"preset":"KA"}"#), "{jkf}");
+        assert_eq!(record, GameRecord::from_jkf(&jkf).unwrap());
+    }
+}
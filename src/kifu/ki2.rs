@@ -0,0 +1,391 @@
+use super::{
+    move_prefix, parse_destination, parse_piece_name, parse_summary, promotion_suffix,
+    record_error, result_to_ja, write_comment, write_preamble, write_variations, RecordBuilder,
+};
+use crate::error::Error;
+use crate::game::{GameRecord, MoveRecord};
+use crate::shogi::{Color, Move, PieceType, Position, Square};
+
+/// Number of moves written in a line.
+const MOVES_PER_LINE: usize = 6;
+
+impl GameRecord {
+    /// Parses a game record in KI2 format, where moves are written without their origins
+    /// and disambiguated with relative positions such as `右` and `上` instead.
+    ///
+    /// The result is read from the summary line such as `まで64手で後手の勝ち`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use usi::GameRecord;
+    ///
+    /// let record = GameRecord::from_ki2("▲７六歩 △３四歩 ▲５八金右\n").unwrap();
+    /// assert_eq!("4i5h", record.moves[2].mv.to_string());
+    /// ```
+    pub fn from_ki2(s: &str) -> Result<GameRecord, Error> {
+        let mut builder = RecordBuilder::new();
+
+        for (n, line) in s.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('&') {
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix('*') {
+                builder.comment(comment);
+                continue;
+            }
+            if let Some(ply) = line
+                .strip_prefix("変化：")
+                .or_else(|| line.strip_prefix("変化:"))
+            {
+                let ply = ply
+                    .trim()
+                    .trim_end_matches('手')
+                    .parse()
+                    .map_err(|_| record_error(n, line))?;
+                builder.variation(n, ply)?;
+                continue;
+            }
+            if line.starts_with("まで") {
+                if let Some(winner) = parse_summary(line) {
+                    builder.set_winner(winner);
+                }
+                continue;
+            }
+
+            if !line.starts_with(is_marker) {
+                if builder.preamble(n, line)? {
+                    continue;
+                }
+                return Err(record_error(n, line));
+            }
+            for token in line.split(is_marker).skip(1) {
+                let token = token.trim_matches([' ', '　']);
+                builder.start()?;
+                let m = parse_move(&builder.pos, token, builder.prev_to)
+                    .ok_or_else(|| record_error(n, token))?;
+                builder.push_move(m, None)?;
+            }
+        }
+
+        builder.finish()
+    }
+
+    /// Returns the record in KI2 format.
+    pub fn to_ki2(&self) -> String {
+        let start = Position::from_sfen(&self.start_sfen).unwrap_or_default();
+        let mut out = String::new();
+        write_preamble(&mut out, self, &start);
+        out.push('\n');
+
+        let pos = write_line(&mut out, &start, &self.moves);
+        if let Some(ref result) = self.result {
+            let (_, summary) = result_to_ja(result, pos.side_to_move());
+            out.push_str(&format!("まで{}手で{}\n", self.moves.len(), summary));
+        }
+
+        write_variations(
+            &mut out,
+            &start,
+            &[],
+            &self.moves,
+            &mut |out, pos, moves, _| {
+                write_line(out, pos, moves);
+            },
+        );
+        out
+    }
+}
+
+fn is_marker(c: char) -> bool {
+    matches!(c, '▲' | '△' | '☗' | '☖')
+}
+
+/// Returns true if the move matches a modifier of the direction, such as `上`.
+fn matches_direction(modifier: char, from: Square, to: Square, color: Color) -> bool {
+    let (from_rank, to_rank) = (from.relative_rank(color), to.relative_rank(color));
+    match modifier {
+        '上' | '行' | '入' => to_rank < from_rank,
+        '引' => to_rank > from_rank,
+        '寄' => to_rank == from_rank,
+        '直' => to_rank < from_rank && from.file() == to.file(),
+        _ => false,
+    }
+}
+
+/// Keeps origins on the rightmost (`右`) or leftmost (`左`) file seen from the player.
+fn select_side(modifier: char, origins: &[Square], color: Color) -> Vec<Square> {
+    let relative_file = |sq: &Square| match color {
+        Color::Black => sq.file(),
+        Color::White => 10 - sq.file(),
+    };
+    let target = match modifier {
+        '右' => origins.iter().map(relative_file).min(),
+        _ => origins.iter().map(relative_file).max(),
+    };
+    origins
+        .iter()
+        .filter(|sq| Some(relative_file(sq)) == target)
+        .copied()
+        .collect()
+}
+
+/// Returns origins of legal moves of the same piece type to the same square as `m`.
+fn candidates(pos: &Position, piece_type: PieceType, to: Square, promote: bool) -> Vec<Square> {
+    pos.legal_moves()
+        .into_iter()
+        .filter_map(|m| match m {
+            Move::Normal {
+                from,
+                to: t,
+                promote: p,
+            } if t == to
+                && p == promote
+                && pos.piece_at(from).map(|p| p.piece_type) == Some(piece_type) =>
+            {
+                Some(from)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn parse_move(pos: &Position, token: &str, prev_to: Option<Square>) -> Option<Move> {
+    let (to, rest) = parse_destination(token, prev_to)?;
+    let (piece_type, rest) = parse_piece_name(rest)?;
+    let modifiers = rest
+        .chars()
+        .take_while(|c| "右左直上引寄行入".contains(*c))
+        .collect::<Vec<_>>();
+    let rest = &rest[modifiers.iter().map(|c| c.len_utf8()).sum::<usize>()..];
+
+    let drop = Move::Drop { to, piece_type };
+    let (promote, rest) = match rest {
+        "打" => return pos.is_legal(&drop).then_some(drop),
+        "不成" | "生" => (false, ""),
+        "成" => (true, ""),
+        _ => (false, rest),
+    };
+    if !rest.is_empty() {
+        return None;
+    }
+
+    let color = pos.side_to_move();
+    let mut origins = candidates(pos, piece_type, to, promote);
+    if origins.is_empty() {
+        return pos.is_legal(&drop).then_some(drop);
+    }
+    for modifier in modifiers.iter().filter(|c| !matches!(c, '右' | '左')) {
+        origins.retain(|from| matches_direction(*modifier, *from, to, color));
+    }
+    for modifier in modifiers.iter().filter(|c| matches!(c, '右' | '左')) {
+        origins = select_side(*modifier, &origins, color);
+    }
+
+    match origins[..] {
+        [from] => Some(Move::Normal { from, to, promote }),
+        _ => None,
+    }
+}
+
+/// Returns the modifiers which distinguish the move from other moves to the same square.
+fn modifiers(pos: &Position, m: &Move) -> &'static str {
+    let (from, to, promote) = match *m {
+        Move::Normal { from, to, promote } => (from, to, promote),
+        Move::Drop { to, piece_type } => {
+            let board_moves = candidates(pos, piece_type, to, false);
+            return if board_moves.is_empty() { "" } else { "打" };
+        }
+    };
+    let piece_type = match pos.piece_at(from) {
+        Some(p) => p.piece_type,
+        None => return "",
+    };
+    let color = pos.side_to_move();
+    let origins = candidates(pos, piece_type, to, promote);
+    if origins.len() <= 1 {
+        return "";
+    }
+
+    let directions = [("上", '上'), ("引", '引'), ("寄", '寄'), ("直", '直')];
+    for (s, d) in directions {
+        if matches_direction(d, from, to, color) {
+            let n = origins
+                .iter()
+                .filter(|o| matches_direction(d, **o, to, color))
+                .count();
+            if n == 1 {
+                return s;
+            }
+        }
+    }
+    for (s, side) in [("右", '右'), ("左", '左')] {
+        if select_side(side, &origins, color) == [from] {
+            return s;
+        }
+    }
+    for (s, d, side) in [
+        ("右上", '上', '右'),
+        ("左上", '上', '左'),
+        ("右引", '引', '右'),
+        ("左引", '引', '左'),
+        ("右寄", '寄', '右'),
+        ("左寄", '寄', '左'),
+    ] {
+        if matches_direction(d, from, to, color) {
+            let same_direction = origins
+                .iter()
+                .filter(|o| matches_direction(d, **o, to, color))
+                .copied()
+                .collect::<Vec<_>>();
+            if select_side(side, &same_direction, color) == [from] {
+                return s;
+            }
+        }
+    }
+    ""
+}
+
+/// Writes moves from the position, and returns the position after them.
+fn write_line(out: &mut String, pos: &Position, moves: &[MoveRecord]) -> Position {
+    let mut pos = pos.clone();
+    let mut prev_to = None;
+    let mut in_line = 0;
+
+    for m in moves {
+        let marker = match pos.side_to_move() {
+            Color::Black => '▲',
+            Color::White => '△',
+        };
+        let mut text = move_prefix(&pos, &m.mv, prev_to);
+        let suffix = modifiers(&pos, &m.mv);
+        if suffix == "打" {
+            text.push_str(suffix);
+        } else {
+            text.push_str(suffix);
+            text.push_str(promotion_suffix(&pos, &m.mv));
+        }
+
+        if in_line > 0 {
+            out.push(' ');
+        }
+        out.push(marker);
+        out.push_str(&text);
+        in_line += 1;
+        if in_line == MOVES_PER_LINE || m.comment.is_some() {
+            out.push('\n');
+            in_line = 0;
+        }
+        write_comment(out, m.comment.as_deref());
+
+        pos.make_move(&m.mv)
+            .expect("moves in a record must be legal");
+        prev_to = Some(m.mv.to());
+    }
+    if in_line > 0 {
+        out.push('\n');
+    }
+    pos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameEndReason, GameResult};
+
+    #[test]
+    fn parse() {
+        let ki2 = "\
+手合割：平手
+先手：先手太郎
+後手：後手花子
+
+▲７六歩    △３四歩    ▲２二角成  △同　銀    ▲５八金左  △４五角
+*角打ち
+▲３六歩
+まで7手で先手の勝ち
+";
+        let record = GameRecord::from_ki2(ki2).unwrap();
+        assert_eq!("後手花子", record.white_name);
+        assert_eq!(
+            vec!["7g7f", "3c3d", "8h2b+", "3a2b", "6i5h", "B*4e", "3g3f"],
+            record
+                .moves
+                .iter()
+                .map(|m| m.mv.to_string())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(Some("角打ち"), record.moves[5].comment.as_deref());
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: GameEndReason::Resign,
+            }),
+            record.result
+        );
+
+        let ng_cases = ["▲５八金\n", "▲７五歩\n", "▲５五角打\n"];
+        for s in ng_cases {
+            assert!(GameRecord::from_ki2(s).is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn disambiguation() {
+        let cases = [
+            // Golds on 6i and 4i.
+            (crate::shogi::STARTPOS_SFEN, "4i5h", "５八金右"),
+            (crate::shogi::STARTPOS_SFEN, "6i7h", "７八金"),
+            // Golds on 5i, 6h and 4h move to 5h.
+            ("4k4/9/9/9/9/9/9/3G1G3/4GK3 b - 1", "5i5h", "５八金上"),
+            ("4k4/9/9/9/9/9/9/3G1G3/4GK3 b - 1", "6h5h", "５八金左"),
+            ("4k4/9/9/9/9/9/9/3G1G3/4GK3 b - 1", "4h5h", "５八金右"),
+            // Silvers on 6i, 4i and 4g move to 5h.
+            ("4k4/9/9/9/9/9/5S3/9/3S1S2K b - 1", "4g5h", "５八銀引"),
+            ("4k4/9/9/9/9/9/5S3/9/3S1S2K b - 1", "4i5h", "５八銀右上"),
+            // Silvers on 6i and 5i move to 5h.
+            ("4k4/9/9/9/9/9/9/9/3SS1K2 b - 1", "5i5h", "５八銀直"),
+            ("4k4/9/9/9/9/9/9/9/3SS1K2 b - 1", "6i5h", "５八銀左"),
+            // Seen from white, 4a is on the left of 6a.
+            ("3g1g3/9/9/9/9/9/9/9/4K3k w - 1", "4a5b", "５二金左"),
+            // Drops are marked when a piece on the board can move there.
+            ("4k4/9/9/9/9/9/9/4G4/5K3 b G 1", "G*5g", "５七金打"),
+            ("4k4/9/9/9/9/9/9/4G4/5K3 b G 1", "5h5g", "５七金"),
+        ];
+
+        for (sfen, m, expected) in cases {
+            let pos = Position::from_sfen(sfen).unwrap();
+            let mv: Move = m.parse().unwrap();
+            let text = format!("{}{}", move_prefix(&pos, &mv, None), modifiers(&pos, &mv));
+            assert_eq!(expected, text, "{sfen} {m}");
+            assert_eq!(Some(mv), parse_move(&pos, expected, None), "{sfen} {m}");
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let kif = "\
+手数----指手---------消費時間--
+   1 ７六歩(77)
+*コメント
+   2 ３四歩(33)
+   3 ２二角成(88)
+   4 同　銀(31)
+   5 ５八金(49)
+   6 ４五角打
+   7 ３六歩(37)
+   8 ６二銀(71)
+   9 投了
+まで8手で後手の勝ち
+
+変化：2手
+   2 ８四歩(83)
+";
+        let record = GameRecord::from_kif(kif).unwrap();
+        let ki2 = record.to_ki2();
+        assert!(ki2.contains("▲５八金右"), "{ki2}");
+        assert!(ki2.contains("\n変化：2手\n△８四歩\n"), "{ki2}");
+        assert_eq!(record, GameRecord::from_ki2(&ki2).unwrap());
+    }
+}
@@ -0,0 +1,331 @@
+use std::time::Duration;
+
+use super::{
+    move_prefix, parse_destination, parse_piece_name, parse_special_move, parse_summary,
+    promotion_suffix, record_error, result_to_ja, write_comment, write_preamble, write_variations,
+    RecordBuilder,
+};
+use crate::error::Error;
+use crate::game::{GameRecord, MoveRecord};
+use crate::shogi::{Move, Position, Square};
+
+impl GameRecord {
+    /// Parses a game record in KIF format.
+    ///
+    /// Headers other than player names and the start position are kept in `headers`,
+    /// comments starting with `*` are attached to the preceding move, and `変化` sections
+    /// are stored as variations of the moves they replace.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use usi::{GameRecord, STARTPOS_SFEN};
+    ///
+    /// let kif = "先手：Black\n後手：White\n\
+    ///            手数----指手---------消費時間--\n   \
+    ///            1 ７六歩(77)   ( 0:03/00:00:03)\n   \
+    ///            2 ３四歩(33)   ( 0:01/00:00:01)\n   \
+    ///            3 投了\n\
+    ///            まで2手で後手の勝ち\n";
+    /// let record = GameRecord::from_kif(kif).unwrap();
+    /// assert_eq!("White", record.white_name);
+    /// assert_eq!(
+    ///     format!("position sfen {} moves 7g7f 3c3d", STARTPOS_SFEN),
+    ///     record.position_command(2).to_string()
+    /// );
+    /// ```
+    pub fn from_kif(s: &str) -> Result<GameRecord, Error> {
+        let mut builder = RecordBuilder::new();
+
+        for (n, line) in s.lines().enumerate() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('&') {
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix('*') {
+                builder.comment(comment);
+                continue;
+            }
+            if let Some(ply) = line
+                .strip_prefix("変化：")
+                .or_else(|| line.strip_prefix("変化:"))
+            {
+                let ply = ply
+                    .trim()
+                    .trim_end_matches('手')
+                    .parse()
+                    .map_err(|_| record_error(n, line))?;
+                builder.variation(n, ply)?;
+                continue;
+            }
+            if line.starts_with("まで") {
+                if let Some(winner) = parse_summary(line) {
+                    builder.set_winner(winner);
+                }
+                continue;
+            }
+            if line.starts_with("手数") {
+                builder.start()?;
+                continue;
+            }
+
+            let is_move = line.trim_start().starts_with(|c: char| c.is_ascii_digit());
+            if !is_move && builder.preamble(n, line)? {
+                continue;
+            }
+            if !is_move {
+                return Err(record_error(n, line));
+            }
+            parse_move_line(&mut builder, n, line)?;
+        }
+
+        builder.finish()
+    }
+
+    /// Returns the record in KIF format.
+    pub fn to_kif(&self) -> String {
+        let start = Position::from_sfen(&self.start_sfen).unwrap_or_default();
+        let mut out = String::new();
+        write_preamble(&mut out, self, &start);
+        out.push_str("手数----指手---------消費時間--\n");
+
+        let pos = write_line(&mut out, &start, &self.moves, 0);
+        if let Some(ref result) = self.result {
+            let (special, summary) = result_to_ja(result, pos.side_to_move());
+            out.push_str(&format!("{:>4} {}\n", self.moves.len() + 1, special));
+            out.push_str(&format!("まで{}手で{}\n", self.moves.len(), summary));
+        }
+
+        write_variations(
+            &mut out,
+            &start,
+            &[],
+            &self.moves,
+            &mut |out, pos, moves, index| {
+                write_line(out, pos, moves, index);
+            },
+        );
+        out
+    }
+}
+
+fn parse_move_line(builder: &mut RecordBuilder, n: usize, line: &str) -> Result<(), Error> {
+    let err = || record_error(n, line);
+    let line = line.trim_start();
+    let digits = line
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(line.len());
+    let ply: usize = line[..digits].parse().map_err(|_| err())?;
+    if ply != builder.ply() + 1 {
+        return Err(err());
+    }
+    let body = line[digits..].trim_start_matches(' ');
+
+    let token = body.split([' ', '(']).next().unwrap_or("");
+    if let Some(result) = parse_special_move(token, builder.start()?.pos.side_to_move()) {
+        builder.set_result(result);
+        return Ok(());
+    }
+
+    let (to, rest) = parse_destination(body, builder.prev_to).ok_or_else(err)?;
+    let (piece_type, rest) = parse_piece_name(rest).ok_or_else(err)?;
+    let (m, rest) = if let Some(rest) = rest.strip_prefix('打') {
+        (Move::Drop { to, piece_type }, rest)
+    } else {
+        let (promote, rest) = match rest.strip_prefix("不成") {
+            Some(rest) => (false, rest),
+            None => match rest.strip_prefix('成') {
+                Some(rest) => (true, rest),
+                None => (false, rest),
+            },
+        };
+        let from = rest.get(..4).map(|s| s.as_bytes()).ok_or_else(err)?;
+        let from = match from {
+            [b'(', f @ b'1'..=b'9', r @ b'1'..=b'9', b')'] => {
+                Square::new(f - b'0', r - b'0').ok_or_else(err)?
+            }
+            _ => return Err(err()),
+        };
+        (Move::Normal { from, to, promote }, &rest[4..])
+    };
+
+    builder.push_move(m, parse_time(rest))?;
+    Ok(())
+}
+
+/// Parses the time spent for a move, such as `( 0:03/00:00:03)`.
+fn parse_time(s: &str) -> Option<Duration> {
+    let (_, s) = s.split_once('(')?;
+    let (elapsed, _) = s.split_once('/')?;
+    let (m, s) = elapsed.trim().split_once(':')?;
+    Some(Duration::from_secs(
+        m.parse::<u64>().ok()? * 60 + s.parse::<u64>().ok()?,
+    ))
+}
+
+/// Writes moves starting at the index `start` in the game, and returns the position after them.
+fn write_line(out: &mut String, pos: &Position, moves: &[MoveRecord], start: usize) -> Position {
+    let mut pos = pos.clone();
+    let mut prev_to = None;
+    let mut total = [Duration::ZERO; 2];
+
+    for (i, m) in moves.iter().enumerate() {
+        let mut text = move_prefix(&pos, &m.mv, prev_to);
+        text.push_str(promotion_suffix(&pos, &m.mv));
+        match m.mv {
+            Move::Normal { from, .. } => {
+                text.push_str(&format!("({}{})", from.file(), from.rank()))
+            }
+            Move::Drop { .. } => text.push('打'),
+        }
+
+        let mut line = format!("{:>4} {}", start + i + 1, text);
+        if let Some(elapsed) = m.elapsed {
+            let t = &mut total[(start + i) % 2];
+            *t += elapsed;
+            let (secs, total_secs) = (elapsed.as_secs(), t.as_secs());
+            // Full-width characters occupy two columns.
+            let width = text
+                .chars()
+                .map(|c| if c.is_ascii() { 1 } else { 2 })
+                .sum::<usize>();
+            line.push_str(&format!(
+                "{:width$} ({:>2}:{:02}/{:02}:{:02}:{:02})",
+                "",
+                secs / 60,
+                secs % 60,
+                total_secs / 3600,
+                total_secs / 60 % 60,
+                total_secs % 60,
+                width = 12usize.saturating_sub(width),
+            ));
+        }
+        if !m.variations.is_empty() {
+            line.push('+');
+        }
+        out.push_str(&line);
+        out.push('\n');
+        write_comment(out, m.comment.as_deref());
+
+        pos.make_move(&m.mv)
+            .expect("moves in a record must be legal");
+        prev_to = Some(m.mv.to());
+    }
+    pos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{GameEndReason, GameResult};
+    use crate::shogi::{Color, STARTPOS_SFEN};
+
+    const KIF: &str = "\
+# ---- Kifu for Windows ----
+開始日時：2024/01/02 10:00:00
+棋戦：テスト
+手合割：平手
+先手：先手太郎
+後手：後手花子
+*対局前のコメント
+手数----指手---------消費時間--
+   1 ７六歩(77)   ( 0:03/00:00:03)
+*角道を開ける
+   2 ３四歩(33)   ( 0:01/00:00:01)+
+   3 ２二角成(88) ( 0:10/00:00:13)
+   4 同　銀(31)   ( 0:02/00:00:03)
+   5 ４五角打     ( 0:05/00:00:18)
+   6 投了         ( 0:01/00:00:04)
+まで5手で先手の勝ち
+
+変化：2手
+   2 ８四歩(83)   ( 0:00/00:00:00)
+   3 ２六歩(27)   ( 0:00/00:00:00)+
+
+変化：3手
+   3 ６八銀(79)   ( 0:00/00:00:00)
+
+変化：2手
+   2 ４四歩(43)   ( 0:00/00:00:00)
+";
+
+    #[test]
+    fn parse() {
+        let record = GameRecord::from_kif(KIF).unwrap();
+
+        assert_eq!("先手太郎", record.black_name);
+        assert_eq!("後手花子", record.white_name);
+        assert_eq!(
+            vec![
+                ("開始日時".to_string(), "2024/01/02 10:00:00".to_string()),
+                ("棋戦".to_string(), "テスト".to_string()),
+            ],
+            record.headers
+        );
+        assert_eq!(Some("対局前のコメント"), record.comment.as_deref());
+        assert_eq!(
+            format!("position sfen {STARTPOS_SFEN} moves 7g7f 3c3d 8h2b+ 3a2b B*4e"),
+            record.position_command(5).to_string()
+        );
+        assert_eq!(Some("角道を開ける"), record.moves[0].comment.as_deref());
+        assert_eq!(Some(Duration::from_secs(10)), record.moves[2].elapsed);
+        assert_eq!(
+            Some(GameResult {
+                winner: Some(Color::Black),
+                reason: GameEndReason::Resign,
+            }),
+            record.result
+        );
+
+        let variations = &record.moves[1].variations;
+        assert_eq!(2, variations.len());
+        assert_eq!("8c8d", variations[0][0].mv.to_string());
+        assert_eq!("7i6h", variations[0][1].variations[0][0].mv.to_string());
+        assert_eq!("4c4d", variations[1][0].mv.to_string());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let record = GameRecord::from_kif(KIF).unwrap();
+        let kif = record.to_kif();
+        assert!(kif.contains("   3 ２二角成(88)"), "{kif}");
+        assert!(kif.contains("   4 同　銀(31)"), "{kif}");
+        assert!(kif.contains("   5 ４五角打"), "{kif}");
+        assert_eq!(record, GameRecord::from_kif(&kif).unwrap());
+
+        // Positions other than handicaps are written as board diagrams.
+        let record = GameRecord {
+            start_sfen:
+                "lr5nl/2g1kg3/p1nps1ppp/2pp5/1p7/2P1P4/PPSP1PPPP/2GB1S3/LN1K1G1NL w RBs2p 1"
+                    .to_string(),
+            moves: vec![MoveRecord::new("S*3b".parse().unwrap())],
+            ..Default::default()
+        };
+        let kif = record.to_kif();
+        assert!(kif.contains("後手の持駒：銀　歩二\n"), "{kif}");
+        assert!(kif.contains("後手番\n"), "{kif}");
+        assert_eq!(record, GameRecord::from_kif(&kif).unwrap());
+
+        let record = GameRecord {
+            start_sfen: "lnsgkgsnl/1r7/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1".to_string(),
+            ..Default::default()
+        };
+        let kif = record.to_kif();
+        assert!(kif.contains("手合割：角落ち\n"), "{kif}");
+        assert_eq!(record, GameRecord::from_kif(&kif).unwrap());
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            "手数----指手---------消費時間--\n   1 ７五歩(77)\n",
+            "手数----指手---------消費時間--\n   2 ７六歩(77)\n",
+            "手数----指手---------消費時間--\n   1 同　歩(77)\n",
+            "手合割：謎落ち\n",
+            "手数----指手---------消費時間--\n   1 ７六歩(77)\n\n変化：3手\n   3 ２六歩(27)\n",
+        ];
+        for s in cases {
+            assert!(GameRecord::from_kif(s).is_err(), "{s}");
+        }
+    }
+}
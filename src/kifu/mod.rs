@@ -0,0 +1,777 @@
+pub(crate) mod csa;
+#[cfg(feature = "serde")]
+mod jkf;
+mod ki2;
+mod kif;
+
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::game::{Adjudication, GameEndReason, GameRecord, GameResult, MoveRecord};
+use crate::shogi::{Color, Move, Piece, PieceType, Position, Square, STARTPOS_SFEN};
+
+/// Represents character encodings of game record files.
+///
+/// KIF and KI2 files are traditionally encoded in Shift_JIS, while `.kifu` and `.ki2u` files
+/// are encoded in UTF-8.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TextEncoding {
+    Utf8,
+    ShiftJis,
+}
+
+impl TextEncoding {
+    /// Guesses the encoding of the given bytes, which is UTF-8 if they are valid as UTF-8.
+    pub fn detect(bytes: &[u8]) -> TextEncoding {
+        if std::str::from_utf8(bytes).is_ok() {
+            TextEncoding::Utf8
+        } else {
+            TextEncoding::ShiftJis
+        }
+    }
+
+    /// Decodes the bytes, removing the byte order mark if exists.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let encoding = match self {
+            TextEncoding::Utf8 => encoding_rs::UTF_8,
+            TextEncoding::ShiftJis => encoding_rs::SHIFT_JIS,
+        };
+        encoding.decode_with_bom_removal(bytes).0.into_owned()
+    }
+
+    /// Encodes the string. Characters which cannot be represented are replaced with HTML entities.
+    pub fn encode(self, s: &str) -> Vec<u8> {
+        match self {
+            TextEncoding::Utf8 => s.as_bytes().to_vec(),
+            TextEncoding::ShiftJis => encoding_rs::SHIFT_JIS.encode(s).0.into_owned(),
+        }
+    }
+}
+
+const FILES: [char; 9] = ['１', '２', '３', '４', '５', '６', '７', '８', '９'];
+const RANKS: [char; 9] = ['一', '二', '三', '四', '五', '六', '七', '八', '九'];
+
+/// Start positions of handicap games, where white (uwate) moves first.
+const HANDICAPS: [(&str, &str); 10] = [
+    (
+        "香落ち",
+        "lnsgkgsn1/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "右香落ち",
+        "1nsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "角落ち",
+        "lnsgkgsnl/1r7/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "飛車落ち",
+        "lnsgkgsnl/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "飛香落ち",
+        "lnsgkgsn1/7b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "二枚落ち",
+        "lnsgkgsnl/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "四枚落ち",
+        "1nsgkgsn1/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "六枚落ち",
+        "2sgkgs2/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "八枚落ち",
+        "3gkg3/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+    (
+        "十枚落ち",
+        "4k4/9/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+    ),
+];
+
+fn record_error(line: usize, s: &str) -> Error {
+    Error::IllegalRecord(format!("line {}: {}", line + 1, s))
+}
+
+fn square_to_ja(sq: Square) -> String {
+    format!(
+        "{}{}",
+        FILES[sq.file() as usize - 1],
+        RANKS[sq.rank() as usize - 1]
+    )
+}
+
+/// Parses a digit written in full-width, half-width or kanji.
+fn parse_digit(c: char) -> Option<u8> {
+    FILES
+        .iter()
+        .chain(RANKS.iter())
+        .position(|d| *d == c)
+        .map(|i| (i % 9) as u8 + 1)
+        .or_else(|| c.to_digit(10).map(|d| d as u8))
+        .filter(|d| (1..=9).contains(d))
+}
+
+/// Parses a number written in kanji, such as `十八`.
+fn parse_kanji_number(s: &str) -> Option<u8> {
+    if s.is_empty() {
+        return Some(1);
+    }
+    match s.split_once('十') {
+        Some((tens, ones)) => {
+            let tens = if tens.is_empty() {
+                1
+            } else {
+                parse_kanji_number(tens)?
+            };
+            let ones = if ones.is_empty() {
+                0
+            } else {
+                parse_kanji_number(ones)?
+            };
+            Some(tens * 10 + ones)
+        }
+        None => {
+            let mut chars = s.chars();
+            match (chars.next().and_then(parse_digit), chars.next()) {
+                (Some(d), None) => Some(d),
+                _ => None,
+            }
+        }
+    }
+}
+
+fn kanji_number(n: u8) -> String {
+    let digit = |d: u8| RANKS[d as usize - 1].to_string();
+    match n {
+        1..=9 => digit(n),
+        10 => "十".to_string(),
+        11..=19 => format!("十{}", digit(n - 10)),
+        _ => n.to_string(),
+    }
+}
+
+/// Returns the name of a piece used in moves.
+fn piece_name(pt: PieceType) -> &'static str {
+    match pt {
+        PieceType::King => "玉",
+        PieceType::Rook => "飛",
+        PieceType::Bishop => "角",
+        PieceType::Gold => "金",
+        PieceType::Silver => "銀",
+        PieceType::Knight => "桂",
+        PieceType::Lance => "香",
+        PieceType::Pawn => "歩",
+        PieceType::Dragon => "龍",
+        PieceType::Horse => "馬",
+        PieceType::ProSilver => "成銀",
+        PieceType::ProKnight => "成桂",
+        PieceType::ProLance => "成香",
+        PieceType::ProPawn => "と",
+    }
+}
+
+/// Returns the single-character name of a piece used in board diagrams.
+fn piece_char(pt: PieceType) -> char {
+    match pt {
+        PieceType::ProSilver => '全',
+        PieceType::ProKnight => '圭',
+        PieceType::ProLance => '杏',
+        _ => piece_name(pt).chars().next().unwrap(),
+    }
+}
+
+/// Parses a piece name at the beginning of `s`, and returns the rest.
+fn parse_piece_name(s: &str) -> Option<(PieceType, &str)> {
+    for (name, pt) in [
+        ("成銀", PieceType::ProSilver),
+        ("成桂", PieceType::ProKnight),
+        ("成香", PieceType::ProLance),
+    ] {
+        if let Some(rest) = s.strip_prefix(name) {
+            return Some((pt, rest));
+        }
+    }
+
+    let mut chars = s.chars();
+    let pt = match chars.next()? {
+        '玉' | '王' => PieceType::King,
+        '飛' => PieceType::Rook,
+        '角' => PieceType::Bishop,
+        '金' => PieceType::Gold,
+        '銀' => PieceType::Silver,
+        '桂' => PieceType::Knight,
+        '香' => PieceType::Lance,
+        '歩' => PieceType::Pawn,
+        '龍' | '竜' => PieceType::Dragon,
+        '馬' => PieceType::Horse,
+        '全' => PieceType::ProSilver,
+        '圭' => PieceType::ProKnight,
+        '杏' => PieceType::ProLance,
+        'と' => PieceType::ProPawn,
+        _ => return None,
+    };
+    Some((pt, chars.as_str()))
+}
+
+/// Parses the destination of a move, where `同` refers to the destination of the previous move.
+fn parse_destination(s: &str, prev_to: Option<Square>) -> Option<(Square, &str)> {
+    if let Some(rest) = s.strip_prefix('同') {
+        return Some((prev_to?, rest.trim_start_matches(['　', ' '])));
+    }
+
+    let mut chars = s.chars();
+    let file = chars.next().and_then(parse_digit)?;
+    let rank = chars.next().and_then(parse_digit)?;
+    Some((Square::new(file, rank)?, chars.as_str()))
+}
+
+/// Returns the destination and the piece of a move in Japanese, such as `７六歩` or `同　角`.
+fn move_prefix(pos: &Position, m: &Move, prev_to: Option<Square>) -> String {
+    let to = if prev_to == Some(m.to()) {
+        "同　".to_string()
+    } else {
+        square_to_ja(m.to())
+    };
+    let pt = match *m {
+        Move::Normal { from, .. } => pos.piece_at(from).map_or(PieceType::Pawn, |p| p.piece_type),
+        Move::Drop { piece_type, .. } => piece_type,
+    };
+    format!("{}{}", to, piece_name(pt))
+}
+
+/// Returns `成` or `不成` if the move can promote.
+fn promotion_suffix(pos: &Position, m: &Move) -> &'static str {
+    match *m {
+        Move::Normal { promote: true, .. } => "成",
+        Move::Normal { from, to, .. } => {
+            let piece = pos.piece_at(from);
+            let promotable = piece.is_some_and(|p| {
+                p.piece_type.promote().is_some()
+                    && (from.in_promotion_zone(p.color) || to.in_promotion_zone(p.color))
+            });
+            if promotable {
+                "不成"
+            } else {
+                ""
+            }
+        }
+        Move::Drop { .. } => "",
+    }
+}
+
+/// Returns the special move and the description of the result used in KIF and KI2,
+/// such as `("投了", "先手の勝ち")`, from the point of view of the side to move.
+fn result_to_ja(result: &GameResult, side_to_move: Color) -> (&'static str, &'static str) {
+    let winner = match result.winner {
+        Some(Color::Black) => "先手の勝ち",
+        Some(Color::White) => "後手の勝ち",
+        None => "",
+    };
+    let loses = result.winner == Some(side_to_move.flip());
+    match result.reason {
+        GameEndReason::Resign | GameEndReason::Adjudicated(Adjudication::ResignScore) => {
+            ("投了", winner)
+        }
+        GameEndReason::Checkmate => ("詰み", winner),
+        GameEndReason::Timeout => ("切れ負け", winner),
+        GameEndReason::DeclareWin => ("入玉勝ち", winner),
+        GameEndReason::IllegalMove | GameEndReason::PerpetualCheck if loses => ("反則負け", winner),
+        GameEndReason::IllegalMove | GameEndReason::PerpetualCheck => ("反則勝ち", winner),
+        GameEndReason::Repetition => ("千日手", "千日手"),
+        GameEndReason::MaxMoves | GameEndReason::Adjudicated(_) => ("持将棋", "持将棋"),
+        GameEndReason::Disconnected => ("中断", "中断"),
+    }
+}
+
+/// Parses a special move, and returns the result from the point of view of the side to move.
+/// Returns `Some(None)` for special moves without results, such as `中断`.
+fn parse_special_move(s: &str, side_to_move: Color) -> Option<Option<GameResult>> {
+    let lose = |reason| GameResult {
+        winner: Some(side_to_move.flip()),
+        reason,
+    };
+    let win = |reason| GameResult {
+        winner: Some(side_to_move),
+        reason,
+    };
+    let draw = |reason| GameResult {
+        winner: None,
+        reason,
+    };
+
+    Some(Some(match s {
+        "投了" => lose(GameEndReason::Resign),
+        "詰み" => lose(GameEndReason::Checkmate),
+        "切れ負け" => lose(GameEndReason::Timeout),
+        "反則負け" => lose(GameEndReason::IllegalMove),
+        "反則勝ち" => win(GameEndReason::IllegalMove),
+        "入玉勝ち" | "宣言勝ち" => win(GameEndReason::DeclareWin),
+        "千日手" => draw(GameEndReason::Repetition),
+        "持将棋" => draw(GameEndReason::Adjudicated(Adjudication::Impasse)),
+        "中断" | "不戦勝" | "不戦敗" | "封じ手" => return Some(None),
+        _ => return None,
+    }))
+}
+
+/// Parses the summary such as `まで64手で後手の勝ち`, and returns the winner if it is written.
+fn parse_summary(s: &str) -> Option<Option<Color>> {
+    let s = s.strip_prefix("まで")?;
+    let (_, outcome) = s.split_once("手で")?;
+    match outcome.trim() {
+        "先手の勝ち" | "下手の勝ち" => Some(Some(Color::Black)),
+        "後手の勝ち" | "上手の勝ち" => Some(Some(Color::White)),
+        "千日手" | "持将棋" => Some(None),
+        _ => None,
+    }
+}
+
+/// Returns the header names for players, which differ in handicap games.
+fn player_labels(start: &Position) -> (&'static str, &'static str) {
+    let sfen = start.to_sfen();
+    if HANDICAPS.iter().any(|(_, s)| *s == sfen) {
+        ("下手", "上手")
+    } else {
+        ("先手", "後手")
+    }
+}
+
+/// Writes headers, the start position and the initial comment shared by KIF and KI2.
+fn write_preamble(out: &mut String, record: &GameRecord, start: &Position) {
+    for (k, v) in &record.headers {
+        out.push_str(&format!("{k}：{v}\n"));
+    }
+
+    let sfen = start.to_sfen();
+    let (black, white) = player_labels(start);
+    if sfen == STARTPOS_SFEN {
+        out.push_str("手合割：平手\n");
+    } else if let Some((name, _)) = HANDICAPS.iter().find(|(_, s)| *s == sfen) {
+        out.push_str(&format!("手合割：{name}\n"));
+    } else {
+        write_board_diagram(out, start);
+    }
+
+    out.push_str(&format!("{}：{}\n", black, record.black_name));
+    out.push_str(&format!("{}：{}\n", white, record.white_name));
+    write_comment(out, record.comment.as_deref());
+}
+
+fn write_comment(out: &mut String, comment: Option<&str>) {
+    for line in comment.into_iter().flat_map(str::lines) {
+        out.push_str(&format!("*{line}\n"));
+    }
+}
+
+fn write_hand(out: &mut String, pos: &Position, color: Color) {
+    let label = match color {
+        Color::Black => "先手の持駒：",
+        Color::White => "後手の持駒：",
+    };
+    let pieces = PieceType::HAND_TYPES
+        .iter()
+        .filter(|pt| pos.hand(color, **pt) > 0)
+        .map(|pt| match pos.hand(color, *pt) {
+            1 => piece_name(*pt).to_string(),
+            n => format!("{}{}", piece_name(*pt), kanji_number(n)),
+        })
+        .collect::<Vec<_>>();
+    if pieces.is_empty() {
+        out.push_str(&format!("{label}なし\n"));
+    } else {
+        out.push_str(&format!("{label}{}\n", pieces.join("　")));
+    }
+}
+
+/// Writes the position as a board diagram (BOD).
+fn write_board_diagram(out: &mut String, pos: &Position) {
+    write_hand(out, pos, Color::White);
+    out.push_str("  ９ ８ ７ ６ ５ ４ ３ ２ １\n");
+    out.push_str("+---------------------------+\n");
+    for rank in 1..=9 {
+        out.push('|');
+        for file in (1..=9).rev() {
+            match pos.piece_at(Square::new(file, rank).unwrap()) {
+                Some(p) => {
+                    out.push(if p.color == Color::White { 'v' } else { ' ' });
+                    out.push(piece_char(p.piece_type));
+                }
+                None => out.push_str(" ・"),
+            }
+        }
+        out.push_str(&format!("|{}\n", RANKS[rank as usize - 1]));
+    }
+    out.push_str("+---------------------------+\n");
+    write_hand(out, pos, Color::Black);
+    if pos.side_to_move() == Color::White {
+        out.push_str("後手番\n");
+    }
+}
+
+/// Represents a line of moves being read, either the main line or a variation.
+struct Line {
+    /// Index of the first move in the game.
+    start: usize,
+    moves: Vec<MoveRecord>,
+    /// Index of the parent line and the index of the move in it which this line replaces.
+    parent: Option<(usize, usize)>,
+}
+
+/// `RecordBuilder` collects headers, moves and variations while reading KIF and KI2.
+struct RecordBuilder {
+    record: GameRecord,
+    handicap: Option<&'static str>,
+    unknown_handicap: Option<Error>,
+    board: Vec<String>,
+    hands: [Option<String>; 2],
+    white_to_move: bool,
+    start: Option<Position>,
+    lines: Vec<Line>,
+    pos: Position,
+    prev_to: Option<Square>,
+}
+
+impl RecordBuilder {
+    fn new() -> RecordBuilder {
+        RecordBuilder {
+            record: GameRecord::default(),
+            handicap: None,
+            unknown_handicap: None,
+            board: Vec::new(),
+            hands: [None, None],
+            white_to_move: false,
+            start: None,
+            lines: vec![Line {
+                start: 0,
+                moves: Vec::new(),
+                parent: None,
+            }],
+            pos: Position::startpos(),
+            prev_to: None,
+        }
+    }
+
+    /// Handles a line before moves: headers and board diagrams. Returns false if not handled.
+    fn preamble(&mut self, n: usize, line: &str) -> Result<bool, Error> {
+        if self.start.is_some() {
+            return Ok(false);
+        }
+
+        if line.starts_with('|') {
+            self.board.push(line.to_string());
+            return Ok(true);
+        }
+        if line.starts_with('+') || line.trim_start().starts_with('９') {
+            return Ok(true);
+        }
+        match line.trim() {
+            "後手番" | "上手番" => {
+                self.white_to_move = true;
+                return Ok(true);
+            }
+            "先手番" | "下手番" => return Ok(true),
+            _ => {}
+        }
+
+        let (key, value) = match line.split_once('：').or_else(|| line.split_once(':')) {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => return Ok(false),
+        };
+        match key {
+            "先手" | "下手" => self.record.black_name = value.to_string(),
+            "後手" | "上手" => self.record.white_name = value.to_string(),
+            "先手の持駒" | "下手の持駒" => self.hands[0] = Some(value.to_string()),
+            "後手の持駒" | "上手の持駒" => self.hands[1] = Some(value.to_string()),
+            "手合割" if value == "平手" => {}
+            "手合割" => match HANDICAPS.iter().find(|(name, _)| *name == value) {
+                Some((_, sfen)) => self.handicap = Some(sfen),
+                // Unknown handicaps are acceptable only with board diagrams.
+                None => self.unknown_handicap = Some(record_error(n, line)),
+            },
+            _ => self
+                .record
+                .headers
+                .push((key.to_string(), value.to_string())),
+        }
+        Ok(true)
+    }
+
+    /// Determines the start position from headers read so far.
+    fn start(&mut self) -> Result<&mut Self, Error> {
+        if self.start.is_some() {
+            return Ok(self);
+        }
+
+        let sfen = if !self.board.is_empty() {
+            self.diagram_sfen()?
+        } else if let Some(e) = self.unknown_handicap.take() {
+            return Err(e);
+        } else {
+            self.handicap.unwrap_or(STARTPOS_SFEN).to_string()
+        };
+        let pos = Position::from_sfen(&sfen)?;
+        self.record.start_sfen = pos.to_sfen();
+        self.pos = pos.clone();
+        self.start = Some(pos);
+        Ok(self)
+    }
+
+    fn diagram_sfen(&self) -> Result<String, Error> {
+        let err =
+            || Error::IllegalRecord(format!("illegal board diagram: {}", self.board.join("")));
+        if self.board.len() != 9 {
+            return Err(err());
+        }
+
+        let mut rows = Vec::new();
+        for row in &self.board {
+            let cells = row.chars().skip(1).take(18).collect::<Vec<_>>();
+            if cells.len() != 18 {
+                return Err(err());
+            }
+            let mut sfen = String::new();
+            let mut empty = 0;
+            for cell in cells.chunks(2) {
+                if cell[1] == '・' {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    sfen.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                let (pt, _) = parse_piece_name(&cell[1].to_string()).ok_or_else(err)?;
+                let color = if cell[0] == 'v' {
+                    Color::White
+                } else {
+                    Color::Black
+                };
+                sfen.push_str(&Piece::new(pt, color).to_string());
+            }
+            if empty > 0 {
+                sfen.push_str(&empty.to_string());
+            }
+            rows.push(sfen);
+        }
+
+        let mut hands = String::new();
+        for (i, color) in [Color::Black, Color::White].into_iter().enumerate() {
+            let hand = match self.hands[i] {
+                Some(ref h) => h,
+                None => continue,
+            };
+            for item in hand
+                .split(['　', ' '])
+                .filter(|s| !s.is_empty() && *s != "なし")
+            {
+                let (pt, count) = parse_piece_name(item).ok_or_else(err)?;
+                let count = parse_kanji_number(count).ok_or_else(err)?;
+                if count > 1 {
+                    hands.push_str(&count.to_string());
+                }
+                hands.push_str(&Piece::new(pt, color).to_string());
+            }
+        }
+        if hands.is_empty() {
+            hands.push('-');
+        }
+
+        let side = if self.white_to_move { "w" } else { "b" };
+        Ok(format!("{} {} {} 1", rows.join("/"), side, hands))
+    }
+
+    fn current(&mut self) -> &mut Line {
+        self.lines.last_mut().unwrap()
+    }
+
+    /// Returns the index of the next move in the current line.
+    fn ply(&self) -> usize {
+        let line = self.lines.last().unwrap();
+        line.start + line.moves.len()
+    }
+
+    fn push_move(&mut self, m: Move, elapsed: Option<Duration>) -> Result<(), Error> {
+        self.start()?;
+        self.pos.make_move(&m)?;
+        self.prev_to = Some(m.to());
+        self.current().moves.push(MoveRecord {
+            elapsed,
+            ..MoveRecord::new(m)
+        });
+        Ok(())
+    }
+
+    /// Sets the result if the current line is the main line.
+    fn set_result(&mut self, result: Option<GameResult>) {
+        if self.lines.len() == 1 {
+            self.record.result = result;
+        }
+    }
+
+    /// Overrides the winner written in the summary.
+    fn set_winner(&mut self, winner: Option<Color>) {
+        if self.lines.len() > 1 {
+            return;
+        }
+        match self.record.result {
+            Some(ref mut result) => result.winner = winner,
+            None => {
+                self.record.result = Some(GameResult {
+                    winner,
+                    reason: match winner {
+                        Some(_) => GameEndReason::Resign,
+                        None => GameEndReason::Repetition,
+                    },
+                })
+            }
+        }
+    }
+
+    fn comment(&mut self, text: &str) {
+        let main = self.lines.len() == 1;
+        let comment = match self.lines.last_mut().unwrap().moves.last_mut() {
+            Some(m) => &mut m.comment,
+            None if main => &mut self.record.comment,
+            None => return,
+        };
+        match comment {
+            Some(c) => {
+                c.push('\n');
+                c.push_str(text);
+            }
+            None => *comment = Some(text.to_string()),
+        }
+    }
+
+    /// Starts a variation replacing the move at `ply`, counted from 1.
+    fn variation(&mut self, n: usize, ply: usize) -> Result<(), Error> {
+        self.start()?;
+        let index = ply
+            .checked_sub(1)
+            .ok_or_else(|| record_error(n, "variation must start at a positive ply"))?;
+
+        // A variation branches from the latest line which has the move, except for lines
+        // starting at the same move, which are its siblings.
+        let parent = self
+            .lines
+            .iter()
+            .rposition(|l| {
+                (l.start < index || l.parent.is_none()) && index < l.start + l.moves.len()
+            })
+            .ok_or_else(|| record_error(n, &format!("no move to branch at {ply}")))?;
+        let branch = index - self.lines[parent].start;
+
+        let moves = self.path(parent, branch);
+        let mut pos = self.start.clone().unwrap();
+        for m in &moves {
+            pos.make_move(m)?;
+        }
+        self.pos = pos;
+        self.prev_to = moves.last().map(Move::to);
+        self.lines.push(Line {
+            start: index,
+            moves: Vec::new(),
+            parent: Some((parent, branch)),
+        });
+        Ok(())
+    }
+
+    /// Returns moves from the start position to the `n`-th move of the line.
+    fn path(&self, line: usize, n: usize) -> Vec<Move> {
+        let l = &self.lines[line];
+        let mut moves = match l.parent {
+            Some((parent, branch)) => self.path(parent, branch),
+            None => Vec::new(),
+        };
+        moves.extend(l.moves[..n].iter().map(|m| m.mv));
+        moves
+    }
+
+    fn finish(mut self) -> Result<GameRecord, Error> {
+        self.start()?;
+        while self.lines.len() > 1 {
+            let line = self.lines.pop().unwrap();
+            let (parent, branch) = line.parent.unwrap();
+            if let Some(m) = self.lines[parent].moves.get_mut(branch) {
+                m.variations.insert(0, line.moves);
+            }
+        }
+        self.record.moves = self.lines.pop().unwrap().moves;
+        Ok(self.record)
+    }
+}
+
+/// Writes variations of `moves`, starting at the index `start` in the game, in the order
+/// where each variation follows the line it branches from, and later branches come first.
+fn write_variations<F>(
+    out: &mut String,
+    start: &Position,
+    path: &[Move],
+    moves: &[MoveRecord],
+    write_line: &mut F,
+) where
+    F: FnMut(&mut String, &Position, &[MoveRecord], usize),
+{
+    for i in (0..moves.len()).rev() {
+        let mut pos = start.clone();
+        for m in path.iter().chain(moves[..i].iter().map(|m| &m.mv)) {
+            pos.make_move(m).expect("moves in a record must be legal");
+        }
+        let index = path.len() + i;
+        for variation in &moves[i].variations {
+            out.push_str(&format!("\n変化：{}手\n", index + 1));
+            write_line(out, &pos, variation, index);
+
+            let mut sub_path = path.to_vec();
+            sub_path.extend(moves[..i].iter().map(|m| m.mv));
+            write_variations(out, start, &sub_path, variation, write_line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notation() {
+        for (s, n) in [("", 1), ("二", 2), ("十", 10), ("十八", 18), ("二十", 20)] {
+            assert_eq!(Some(n), parse_kanji_number(s), "{s}");
+        }
+        for n in 1..=18 {
+            assert_eq!(Some(n), parse_kanji_number(&kanji_number(n)));
+        }
+
+        let sq = Square::new(7, 6).unwrap();
+        assert_eq!("７六", square_to_ja(sq));
+        assert_eq!(Some((sq, "歩")), parse_destination("７六歩", None));
+        assert_eq!(Some((sq, "歩")), parse_destination("76歩", None));
+        assert_eq!(Some((sq, "角成")), parse_destination("同　角成", Some(sq)));
+        assert_eq!(None, parse_destination("同　角成", None));
+
+        assert_eq!(
+            Some((PieceType::ProSilver, "(45)")),
+            parse_piece_name("成銀(45)")
+        );
+        assert_eq!(Some((PieceType::Dragon, "")), parse_piece_name("竜"));
+    }
+
+    #[test]
+    fn encoding() {
+        let s = "先手：羽生善治\n";
+        let sjis = TextEncoding::ShiftJis.encode(s);
+        assert_ne!(s.as_bytes(), &sjis[..]);
+        assert_eq!(TextEncoding::ShiftJis, TextEncoding::detect(&sjis));
+        assert_eq!(s, TextEncoding::ShiftJis.decode(&sjis));
+
+        let bom = [&[0xef, 0xbb, 0xbf][..], s.as_bytes()].concat();
+        assert_eq!(TextEncoding::Utf8, TextEncoding::detect(&bom));
+        assert_eq!(s, TextEncoding::Utf8.decode(&bom));
+    }
+}
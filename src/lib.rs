@@ -6,6 +6,8 @@
 //! # Data types representing commands defined in USI protocol.
 //!
 //! `GuiCommand` and `EngineCommand` represents input/output commands defined in the protocol.
+//! With the `serde` feature, they implement `Serialize` and `Deserialize` as JSON objects
+//! tagged by `type`, with durations in milliseconds.
 //!
 //! # Examples
 //!
@@ -66,10 +68,62 @@
 //! }).unwrap();
 //! handler.send_command(&GuiCommand::Usi).unwrap();
 //! ```
+//!
+//! On Linux, `ResourceLimits` pins an engine to CPUs and limits its memory, CPU time and
+//! priority, and `ResourceUsage` reports its peak RSS and CPU time after it exits.
+//!
+//! `PonderController` tells which commands to send for `go ponder`, `ponderhit` and `stop`
+//! as the game goes on, and which outputs belong to an obsolete ponder search.
+//!
+//! # Playing games between engines
+//!
+//! `Match` drives two `EnginePlayer`s through a game, validating moves with `Position`
+//! and returning a `GameRecord`.
+//! `Tournament` schedules round-robin or gauntlet games between multiple engines
+//! and plays them concurrently, summarizing the results in a `Crosstable`.
+//! `EnginePool` keeps several initialized engines for long-running services, lending them out
+//! and respawning the ones which crashed or stopped responding.
+//! `EloEstimate` and `Sprt` evaluate the results, so that a test can be stopped
+//! as soon as it is decided.
+//!
+//! # Game records
+//!
+//! `GameRecord` can be read from and written to KIF, KI2 and CSA files, and `TextEncoding`
+//! handles both Shift_JIS and UTF-8 files. `GameRecord::position_command` turns a loaded
+//! game into a `position` command to analyze it with an engine. With the `serde` feature,
+//! records can also be converted from and to JSON Kifu Format used by web viewers.
+//!
+//! # Playing on CSA servers
+//!
+//! `CsaClient` connects an `EnginePlayer` to a server speaking the CSA protocol, such as
+//! Floodgate, and returns a `GameRecord` of each game played there.
+//!
+//! # Analyzing positions
+//!
+//! `Analyzer` searches positions loaded by `load_positions` with a fixed depth, number of
+//! nodes or time, using several engine processes in parallel. Results are appended to a JSONL
+//! or CSV file, which also allows an interrupted analysis to be resumed.
+//! `Annotator` reviews a whole game with MultiPV, classifying moves by how much they lost
+//! against the engine's best move, and returns an annotated `GameRecord` along with
+//! the evaluation graph.
+//! `MateSolver` asks engines to solve tsume problems with `go mate`, verifying the answers
+//! with `verify_mate` and checking that the first move of each problem is unique.
+mod analysis;
+mod csa;
 mod error;
+mod game;
+mod kifu;
 mod process;
 mod protocol;
+mod shogi;
+mod stats;
 
+pub use self::analysis::*;
+pub use self::csa::*;
 pub use self::error::*;
+pub use self::game::*;
+pub use self::kifu::*;
 pub use self::process::*;
 pub use self::protocol::*;
+pub use self::shogi::*;
+pub use self::stats::*;
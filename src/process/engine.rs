@@ -2,19 +2,27 @@ use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::io::BufReader;
 use std::path::Path;
-use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
+use std::sync::Arc;
 use std::thread;
+use std::time::{Duration, Instant};
 
+#[cfg(target_os = "linux")]
+use super::limits::{self, ResourceLimits, ResourceUsage};
 use super::reader::{EngineCommandReader, EngineOutput};
+use super::session::SearchSessions;
 use super::writer::GuiCommandWriter;
 use crate::error::Error;
 use crate::protocol::*;
 
 /// Represents a metadata returned from a USI engine.
 #[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EngineInfo {
     name: String,
+    author: String,
     options: HashMap<String, String>,
+    option_params: Vec<OptionParams>,
 }
 
 impl EngineInfo {
@@ -23,10 +31,20 @@ impl EngineInfo {
         &self.name
     }
 
+    /// Returns an engine author.
+    pub fn author(&self) -> &str {
+        &self.author
+    }
+
     /// Returns available engine options.
     pub fn options(&self) -> &HashMap<String, String> {
         &self.options
     }
+
+    /// Returns definitions of available engine options in the order the engine reported them.
+    pub fn option_params(&self) -> &[OptionParams] {
+        &self.option_params
+    }
 }
 
 /// `UsiEngineHandler` provides a type-safe interface to the USI engine process.
@@ -63,16 +81,28 @@ impl EngineInfo {
 /// }).unwrap();
 /// handler.send_command(&GuiCommand::Usi).unwrap();
 /// ```
+///
+/// # Search sessions
+///
+/// Each `go` command starts a new search session, and outputs of the engine are tagged with
+/// the session they belong to by `EngineOutput::session`. Exactly one `bestmove` is delivered
+/// per session: a `bestmove` received without an active search is not passed to the hook,
+/// and is reported by `take_protocol_errors` instead.
 #[derive(Debug)]
 pub struct UsiEngineHandler {
     process: Child,
     reader: Option<EngineCommandReader<BufReader<ChildStdout>>>,
     writer: GuiCommandWriter<ChildStdin>,
+    sessions: Arc<SearchSessions>,
+    exit: Option<ExitStatus>,
+    #[cfg(target_os = "linux")]
+    usage: Option<ResourceUsage>,
 }
 
 impl Drop for UsiEngineHandler {
     fn drop(&mut self) {
-        self.kill().unwrap();
+        // The engine may already have exited after `quit`, in which case writing fails.
+        let _ = self.kill();
     }
 }
 impl UsiEngineHandler {
@@ -81,8 +111,24 @@ impl UsiEngineHandler {
         engine_path: P,
         working_dir: Q,
     ) -> Result<Self, Error> {
-        let mut process = Command::new(engine_path)
-            .current_dir(working_dir)
+        Self::spawn_command(Command::new(engine_path).current_dir(working_dir))
+    }
+
+    /// Spawns a new process of the specific USI engine, restricting its resources.
+    #[cfg(target_os = "linux")]
+    pub fn spawn_with_limits<P: AsRef<OsStr>, Q: AsRef<Path>>(
+        engine_path: P,
+        working_dir: Q,
+        limits: &ResourceLimits,
+    ) -> Result<Self, Error> {
+        let mut command = Command::new(engine_path);
+        command.current_dir(working_dir);
+        limits.apply(&mut command);
+        Self::spawn_command(&mut command)
+    }
+
+    fn spawn_command(command: &mut Command) -> Result<Self, Error> {
+        let mut process = command
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
             .spawn()?;
@@ -94,6 +140,10 @@ impl UsiEngineHandler {
             process,
             reader: Some(EngineCommandReader::new(BufReader::new(stdout))),
             writer: GuiCommandWriter::new(stdin),
+            sessions: Arc::default(),
+            exit: None,
+            #[cfg(target_os = "linux")]
+            usage: None,
         })
     }
 
@@ -116,10 +166,15 @@ impl UsiEngineHandler {
                 Some(EngineCommand::Id(IdParams::Name(name))) => {
                     info.name = name.to_string();
                 }
-                Some(EngineCommand::Option(OptionParams {
-                    ref name,
-                    ref value,
-                })) => {
+                Some(EngineCommand::Id(IdParams::Author(author))) => {
+                    info.author = author.to_string();
+                }
+                Some(EngineCommand::Option(
+                    params @ OptionParams {
+                        ref name,
+                        ref value,
+                    },
+                )) => {
                     info.options.insert(
                         name.to_string(),
                         match value {
@@ -138,6 +193,7 @@ impl UsiEngineHandler {
                             _ => String::new(),
                         },
                     );
+                    info.option_params.push(params.clone());
                 }
                 Some(EngineCommand::UsiOk) => break,
                 _ => {}
@@ -169,18 +225,119 @@ impl UsiEngineHandler {
     }
     /// Sends a command to the engine.
     pub fn send_command(&mut self, command: &GuiCommand) -> Result<(), Error> {
+        if let GuiCommand::Go(_) = command {
+            self.sessions.start();
+        }
         self.writer.send(command)
     }
 
+    /// Sends a line to the engine as is, e.g. to pass through commands of GUI extensions.
+    /// A line starting with `go` starts a search session as `send_command` does.
+    pub fn send_raw(&mut self, line: &str) -> Result<(), Error> {
+        if line.split_whitespace().next() == Some("go") {
+            self.sessions.start();
+        }
+        self.writer.send_raw(line)
+    }
+
+    /// Stops the latest search, and waits until its `bestmove` is received.
+    /// `stop` is not sent if the search has already finished, in which case its `bestmove`
+    /// is returned right away.
+    /// Returns `Error::IllegalOperation` when called before `listen` method or without
+    /// any search.
+    pub fn stop_and_wait(&mut self, timeout: Duration) -> Result<BestMoveParams, Error> {
+        if self.reader.is_some() {
+            return Err(Error::IllegalOperation);
+        }
+        let id = self.sessions.latest().ok_or(Error::IllegalOperation)?;
+
+        let deadline = Instant::now() + timeout;
+        if self.sessions.is_pending(id) {
+            self.writer.send(&GuiCommand::Stop)?;
+        }
+        self.sessions.wait(id, deadline)
+    }
+
+    /// Returns protocol violations found by the listening thread since the last call,
+    /// such as `Error::UnexpectedBestMove` and `Error::DuplicateBestMove`.
+    pub fn take_protocol_errors(&mut self) -> Vec<Error> {
+        self.sessions.take_errors()
+    }
+
+    /// Returns the exit status if the engine process has exited, without blocking.
+    pub fn try_wait(&mut self) -> Result<Option<ExitStatus>, Error> {
+        if self.exit.is_none() {
+            self.exit = self.reap(false)?;
+        }
+        Ok(self.exit)
+    }
+
+    /// Sends `quit` command and waits until the engine exits.
+    /// The engine is killed if it does not exit within `timeout`.
+    pub fn quit(&mut self, timeout: Duration) -> Result<ExitStatus, Error> {
+        // The engine may already have exited, in which case writing fails.
+        let _ = self.writer.send(&GuiCommand::Quit);
+        self.wait_timeout(timeout)
+    }
+
+    /// Waits until the engine exits, e.g. after `quit` was sent with `send_command`.
+    /// The engine is killed if it does not exit within `timeout`.
+    pub fn wait_timeout(&mut self, timeout: Duration) -> Result<ExitStatus, Error> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = self.try_wait()? {
+                return Ok(status);
+            }
+            if Instant::now() >= deadline {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        self.process.kill()?;
+        self.exit = self.reap(true)?;
+        Ok(self.exit.expect("the engine must have exited"))
+    }
+
+    /// Returns resources consumed by the engine, once `try_wait` or `quit` found it exited.
+    #[cfg(target_os = "linux")]
+    pub fn resource_usage(&self) -> Option<ResourceUsage> {
+        self.usage
+    }
+
+    #[cfg(target_os = "linux")]
+    fn reap(&mut self, block: bool) -> Result<Option<ExitStatus>, Error> {
+        // `Child::try_wait` would discard the resource usage, so reap the process by ourselves.
+        Ok(
+            limits::wait(self.process.id(), block)?.map(|(status, usage)| {
+                self.usage = Some(usage);
+                status
+            }),
+        )
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn reap(&mut self, block: bool) -> Result<Option<ExitStatus>, Error> {
+        if block {
+            Ok(Some(self.process.wait()?))
+        } else {
+            Ok(self.process.try_wait()?)
+        }
+    }
+
     /// Terminates the engine.
     pub fn kill(&mut self) -> Result<(), Error> {
         self.writer.send(&GuiCommand::Quit)?;
-        self.process.kill()?;
+        // The process ID may have been reused once the process was reaped.
+        if self.exit.is_none() {
+            self.process.kill()?;
+        }
         Ok(())
     }
 
     /// Spanws a new thread to monitor outputs from the engine.
-    /// `hook` will be called for each USI command received.
+    /// `hook` will be called for each USI command received, including lines which failed to parse
+    /// as `EngineCommand::Unparsed`.
     /// `prepare` method can only be called before `listen` method.
     pub fn listen<F, E>(&mut self, mut hook: F) -> Result<(), Error>
     where
@@ -188,19 +345,19 @@ impl UsiEngineHandler {
         E: std::error::Error + Send + Sync + 'static,
     {
         let mut reader = self.reader.take().ok_or(Error::IllegalOperation)?;
+        let sessions = Arc::clone(&self.sessions);
 
         thread::spawn(move || -> Result<(), Error> {
             loop {
                 match reader.next_command() {
-                    Ok(output) => {
+                    Ok(mut output) => {
+                        if !sessions.receive(&mut output) {
+                            continue;
+                        }
                         if let Err(e) = hook(&output) {
                             return Err(Error::HandlerError(Box::new(e)));
                         }
                     }
-                    Err(Error::IllegalSyntax) => {
-                        // Ignore illegal commands.
-                        continue;
-                    }
                     Err(err) => {
                         return Err(err);
                     }
@@ -211,3 +368,125 @@ impl UsiEngineHandler {
         Ok(())
     }
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+    use crate::process::testing::{script_engine, spawn, spawn_silent_engine};
+
+    fn listen(handler: &mut UsiEngineHandler) -> mpsc::Receiver<(Option<u64>, String)> {
+        handler.get_info().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        handler
+            .listen(move |output| -> Result<(), Error> {
+                let _ = sender.send((output.session(), output.raw_str().trim().to_string()));
+                Ok(())
+            })
+            .unwrap();
+        receiver
+    }
+
+    #[test]
+    fn stop_and_wait() {
+        // The engine keeps searching until the next command arrives.
+        let mut handler = spawn(script_engine("stop", "read -r _; echo 'bestmove 7g7f'"));
+        assert!(matches!(
+            handler.stop_and_wait(Duration::from_secs(1)),
+            Err(Error::IllegalOperation)
+        ));
+        let receiver = listen(&mut handler);
+        assert!(matches!(
+            handler.stop_and_wait(Duration::from_secs(1)),
+            Err(Error::IllegalOperation)
+        ));
+
+        handler
+            .send_command(&GuiCommand::Go(ThinkParams::new().infinite()))
+            .unwrap();
+        let best = BestMoveParams::MakeMove("7g7f".to_string(), None);
+        assert_eq!(best, handler.stop_and_wait(Duration::from_secs(5)).unwrap());
+        assert_eq!(
+            (Some(0), "bestmove 7g7f".to_string()),
+            receiver.recv().unwrap()
+        );
+
+        // The search has already finished, so `stop` is not sent again.
+        assert_eq!(
+            best,
+            handler.stop_and_wait(Duration::from_millis(100)).unwrap()
+        );
+        assert!(handler.take_protocol_errors().is_empty());
+    }
+
+    #[test]
+    fn wait_timeout() {
+        let mut handler = spawn_silent_engine();
+        handler.send_command(&GuiCommand::Quit).unwrap();
+        assert!(handler
+            .wait_timeout(Duration::from_secs(5))
+            .unwrap()
+            .success());
+
+        // An engine which is never asked to quit is killed.
+        let mut handler = spawn_silent_engine();
+        assert!(!handler
+            .wait_timeout(Duration::from_millis(100))
+            .unwrap()
+            .success());
+    }
+
+    #[test]
+    fn duplicate_best_move() {
+        let mut handler = spawn(script_engine(
+            "duplicate",
+            "echo 'info depth 1'; echo 'bestmove 7g7f'; echo 'bestmove 2g2f'",
+        ));
+        let receiver = listen(&mut handler);
+
+        handler
+            .send_command(&GuiCommand::Go(ThinkParams::new()))
+            .unwrap();
+        handler.send_command(&GuiCommand::IsReady).unwrap();
+        let outputs = receiver.iter().take(3).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (Some(0), "info depth 1".to_string()),
+                (Some(0), "bestmove 7g7f".to_string()),
+                (None, "readyok".to_string()),
+            ],
+            outputs
+        );
+
+        let errors = handler.take_protocol_errors();
+        assert!(
+            matches!(errors[..], [Error::DuplicateBestMove(0)]),
+            "{errors:?}"
+        );
+        assert!(handler.take_protocol_errors().is_empty());
+    }
+
+    #[test]
+    fn unparsed() {
+        let mut handler = spawn(script_engine(
+            "unparsed",
+            "echo 'info depth'; echo 'bestmove'; echo 'bestmove 7g7f'",
+        ));
+        let receiver = listen(&mut handler);
+
+        handler
+            .send_command(&GuiCommand::Go(ThinkParams::new()))
+            .unwrap();
+        let outputs = receiver.iter().take(3).collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                (Some(0), "info depth".to_string()),
+                (Some(0), "bestmove".to_string()),
+                (Some(0), "bestmove 7g7f".to_string()),
+            ],
+            outputs
+        );
+        assert!(handler.take_protocol_errors().is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+use std::io;
+use std::mem;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Command, ExitStatus};
+use std::time::Duration;
+
+/// Represents restrictions on the resources an engine process can use.
+///
+/// Limits are applied in the child process just before the engine is executed,
+/// so that parallel games do not disturb each other.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use usi::{ResourceLimits, UsiEngineHandler};
+///
+/// let limits = ResourceLimits::new()
+///     .cpu_affinity(&[2, 3])
+///     .memory(2 << 30)
+///     .cpu_time(Duration::from_secs(3600))
+///     .nice(5);
+/// let handler =
+///     UsiEngineHandler::spawn_with_limits("/path/to/usi_engine", "/path/to/working_dir", &limits)
+///         .unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ResourceLimits {
+    cpu_affinity: Vec<usize>,
+    memory: Option<u64>,
+    cpu_time: Option<Duration>,
+    nice: Option<i32>,
+}
+
+impl ResourceLimits {
+    pub fn new() -> Self {
+        ResourceLimits::default()
+    }
+
+    /// Pins the process to the given CPUs.
+    #[must_use]
+    pub fn cpu_affinity(mut self, cpus: &[usize]) -> Self {
+        self.cpu_affinity = cpus.to_vec();
+        self
+    }
+
+    /// Limits the virtual memory of the process in bytes (`RLIMIT_AS`).
+    #[must_use]
+    pub fn memory(mut self, bytes: u64) -> Self {
+        self.memory = Some(bytes);
+        self
+    }
+
+    /// Limits the CPU time of the process (`RLIMIT_CPU`), rounded up to seconds.
+    /// The process receives `SIGXCPU` when exceeding it, and is killed a second later.
+    #[must_use]
+    pub fn cpu_time(mut self, t: Duration) -> Self {
+        self.cpu_time = Some(t);
+        self
+    }
+
+    /// Sets the nice value of the process.
+    #[must_use]
+    pub fn nice(mut self, n: i32) -> Self {
+        self.nice = Some(n);
+        self
+    }
+
+    pub(super) fn apply(&self, command: &mut Command) {
+        let limits = self.clone();
+        // SAFETY: `apply_to_current` only issues system calls, without allocating memory.
+        unsafe {
+            command.pre_exec(move || limits.apply_to_current());
+        }
+    }
+
+    fn apply_to_current(&self) -> io::Result<()> {
+        if !self.cpu_affinity.is_empty() {
+            // SAFETY: `cpu_set_t` is a plain bit array, for which zeroes are an empty set.
+            let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+            for &cpu in &self.cpu_affinity {
+                if cpu >= libc::CPU_SETSIZE as usize {
+                    return Err(io::Error::from_raw_os_error(libc::EINVAL));
+                }
+                unsafe { libc::CPU_SET(cpu, &mut set) };
+            }
+            check(unsafe { libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) })?;
+        }
+        if let Some(bytes) = self.memory {
+            set_rlimit(libc::RLIMIT_AS, bytes, bytes)?;
+        }
+        if let Some(t) = self.cpu_time {
+            let secs = t.as_secs() + u64::from(t.subsec_nanos() > 0);
+            set_rlimit(libc::RLIMIT_CPU, secs, secs + 1)?;
+        }
+        if let Some(n) = self.nice {
+            check(unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, n) })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_env = "gnu")]
+type RlimitResource = libc::__rlimit_resource_t;
+#[cfg(not(target_env = "gnu"))]
+type RlimitResource = libc::c_int;
+
+fn set_rlimit(resource: RlimitResource, soft: u64, hard: u64) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+    check(unsafe { libc::setrlimit(resource, &limit) })
+}
+
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Represents resources consumed by an engine process until it exited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ResourceUsage {
+    /// Peak resident set size in bytes.
+    pub peak_rss: u64,
+    /// CPU time spent in user mode.
+    pub user_time: Duration,
+    /// CPU time spent in the kernel.
+    pub system_time: Duration,
+}
+
+impl ResourceUsage {
+    /// Returns the total CPU time.
+    pub fn cpu_time(&self) -> Duration {
+        self.user_time + self.system_time
+    }
+}
+
+/// Reaps the process with `wait4` to collect its resource usage.
+/// Returns `None` if `block` is false and the process is still running.
+pub(super) fn wait(pid: u32, block: bool) -> io::Result<Option<(ExitStatus, ResourceUsage)>> {
+    let options = if block { 0 } else { libc::WNOHANG };
+    let mut status = 0;
+    // SAFETY: `rusage` consists of integers only.
+    let mut usage: libc::rusage = unsafe { mem::zeroed() };
+
+    loop {
+        match unsafe { libc::wait4(pid as libc::pid_t, &mut status, options, &mut usage) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            0 => return Ok(None),
+            _ => break,
+        }
+    }
+
+    Ok(Some((
+        ExitStatus::from_raw(status),
+        ResourceUsage {
+            // Linux reports the size in kilobytes.
+            peak_rss: usage.ru_maxrss as u64 * 1024,
+            user_time: to_duration(usage.ru_utime),
+            system_time: to_duration(usage.ru_stime),
+        },
+    )))
+}
+
+fn to_duration(t: libc::timeval) -> Duration {
+    Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::process::testing::script_engine;
+    use crate::process::UsiEngineHandler;
+    use crate::protocol::{EngineCommand, GuiCommand, InfoParams, ThinkParams};
+
+    #[test]
+    fn limits_and_usage() {
+        // Reports the limits seen by the engine, and burns some CPU time.
+        let path = script_engine(
+            "limits",
+            "i=0; while [ $i -lt 20000 ]; do i=$((i + 1)); done; \
+             echo \"info string $(ulimit -t) $(ulimit -v) \
+             $(awk '/Cpus_allowed_list/ {print $2}' /proc/$$/status) \
+             $(cut -d ' ' -f 19 /proc/$$/stat)\"; \
+             echo 'bestmove resign'",
+        );
+        let limits = ResourceLimits::new()
+            .cpu_affinity(&[0])
+            .memory(1 << 30)
+            .cpu_time(Duration::from_millis(59_500))
+            .nice(3);
+        let mut handler =
+            UsiEngineHandler::spawn_with_limits(&path, path.parent().unwrap(), &limits).unwrap();
+        assert!(handler.resource_usage().is_none());
+
+        handler.get_info().unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        handler
+            .listen(move |output| -> Result<(), crate::Error> {
+                if let Some(EngineCommand::Info(params)) = output.response() {
+                    let _ = sender.send(params.clone());
+                }
+                Ok(())
+            })
+            .unwrap();
+        handler
+            .send_command(&GuiCommand::Go(ThinkParams::new()))
+            .unwrap();
+        assert_eq!(
+            vec![InfoParams::Text("60 1048576 0 3".to_string())],
+            receiver.recv_timeout(Duration::from_secs(10)).unwrap()
+        );
+
+        assert!(handler.quit(Duration::from_secs(5)).unwrap().success());
+        let usage = handler.resource_usage().unwrap();
+        assert!(usage.peak_rss > 0);
+        assert!(usage.cpu_time() > Duration::ZERO, "{usage:?}");
+    }
+}
@@ -1,7 +1,17 @@
 mod engine;
+#[cfg(target_os = "linux")]
+mod limits;
+mod ponder;
 mod reader;
+mod session;
 mod writer;
 
+#[cfg(all(test, unix))]
+pub(crate) mod testing;
+
 pub use self::engine::{EngineInfo, UsiEngineHandler};
-pub use self::reader::{EngineCommandReader, EngineOutput};
+#[cfg(target_os = "linux")]
+pub use self::limits::{ResourceLimits, ResourceUsage};
+pub use self::ponder::{PonderController, PonderStats};
+pub use self::reader::{EngineCommandReader, EngineOutput, EngineOutputRef};
 pub use self::writer::GuiCommandWriter;
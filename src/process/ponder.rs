@@ -0,0 +1,266 @@
+use std::mem;
+
+use crate::protocol::{BestMoveParams, EngineCommand, GuiCommand, ThinkParams};
+
+/// Represents how often the engine predicted the opponent's move while pondering.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PonderStats {
+    pub hits: u32,
+    pub misses: u32,
+}
+
+impl PonderStats {
+    /// Returns the number of ponder searches resolved by the opponent's move.
+    pub fn total(&self) -> u32 {
+        self.hits + self.misses
+    }
+
+    /// Returns the ratio of ponder hits, or `None` if the engine has never pondered.
+    pub fn hit_rate(&self) -> Option<f64> {
+        match self.total() {
+            0 => None,
+            n => Some(self.hits as f64 / n as f64),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+enum State {
+    #[default]
+    Idle,
+    Searching,
+    Pondering(String),
+}
+
+/// `PonderController` keeps track of `go ponder` searches of an engine.
+///
+/// It does not communicate with the engine by itself. Instead, it returns commands to be sent,
+/// and tells which outputs from the engine belong to an obsolete search through `accept`.
+///
+/// # Examples
+///
+/// ```
+/// use usi::{EngineCommand, GuiCommand, PonderController, ThinkParams};
+///
+/// let mut ponder = PonderController::new();
+/// let startpos = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+///
+/// // The engine thinks of its move, predicting the reply.
+/// ponder.go(startpos, ThinkParams::new());
+/// let best = EngineCommand::parse("bestmove 7g7f ponder 3c3d").unwrap();
+/// assert!(ponder.accept(&best));
+///
+/// // Ponder on the predicted reply while the opponent is thinking.
+/// let best = match best {
+///     EngineCommand::BestMove(params) => params,
+///     _ => unreachable!(),
+/// };
+/// let commands = ponder.ponder(startpos, &best, ThinkParams::new()).unwrap();
+/// assert_eq!("go ponder", commands[1].to_string());
+///
+/// // The opponent played another move, so the ponder search is stopped and its result discarded.
+/// let commands = ponder.go(&format!("{startpos} moves 7g7f 8c8d"), ThinkParams::new());
+/// assert_eq!(GuiCommand::Stop, commands[0]);
+/// assert!(!ponder.accept(&EngineCommand::parse("bestmove 2g2f").unwrap()));
+/// assert!(ponder.accept(&EngineCommand::parse("bestmove 6g6f").unwrap()));
+/// assert_eq!(1, ponder.stats().misses);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PonderController {
+    state: State,
+    stale: usize,
+    stats: PonderStats,
+}
+
+impl PonderController {
+    pub fn new() -> Self {
+        PonderController::default()
+    }
+
+    /// Returns the statistics of ponder hits and misses so far.
+    pub fn stats(&self) -> PonderStats {
+        self.stats
+    }
+
+    /// Returns true while the engine is pondering.
+    pub fn is_pondering(&self) -> bool {
+        matches!(self.state, State::Pondering(_))
+    }
+
+    /// Returns commands to start pondering on the reply predicted in `best_move`, where
+    /// `position` is the position the engine searched, in the form of `GuiCommand::Position`.
+    /// Returns `None` if the engine did not predict a reply.
+    pub fn ponder(
+        &mut self,
+        position: &str,
+        best_move: &BestMoveParams,
+        params: ThinkParams,
+    ) -> Option<Vec<GuiCommand>> {
+        let (best, reply) = match best_move {
+            BestMoveParams::MakeMove(best, Some(reply)) => (best, reply),
+            _ => return None,
+        };
+
+        let position = append_move(&append_move(position, best), reply);
+        self.state = State::Pondering(position.clone());
+        Some(vec![
+            GuiCommand::Position(position),
+            GuiCommand::Go(params.ponder()),
+        ])
+    }
+
+    /// Returns commands to search `position` for the engine's move.
+    ///
+    /// If the engine is pondering on the same position, `ponderhit` is sent to keep the search.
+    /// Otherwise the ponder search is stopped, and a new search starts with `params`.
+    pub fn go(&mut self, position: &str, params: ThinkParams) -> Vec<GuiCommand> {
+        let mut commands = Vec::new();
+        match mem::replace(&mut self.state, State::Searching) {
+            State::Pondering(ref p) if p == position => {
+                self.stats.hits += 1;
+                return vec![GuiCommand::Ponderhit];
+            }
+            State::Pondering(_) => {
+                self.stats.misses += 1;
+                self.stale += 1;
+                commands.push(GuiCommand::Stop);
+            }
+            State::Idle | State::Searching => {}
+        }
+
+        commands.push(GuiCommand::Position(position.to_string()));
+        commands.push(GuiCommand::Go(params));
+        commands
+    }
+
+    /// Returns commands to abandon the ponder search, e.g. when the game is over.
+    /// The result of the search is discarded by `accept`.
+    pub fn stop(&mut self) -> Vec<GuiCommand> {
+        match mem::replace(&mut self.state, State::Idle) {
+            State::Pondering(_) => {
+                self.stale += 1;
+                vec![GuiCommand::Stop]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns false if `command` is an output of a stopped ponder search, which should be
+    /// ignored. Outputs must be passed in the order the engine sent them.
+    pub fn accept(&mut self, command: &EngineCommand) -> bool {
+        let is_best_move = matches!(command, EngineCommand::BestMove(_));
+        if self.stale > 0 {
+            if is_best_move {
+                self.stale -= 1;
+            }
+            return false;
+        }
+
+        if is_best_move && self.state == State::Searching {
+            self.state = State::Idle;
+        }
+        true
+    }
+}
+
+fn append_move(position: &str, mv: &str) -> String {
+    if position.contains(" moves ") {
+        format!("{position} {mv}")
+    } else {
+        format!("{position} moves {mv}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SFEN: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+
+    fn best_move(s: &str) -> BestMoveParams {
+        match EngineCommand::parse(s).unwrap() {
+            EngineCommand::BestMove(params) => params,
+            cmd => unreachable!("unexpected {cmd:?}"),
+        }
+    }
+
+    fn to_strings(commands: &[GuiCommand]) -> Vec<String> {
+        commands.iter().map(GuiCommand::to_string).collect()
+    }
+
+    #[test]
+    fn hit_and_miss() {
+        let mut ponder = PonderController::new();
+        let params = ThinkParams::new().byoyomi(std::time::Duration::from_secs(1));
+
+        assert_eq!(
+            vec![
+                format!("position sfen {SFEN}"),
+                "go byoyomi 1000".to_string()
+            ],
+            to_strings(&ponder.go(SFEN, params.clone()))
+        );
+        let best = best_move("bestmove 7g7f ponder 3c3d");
+        assert!(ponder.accept(&EngineCommand::BestMove(best.clone())));
+
+        // Hit.
+        assert_eq!(
+            vec![
+                format!("position sfen {SFEN} moves 7g7f 3c3d"),
+                "go ponder byoyomi 1000".to_string(),
+            ],
+            to_strings(&ponder.ponder(SFEN, &best, params.clone()).unwrap())
+        );
+        assert!(ponder.is_pondering());
+        let position = format!("{SFEN} moves 7g7f 3c3d");
+        assert_eq!(
+            vec![GuiCommand::Ponderhit],
+            ponder.go(&position, params.clone())
+        );
+        assert!(ponder.accept(&EngineCommand::parse("info depth 10").unwrap()));
+        let best = best_move("bestmove 2g2f ponder 8c8d");
+        assert!(ponder.accept(&EngineCommand::BestMove(best.clone())));
+
+        // Miss.
+        ponder.ponder(&position, &best, params.clone()).unwrap();
+        let position = format!("{SFEN} moves 7g7f 3c3d 2g2f 4a3b");
+        assert_eq!(
+            vec![
+                "stop".to_string(),
+                format!("position sfen {position}"),
+                "go byoyomi 1000".to_string(),
+            ],
+            to_strings(&ponder.go(&position, params.clone()))
+        );
+        assert!(!ponder.accept(&EngineCommand::parse("info depth 5").unwrap()));
+        assert!(!ponder.accept(&EngineCommand::parse("bestmove 9g9f").unwrap()));
+        assert!(ponder.accept(&EngineCommand::parse("info depth 1").unwrap()));
+        let best = best_move("bestmove 2f2e");
+        assert!(ponder.accept(&EngineCommand::BestMove(best.clone())));
+
+        // No prediction.
+        assert_eq!(None, ponder.ponder(&position, &best, params));
+        assert!(!ponder.is_pondering());
+
+        let stats = ponder.stats();
+        assert_eq!(PonderStats { hits: 1, misses: 1 }, stats);
+        assert_eq!(Some(0.5), stats.hit_rate());
+    }
+
+    #[test]
+    fn stop() {
+        let mut ponder = PonderController::new();
+        assert!(ponder.stop().is_empty());
+
+        ponder.go(SFEN, ThinkParams::new());
+        let best = best_move("bestmove 7g7f ponder 3c3d");
+        assert!(ponder.accept(&EngineCommand::BestMove(best.clone())));
+        ponder.ponder(SFEN, &best, ThinkParams::new()).unwrap();
+
+        assert_eq!(vec![GuiCommand::Stop], ponder.stop());
+        assert!(!ponder.accept(&EngineCommand::parse("bestmove 2g2f").unwrap()));
+        assert!(ponder.accept(&EngineCommand::parse("info string new game").unwrap()));
+        assert_eq!(PonderStats::default(), ponder.stats());
+        assert_eq!(None, ponder.stats().hit_rate());
+    }
+}
@@ -1,15 +1,18 @@
 use std::io::BufRead;
+use std::mem;
 use std::time::Instant;
 
-use crate::error::Error;
-use crate::EngineCommand;
+use crate::error::{Error, ParseError};
+use crate::{EngineCommand, EngineCommandRef, ParseMode};
 
 /// A struct to represent each output produced from a USI engine process.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct EngineOutput {
     response: Option<EngineCommand>,
     raw_str: String,
     timestamp: Instant,
+    session: Option<u64>,
+    warnings: Vec<ParseError>,
 }
 
 impl EngineOutput {
@@ -24,6 +27,59 @@ impl EngineOutput {
     pub fn timestamp(&self) -> &Instant {
         &self.timestamp
     }
+
+    /// Returns the ID of the search session this output belongs to, counting `go` commands
+    /// sent by `UsiEngineHandler` from zero. `None` if no search was running.
+    pub fn session(&self) -> Option<u64> {
+        self.session
+    }
+
+    /// Returns deviations from the spec which were skipped in lenient mode.
+    pub fn warnings(&self) -> &[ParseError] {
+        &self.warnings
+    }
+
+    pub(super) fn set_session(&mut self, session: Option<u64>) {
+        self.session = session;
+    }
+}
+
+/// Borrowed version of `EngineOutput`, returned by `EngineCommandReader::next_command_ref`.
+#[derive(Clone, Debug)]
+pub struct EngineOutputRef<'a> {
+    response: EngineCommandRef<'a>,
+    raw_str: &'a str,
+    timestamp: Instant,
+    warnings: Vec<ParseError>,
+}
+
+impl<'a> EngineOutputRef<'a> {
+    pub fn response(&self) -> &EngineCommandRef<'a> {
+        &self.response
+    }
+
+    pub fn raw_str(&self) -> &'a str {
+        self.raw_str
+    }
+
+    pub fn timestamp(&self) -> &Instant {
+        &self.timestamp
+    }
+
+    /// Returns deviations from the spec which were skipped in lenient mode.
+    pub fn warnings(&self) -> &[ParseError] {
+        &self.warnings
+    }
+
+    pub fn to_owned(&self) -> EngineOutput {
+        EngineOutput {
+            response: Some(self.response.to_owned()),
+            raw_str: self.raw_str.to_string(),
+            timestamp: self.timestamp,
+            session: None,
+            warnings: self.warnings.clone(),
+        }
+    }
 }
 
 /// `EngineCommandReader<R>` produces a structured output from a reader.
@@ -42,45 +98,114 @@ impl EngineOutput {
 #[derive(Debug)]
 pub struct EngineCommandReader<R: BufRead> {
     receive: R,
+    mode: ParseMode,
+    buf: String,
 }
 
 impl<R: BufRead> EngineCommandReader<R> {
+    /// Creates a reader parsing outputs in lenient mode, as engines in the wild often
+    /// deviate from the spec slightly.
     pub fn new(receive: R) -> Self {
-        EngineCommandReader { receive }
+        EngineCommandReader {
+            receive,
+            mode: ParseMode::Lenient,
+            buf: String::new(),
+        }
     }
 
-    pub fn next_command(&mut self) -> Result<EngineOutput, Error> {
-        let mut buf = String::new();
-
-        loop {
-            let bytes_read = self.receive.read_line(&mut buf)?;
-            if bytes_read == 0 {
-                return Ok(EngineOutput {
-                    response: None,
-                    raw_str: buf,
-                    timestamp: Instant::now(),
-                });
-            }
+    /// Sets how strictly outputs are checked against the spec.
+    #[must_use]
+    pub fn mode(mut self, mode: ParseMode) -> Self {
+        self.mode = mode;
+        self
+    }
 
-            if !buf.trim().is_empty() {
-                break;
-            }
-            buf.clear();
+    /// Reads the next output, whose `response` is `None` at the end of the stream.
+    /// A line which fails to parse is returned as `EngineCommand::Unparsed` instead of an error,
+    /// so that it is still available with `raw_str`.
+    pub fn next_command(&mut self) -> Result<EngineOutput, Error> {
+        if !self.read_line()? {
+            return Ok(EngineOutput {
+                response: None,
+                raw_str: mem::take(&mut self.buf),
+                timestamp: Instant::now(),
+                session: None,
+                warnings: Vec::new(),
+            });
         }
 
-        let res = EngineCommand::parse(&buf)?;
+        let (res, warnings) = match EngineCommand::parse_with(&self.buf, self.mode) {
+            Ok(parsed) => parsed,
+            Err(Error::IllegalEngineCommand(error)) => {
+                (EngineCommand::Unparsed { error }, Vec::new())
+            }
+            Err(e) => return Err(e),
+        };
         Ok(EngineOutput {
             response: Some(res),
-            raw_str: buf,
+            raw_str: mem::take(&mut self.buf),
             timestamp: Instant::now(),
+            session: None,
+            warnings,
         })
     }
+
+    /// Reads the next output into a buffer reused across calls, and parses it without copying.
+    /// Returns `None` at the end of the stream.
+    /// A line which fails to parse is returned as `EngineCommandRef::Unparsed` as in `next_command`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use usi::{BestMoveParamsRef, EngineCommandReader, EngineCommandRef};
+    ///
+    /// let buf = "bestmove 7g7f\n";
+    /// let mut reader = EngineCommandReader::new(buf.as_bytes());
+    /// let output = reader.next_command_ref().unwrap().unwrap();
+    /// assert_eq!(
+    ///     EngineCommandRef::BestMove(BestMoveParamsRef::MakeMove("7g7f", None)),
+    ///     *output.response()
+    /// );
+    /// assert!(reader.next_command_ref().unwrap().is_none());
+    /// ```
+    pub fn next_command_ref(&mut self) -> Result<Option<EngineOutputRef<'_>>, Error> {
+        if !self.read_line()? {
+            return Ok(None);
+        }
+
+        let (response, warnings) = match EngineCommandRef::parse_with(&self.buf, self.mode) {
+            Ok(parsed) => parsed,
+            Err(Error::IllegalEngineCommand(error)) => {
+                (EngineCommandRef::Unparsed { error }, Vec::new())
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(Some(EngineOutputRef {
+            response,
+            raw_str: &self.buf,
+            timestamp: Instant::now(),
+            warnings,
+        }))
+    }
+
+    /// Reads the next non-empty line into `buf`, returning false at the end of the stream.
+    fn read_line(&mut self) -> Result<bool, Error> {
+        loop {
+            self.buf.clear();
+            if self.receive.read_line(&mut self.buf)? == 0 {
+                return Ok(false);
+            }
+            if !self.buf.trim().is_empty() {
+                return Ok(true);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::BestMoveParams;
+    use crate::{BestMoveParams, InfoParams, InfoParamsRef};
 
     #[test]
     fn it_works() {
@@ -110,4 +235,71 @@ mod tests {
         }
         assert_eq!("  bestmove 5e5f\n", output.raw_str());
     }
+
+    #[test]
+    fn mode() {
+        let buf = "info depth 5 foo nodes 100\n";
+
+        let mut reader = EngineCommandReader::new(buf.as_bytes());
+        let output = reader.next_command().unwrap();
+        assert_eq!(
+            Some(EngineCommand::Info(vec![
+                InfoParams::Depth(5, None),
+                InfoParams::Nodes(100)
+            ])),
+            *output.response()
+        );
+        assert_eq!(1, output.warnings().len());
+        assert_eq!(Some(3), output.warnings()[0].token_index());
+
+        let mut reader = EngineCommandReader::new(buf.as_bytes()).mode(ParseMode::Strict);
+        let output = reader.next_command().unwrap();
+        match output.response() {
+            Some(EngineCommand::Unparsed { error }) => assert_eq!(Some(3), error.token_index()),
+            r => panic!("unexpected {r:?}"),
+        }
+        assert_eq!(buf, output.raw_str());
+
+        // The borrowing reader keeps the line in the same way.
+        let mut reader = EngineCommandReader::new(buf.as_bytes()).mode(ParseMode::Strict);
+        let output = reader.next_command_ref().unwrap().unwrap();
+        match output.response() {
+            EngineCommandRef::Unparsed { error } => assert_eq!(Some(3), error.token_index()),
+            r => panic!("unexpected {r:?}"),
+        }
+        assert_eq!(buf, output.raw_str());
+        assert!(matches!(
+            output.to_owned().response(),
+            Some(EngineCommand::Unparsed { .. })
+        ));
+    }
+
+    #[test]
+    fn next_command_ref() {
+        let buf = "\ninfo depth 2 pv 7g7f 3c3d\n\n  usiok\n";
+        let mut reader = EngineCommandReader::new(buf.as_bytes());
+
+        let output = reader.next_command_ref().unwrap().unwrap();
+        assert_eq!(
+            EngineCommandRef::Info(vec![
+                InfoParamsRef::Depth(2, None),
+                InfoParamsRef::Pv(vec!["7g7f", "3c3d"])
+            ]),
+            *output.response()
+        );
+        assert_eq!("info depth 2 pv 7g7f 3c3d\n", output.raw_str());
+        assert_eq!(
+            Some(EngineCommand::Info(vec![
+                InfoParams::Depth(2, None),
+                InfoParams::Pv(vec!["7g7f".to_string(), "3c3d".to_string()])
+            ])),
+            *output.to_owned().response()
+        );
+
+        let output = reader.next_command_ref().unwrap().unwrap();
+        assert_eq!(EngineCommandRef::UsiOk, *output.response());
+        assert_eq!("  usiok\n", output.raw_str());
+
+        assert!(reader.next_command_ref().unwrap().is_none());
+    }
 }
@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Instant;
+
+use super::reader::EngineOutput;
+use crate::error::Error;
+use crate::protocol::{BestMoveParams, EngineCommand};
+
+#[derive(Debug, Default)]
+struct State {
+    next_id: u64,
+    // Sessions waiting for `bestmove`, oldest first.
+    pending: VecDeque<u64>,
+    last: Option<(u64, BestMoveParams)>,
+    errors: Vec<Error>,
+    closed: bool,
+}
+
+/// Keeps track of search sessions started by each `go` command, shared between
+/// `UsiEngineHandler` and its listening thread.
+#[derive(Debug, Default)]
+pub(super) struct SearchSessions {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+impl SearchSessions {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Starts a new session, returning its ID.
+    pub fn start(&self) -> u64 {
+        let mut state = self.lock();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.pending.push_back(id);
+        id
+    }
+
+    /// Returns the ID of the latest session.
+    pub fn latest(&self) -> Option<u64> {
+        self.lock().next_id.checked_sub(1)
+    }
+
+    /// Returns true if the session has not received `bestmove` yet.
+    pub fn is_pending(&self, id: u64) -> bool {
+        self.lock().pending.contains(&id)
+    }
+
+    /// Assigns `output` to the session it belongs to. Returns false if `output` is `bestmove`
+    /// which violates the protocol, so that it is not delivered.
+    pub fn receive(&self, output: &mut EngineOutput) -> bool {
+        let mut state = self.lock();
+        let id = state.pending.front().copied();
+        output.set_session(id);
+
+        match output.response() {
+            Some(EngineCommand::BestMove(params)) => match id {
+                Some(id) => {
+                    state.pending.pop_front();
+                    state.last = Some((id, params.clone()));
+                    self.cond.notify_all();
+                    true
+                }
+                None => {
+                    let err = match state.last {
+                        Some((last, _)) => Error::DuplicateBestMove(last),
+                        None => Error::UnexpectedBestMove,
+                    };
+                    state.errors.push(err);
+                    false
+                }
+            },
+            None if output.raw_str().is_empty() => {
+                state.closed = true;
+                self.cond.notify_all();
+                true
+            }
+            _ => true,
+        }
+    }
+
+    /// Waits until the session receives `bestmove`, and returns it.
+    pub fn wait(&self, id: u64, deadline: Instant) -> Result<BestMoveParams, Error> {
+        let mut state = self.lock();
+        loop {
+            match state.last {
+                Some((last, ref params)) if last == id => return Ok(params.clone()),
+                _ if state.closed => {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+                }
+                _ => {}
+            }
+
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return Err(Error::Timeout);
+            }
+            state = self
+                .cond
+                .wait_timeout(state, timeout)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Returns protocol violations detected so far, clearing them.
+    pub fn take_errors(&self) -> Vec<Error> {
+        std::mem::take(&mut self.lock().errors)
+    }
+}
@@ -0,0 +1,73 @@
+//! Helpers to run tests against scripted USI engines.
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::UsiEngineHandler;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns a new empty directory for the current test.
+pub fn temp_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "usi-rs-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes a shell script engine which answers the handshake, and plays `moves` in turn
+/// on every `go`, picking a move by the number of moves already played in the position.
+/// The engine resigns if `moves` is empty.
+pub fn fake_engine(name: &str, moves: &[&str]) -> PathBuf {
+    let go = if moves.is_empty() {
+        "echo 'bestmove resign'".to_string()
+    } else {
+        format!(
+            "set -- {}; i=$(( n / 2 % $# + 1 )); eval \"m=\\${{$i}}\"; \
+             echo 'info depth 1 score cp 0'; echo \"bestmove $m\"",
+            moves.join(" ")
+        )
+    };
+    script_engine(name, &go)
+}
+
+/// Writes a shell script engine which answers the handshake, and runs `go` on every `go`.
+/// `n` holds the number of moves played in the last `position`, and `pos` holds its arguments.
+pub fn script_engine(name: &str, go: &str) -> PathBuf {
+    let path = temp_dir().join("engine.sh");
+    let script = format!(
+        "#!/bin/sh\n\
+         n=0\n\
+         while read -r cmd rest; do\n\
+         \x20 case \"$cmd\" in\n\
+         \x20   usi) echo 'id name {name}'; echo 'id author tester'; echo usiok;;\n\
+         \x20   isready) echo readyok;;\n\
+         \x20   position) pos=$rest; set -- $rest; if [ $# -gt 5 ]; then n=$(( $# - 6 )); else n=0; fi;;\n\
+         \x20   go) {go};;\n\
+         \x20   quit) exit 0;;\n\
+         \x20 esac\n\
+         done\n"
+    );
+    fs::write(&path, script).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    path
+}
+
+/// Spawns a fake engine created by `fake_engine`.
+pub fn spawn_fake_engine(name: &str, moves: &[&str]) -> UsiEngineHandler {
+    spawn(fake_engine(name, moves))
+}
+
+/// Spawns an engine which never responds to `go`.
+pub fn spawn_silent_engine() -> UsiEngineHandler {
+    spawn(script_engine("silent", ":"))
+}
+
+pub fn spawn(path: PathBuf) -> UsiEngineHandler {
+    let dir = path.parent().unwrap().to_path_buf();
+    UsiEngineHandler::spawn(path, dir).unwrap()
+}
@@ -35,6 +35,15 @@ impl<W: Write> GuiCommandWriter<W> {
 
         Ok(())
     }
+
+    /// Writes a line as is, without parsing it into a `GuiCommand`.
+    pub fn send_raw(&mut self, line: &str) -> Result<(), Error> {
+        let s = format!("{}\n", line.trim_end_matches(['\r', '\n']));
+        self.writer.write_all(s.as_bytes())?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -62,4 +71,20 @@ mod tests {
             std::str::from_utf8(&buf).unwrap()
         );
     }
+
+    #[test]
+    fn send_raw() {
+        let mut buf: Vec<u8> = Vec::new();
+        let mut writer = GuiCommandWriter::new(&mut buf);
+        writer
+            .send_raw("position startpos moves 7g7f\r\n")
+            .expect("failed to write to the buffer");
+        writer
+            .send_raw("ponderhit btime 1000 wtime 2000")
+            .expect("failed to write to the buffer");
+        assert_eq!(
+            "position startpos moves 7g7f\nponderhit btime 1000 wtime 2000\n",
+            std::str::from_utf8(&buf).unwrap()
+        );
+    }
 }
@@ -1,10 +1,26 @@
 use std::time::Duration;
 
-use super::parser::EngineCommandParser;
-use crate::error::Error;
+use super::EngineCommandRef;
+use crate::error::{Error, ParseError};
+
+/// Represents how strictly engine outputs are checked against the USI protocol.
+///
+/// The default is `Strict`, which `EngineCommand::parse` uses, while `EngineCommandReader`
+/// reads engine outputs in `Lenient` mode unless told otherwise.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ParseMode {
+    /// Rejects any deviation from the spec, such as trailing tokens or unknown info parameters.
+    #[default]
+    Strict,
+    /// Recovers as much information as possible, recording each deviation as a warning.
+    /// Lines without the mandatory parts of a command are still rejected.
+    Lenient,
+}
 
 /// Represents a kind of "option" command value.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
 pub enum OptionKind {
     Check {
         default: Option<bool>,
@@ -31,6 +47,7 @@ pub enum OptionKind {
 
 /// Represents parameters of "option" command.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct OptionParams {
     pub name: String,
     pub value: OptionKind,
@@ -38,6 +55,8 @@ pub struct OptionParams {
 
 /// Represents a kind of "score" parameter value in "info" command.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ScoreKind {
     CpExact,
     CpLowerbound,
@@ -50,6 +69,11 @@ pub enum ScoreKind {
 
 /// Represents parameters of "info" command.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum InfoParams {
     CurrMove(String),
     Depth(i32, Option<i32>),
@@ -60,11 +84,16 @@ pub enum InfoParams {
     Pv(Vec<String>),
     Score(i32, ScoreKind),
     Text(String),
-    Time(Duration),
+    Time(#[cfg_attr(feature = "serde", serde(with = "super::duration_ms"))] Duration),
 }
 
 /// Represents parameters of "checkmate" command.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum CheckmateParams {
     Mate(Vec<String>),
     NoMate,
@@ -74,6 +103,11 @@ pub enum CheckmateParams {
 
 /// Represents parameters of "bestmove" command.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum BestMoveParams {
     MakeMove(String, Option<String>),
     Resign,
@@ -82,6 +116,11 @@ pub enum BestMoveParams {
 
 /// Represents parameters of "id" command.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum IdParams {
     Name(String),
     Author(String),
@@ -104,6 +143,11 @@ pub enum IdParams {
 /// }
 /// ```
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "lowercase")
+)]
 pub enum EngineCommand {
     Id(IdParams),
     BestMove(BestMoveParams),
@@ -113,13 +157,47 @@ pub enum EngineCommand {
     ReadyOk,
     UsiOk,
     Unknown,
+    /// A line which failed to parse, produced by `EngineCommandReader` to keep it visible.
+    #[cfg_attr(feature = "serde", serde(skip_deserializing))]
+    Unparsed {
+        error: ParseError,
+    },
 }
 
 impl EngineCommand {
-    /// Parses a USI command string into a new instance of `EngineCommand`.
+    /// Parses a USI command string into a new instance of `EngineCommand` in strict mode.
+    ///
+    /// Returns `Error::IllegalEngineCommand` describing which token was wrong on failure.
+    /// Since 0.7.0, deviations such as trailing tokens are rejected as well;
+    /// use `parse_with` with `ParseMode::Lenient` to accept them with warnings.
     pub fn parse(cmd: &str) -> Result<EngineCommand, Error> {
-        let parser = EngineCommandParser::new(cmd);
-        parser.parse()
+        Ok(EngineCommand::parse_with(cmd, ParseMode::Strict)?.0)
+    }
+
+    /// Parses a USI command string in the given mode, returning deviations from the spec
+    /// which were skipped in lenient mode along with the command.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use usi::{EngineCommand, InfoParams, ParseMode};
+    ///
+    /// let line = "info depth 3 seldepth x nodes 1024";
+    /// assert!(EngineCommand::parse_with(line, ParseMode::Strict).is_err());
+    ///
+    /// let (cmd, warnings) = EngineCommand::parse_with(line, ParseMode::Lenient).unwrap();
+    /// assert_eq!(
+    ///     EngineCommand::Info(vec![InfoParams::Depth(3, None), InfoParams::Nodes(1024)]),
+    ///     cmd
+    /// );
+    /// assert_eq!(Some("seldepth"), warnings[0].field());
+    /// ```
+    pub fn parse_with(
+        cmd: &str,
+        mode: ParseMode,
+    ) -> Result<(EngineCommand, Vec<ParseError>), Error> {
+        let (cmd, warnings) = EngineCommandRef::parse_with(cmd, mode)?;
+        Ok((cmd.to_owned(), warnings))
     }
 }
 
@@ -183,4 +261,233 @@ mod tests {
             assert!(EngineCommand::parse(c).is_err(), "failed at #{i}");
         }
     }
+
+    #[test]
+    fn parse_error() {
+        let cases = [
+            (
+                "",
+                None,
+                None,
+                None,
+                "expected a command, found the end of the line\n\n^",
+            ),
+            (
+                "bestmove 7g7f ponder",
+                None,
+                Some("bestmove"),
+                Some("ponder"),
+                "expected a move for `ponder` in `bestmove` command, found the end of the line\n\
+                 bestmove 7g7f ponder\n\
+                 \x20                   ^",
+            ),
+            (
+                "info depth 3 score foo 1",
+                Some(4),
+                Some("info"),
+                Some("score"),
+                "expected `cp` or `mate` for `score` in `info` command, found `foo`\n\
+                 info depth 3 score foo 1\n\
+                 \x20                  ^^^",
+            ),
+            (
+                "info\tdepth 1 seldepth x",
+                Some(4),
+                Some("info"),
+                Some("seldepth"),
+                "expected an integer for `seldepth` in `info` command, found `x`\n\
+                 info\tdepth 1 seldepth x\n\
+                 \x20   \t                 ^",
+            ),
+            (
+                "option name 定跡 typ spin\n",
+                Some(3),
+                Some("option"),
+                None,
+                "expected `type` in `option` command, found `typ`\n\
+                 option name 定跡 typ spin\n\
+                 \x20              ^^^",
+            ),
+        ];
+
+        for (line, token, command, field, msg) in cases {
+            match EngineCommand::parse(line) {
+                Err(Error::IllegalEngineCommand(e)) => {
+                    assert_eq!(token, e.token_index(), "{line}");
+                    assert_eq!(command, e.command(), "{line}");
+                    assert_eq!(field, e.field(), "{line}");
+                    assert_eq!(msg, e.to_string());
+                }
+                r => panic!("unexpected result for {line}: {r:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn parse_strict() {
+        let cases = [
+            "usiok now",
+            "bestmove 7g7f ponder 3c3d extra",
+            "bestmove 7g7f 3c3d",
+            "id name",
+            "info depth 3 foo 1",
+            "info score mate x",
+            "option name Hash type spin default 16 step 1",
+            "option name Hash type spin default foo",
+            "option name UseBook type check default yes",
+            "option name Style type combo default Normal var",
+            "option name Book type string public.bin",
+            "option name X type spin default 1 min 0 max 10 step 1",
+            "option name S type string default",
+            "option name O type combo default A var A var B extra",
+        ];
+
+        for (i, c) in cases.iter().enumerate() {
+            assert!(EngineCommand::parse(c).is_err(), "failed at #{i}");
+            assert!(
+                EngineCommand::parse_with(c, ParseMode::Lenient).is_ok(),
+                "failed at #{i}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_lenient() {
+        let cases = [
+            ("usiok now", EngineCommand::UsiOk, vec![(Some(1), None)]),
+            (
+                "info depth 3 foo nodes x nps 100 score cp pv 7g7f",
+                EngineCommand::Info(vec![
+                    InfoParams::Depth(3, None),
+                    InfoParams::Nps(100),
+                    InfoParams::Pv(vec!["7g7f".to_string()]),
+                ]),
+                vec![
+                    (Some(3), None),
+                    (Some(5), Some("nodes")),
+                    (Some(10), Some("score cp")),
+                ],
+            ),
+            (
+                "option name Hash type spin default 16 step 1 min x max 1024",
+                EngineCommand::Option(OptionParams {
+                    name: "Hash".to_string(),
+                    value: OptionKind::Spin {
+                        default: Some(16),
+                        min: None,
+                        max: Some(1024),
+                    },
+                }),
+                vec![(Some(7), None), (Some(8), None), (Some(10), Some("min"))],
+            ),
+            (
+                "option name Style type combo default Normal var Solid var Risky",
+                EngineCommand::Option(OptionParams {
+                    name: "Style".to_string(),
+                    value: OptionKind::Combo {
+                        default: Some("Normal".to_string()),
+                        vars: vec!["Solid".to_string(), "Risky".to_string()],
+                    },
+                }),
+                vec![],
+            ),
+            (
+                "bestmove 7g7f ponder",
+                EngineCommand::BestMove(BestMoveParams::MakeMove("7g7f".to_string(), None)),
+                vec![(None, Some("ponder"))],
+            ),
+        ];
+
+        for (line, cmd, warnings) in cases {
+            let (actual, actual_warnings) = EngineCommand::parse_with(line, ParseMode::Lenient)
+                .unwrap_or_else(|e| panic!("{line}: {e}"));
+            assert_eq!(cmd, actual, "{line}");
+            assert_eq!(
+                warnings,
+                actual_warnings
+                    .iter()
+                    .map(|w| (w.token_index(), w.field()))
+                    .collect::<Vec<_>>(),
+                "{line}"
+            );
+        }
+
+        // Lines lacking the mandatory parts are rejected in lenient mode as well.
+        for line in [
+            "",
+            "bestmove",
+            "id foo bar",
+            "option name Hash",
+            "option foo",
+        ] {
+            assert!(
+                EngineCommand::parse_with(line, ParseMode::Lenient).is_err(),
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_trailing_score() {
+        assert_eq!(
+            EngineCommand::Info(vec![
+                InfoParams::Depth(1, None),
+                InfoParams::Score(-5, ScoreKind::CpExact)
+            ]),
+            EngineCommand::parse("info depth 1 score cp -5").unwrap()
+        );
+        assert_eq!(
+            EngineCommand::Info(vec![InfoParams::Score(3, ScoreKind::MateExact)]),
+            EngineCommand::parse("info score mate 3").unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_checkmate() {
+        let cases = [
+            ("checkmate nomate", CheckmateParams::NoMate),
+            ("checkmate notimplemented", CheckmateParams::NotImplemented),
+            ("checkmate timeout", CheckmateParams::Timeout),
+            (
+                "checkmate G*5b",
+                CheckmateParams::Mate(vec!["G*5b".to_string()]),
+            ),
+        ];
+        for (s, params) in cases {
+            assert_eq!(
+                EngineCommand::Checkmate(params),
+                EngineCommand::parse(s).unwrap()
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let cases = [
+            (
+                r#"{"type":"info","value":[{"type":"depth","value":[3,5]},{"type":"time","value":1141},{"type":"score","value":[-1521,"cp_exact"]},{"type":"pv","value":["3a3b","L*4h"]}]}"#,
+                "info depth 3 seldepth 5 time 1141 score cp -1521 pv 3a3b L*4h",
+            ),
+            (
+                r#"{"type":"option","value":{"name":"Selectivity","value":{"type":"spin","default":2,"min":0,"max":4}}}"#,
+                "option name Selectivity type spin default 2 min 0 max 4",
+            ),
+            (
+                r#"{"type":"bestmove","value":{"type":"make_move","value":["8h2b+","3a2b"]}}"#,
+                "bestmove 8h2b+ ponder 3a2b",
+            ),
+            (
+                r#"{"type":"checkmate","value":{"type":"no_mate"}}"#,
+                "checkmate nomate",
+            ),
+            (r#"{"type":"readyok"}"#, "readyok"),
+        ];
+
+        for (json, s) in cases {
+            let cmd = EngineCommand::parse(s).unwrap();
+            assert_eq!(json, serde_json::to_string(&cmd).unwrap());
+            assert_eq!(cmd, serde_json::from_str::<EngineCommand>(json).unwrap());
+        }
+    }
 }
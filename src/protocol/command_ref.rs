@@ -0,0 +1,344 @@
+use std::time::Duration;
+
+use itertools::Itertools;
+
+use super::parser::EngineCommandParser;
+use super::{
+    BestMoveParams, CheckmateParams, EngineCommand, IdParams, InfoParams, OptionKind, OptionParams,
+    ParseMode, ScoreKind,
+};
+use crate::error::{Error, ParseError};
+
+/// Borrowed version of `OptionKind`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum OptionKindRef<'a> {
+    Check {
+        default: Option<bool>,
+    },
+    Spin {
+        default: Option<i32>,
+        min: Option<i32>,
+        max: Option<i32>,
+    },
+    Combo {
+        default: Option<&'a str>,
+        vars: Vec<&'a str>,
+    },
+    Button {
+        default: Option<&'a str>,
+    },
+    String {
+        default: Option<&'a str>,
+    },
+    Filename {
+        default: Option<&'a str>,
+    },
+}
+
+impl OptionKindRef<'_> {
+    pub fn to_owned(&self) -> OptionKind {
+        let owned = |s: &Option<&str>| s.map(str::to_string);
+        match *self {
+            OptionKindRef::Check { default } => OptionKind::Check { default },
+            OptionKindRef::Spin { default, min, max } => OptionKind::Spin { default, min, max },
+            OptionKindRef::Combo {
+                ref default,
+                ref vars,
+            } => OptionKind::Combo {
+                default: owned(default),
+                vars: vars.iter().map(|v| v.to_string()).collect(),
+            },
+            OptionKindRef::Button { ref default } => OptionKind::Button {
+                default: owned(default),
+            },
+            OptionKindRef::String { ref default } => OptionKind::String {
+                default: owned(default),
+            },
+            OptionKindRef::Filename { ref default } => OptionKind::Filename {
+                default: owned(default),
+            },
+        }
+    }
+}
+
+/// Borrowed version of `OptionParams`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OptionParamsRef<'a> {
+    pub name: &'a str,
+    pub value: OptionKindRef<'a>,
+}
+
+impl OptionParamsRef<'_> {
+    pub fn to_owned(&self) -> OptionParams {
+        OptionParams {
+            name: self.name.to_string(),
+            value: self.value.to_owned(),
+        }
+    }
+}
+
+/// Borrowed version of `InfoParams`.
+///
+/// `Text` holds the rest of the line with the original spacing.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InfoParamsRef<'a> {
+    CurrMove(&'a str),
+    Depth(i32, Option<i32>),
+    HashFull(i32),
+    MultiPv(i32),
+    Nodes(i32),
+    Nps(i32),
+    Pv(Vec<&'a str>),
+    Score(i32, ScoreKind),
+    Text(&'a str),
+    Time(Duration),
+}
+
+impl InfoParamsRef<'_> {
+    pub fn to_owned(&self) -> InfoParams {
+        match *self {
+            InfoParamsRef::CurrMove(m) => InfoParams::CurrMove(m.to_string()),
+            InfoParamsRef::Depth(depth, sel_depth) => InfoParams::Depth(depth, sel_depth),
+            InfoParamsRef::HashFull(n) => InfoParams::HashFull(n),
+            InfoParamsRef::MultiPv(n) => InfoParams::MultiPv(n),
+            InfoParamsRef::Nodes(n) => InfoParams::Nodes(n),
+            InfoParamsRef::Nps(n) => InfoParams::Nps(n),
+            InfoParamsRef::Pv(ref pv) => InfoParams::Pv(pv.iter().map(|m| m.to_string()).collect()),
+            InfoParamsRef::Score(v, ref kind) => InfoParams::Score(v, kind.clone()),
+            InfoParamsRef::Text(s) => InfoParams::Text(s.split_whitespace().join(" ")),
+            InfoParamsRef::Time(t) => InfoParams::Time(t),
+        }
+    }
+}
+
+/// Borrowed version of `CheckmateParams`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CheckmateParamsRef<'a> {
+    Mate(Vec<&'a str>),
+    NoMate,
+    NotImplemented,
+    Timeout,
+}
+
+impl CheckmateParamsRef<'_> {
+    pub fn to_owned(&self) -> CheckmateParams {
+        match *self {
+            CheckmateParamsRef::Mate(ref moves) => {
+                CheckmateParams::Mate(moves.iter().map(|m| m.to_string()).collect())
+            }
+            CheckmateParamsRef::NoMate => CheckmateParams::NoMate,
+            CheckmateParamsRef::NotImplemented => CheckmateParams::NotImplemented,
+            CheckmateParamsRef::Timeout => CheckmateParams::Timeout,
+        }
+    }
+}
+
+/// Borrowed version of `BestMoveParams`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BestMoveParamsRef<'a> {
+    MakeMove(&'a str, Option<&'a str>),
+    Resign,
+    Win,
+}
+
+impl BestMoveParamsRef<'_> {
+    pub fn to_owned(&self) -> BestMoveParams {
+        match *self {
+            BestMoveParamsRef::MakeMove(m, pm) => {
+                BestMoveParams::MakeMove(m.to_string(), pm.map(str::to_string))
+            }
+            BestMoveParamsRef::Resign => BestMoveParams::Resign,
+            BestMoveParamsRef::Win => BestMoveParams::Win,
+        }
+    }
+}
+
+/// Borrowed version of `IdParams`, holding the rest of the line with the original spacing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IdParamsRef<'a> {
+    Name(&'a str),
+    Author(&'a str),
+}
+
+impl IdParamsRef<'_> {
+    pub fn to_owned(&self) -> IdParams {
+        match *self {
+            IdParamsRef::Name(s) => IdParams::Name(s.split_whitespace().join(" ")),
+            IdParamsRef::Author(s) => IdParams::Author(s.split_whitespace().join(" ")),
+        }
+    }
+}
+
+/// Represents a USI command sent from the engine, borrowing strings from the parsed line.
+///
+/// Parsing into `EngineCommandRef` avoids allocating a `String` for every move and name,
+/// which matters when processing large amounts of engine logs.
+/// `to_owned` converts it into the same value as `EngineCommand::parse` returns,
+/// collapsing whitespace in texts and names into single spaces.
+///
+/// # Examples
+///
+/// ```
+/// use usi::{EngineCommand, EngineCommandRef, InfoParamsRef};
+///
+/// let line = "info depth 3 pv 7g7f 3c3d";
+/// let cmd = EngineCommandRef::parse(line).unwrap();
+/// match cmd {
+///     EngineCommandRef::Info(ref params) => {
+///         assert_eq!(InfoParamsRef::Pv(vec!["7g7f", "3c3d"]), params[1]);
+///     }
+///     _ => unreachable!(),
+/// }
+/// assert_eq!(EngineCommand::parse(line).unwrap(), cmd.to_owned());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum EngineCommandRef<'a> {
+    Id(IdParamsRef<'a>),
+    BestMove(BestMoveParamsRef<'a>),
+    Checkmate(CheckmateParamsRef<'a>),
+    Info(Vec<InfoParamsRef<'a>>),
+    Option(OptionParamsRef<'a>),
+    ReadyOk,
+    UsiOk,
+    Unknown,
+    /// A line which failed to parse, produced by `EngineCommandReader` to keep it visible.
+    Unparsed {
+        error: ParseError,
+    },
+}
+
+impl<'a> EngineCommandRef<'a> {
+    /// Parses a USI command string in strict mode, borrowing strings from it.
+    /// See `EngineCommand::parse` for details.
+    pub fn parse(cmd: &'a str) -> Result<EngineCommandRef<'a>, Error> {
+        Ok(EngineCommandRef::parse_with(cmd, ParseMode::Strict)?.0)
+    }
+
+    /// Parses a USI command string in the given mode, borrowing strings from it.
+    /// See `EngineCommand::parse_with` for details.
+    pub fn parse_with(
+        cmd: &'a str,
+        mode: ParseMode,
+    ) -> Result<(EngineCommandRef<'a>, Vec<ParseError>), Error> {
+        let parser = EngineCommandParser::new(cmd, mode);
+        Ok(parser.parse()?)
+    }
+
+    pub fn to_owned(&self) -> EngineCommand {
+        match *self {
+            EngineCommandRef::Id(ref params) => EngineCommand::Id(params.to_owned()),
+            EngineCommandRef::BestMove(ref params) => EngineCommand::BestMove(params.to_owned()),
+            EngineCommandRef::Checkmate(ref params) => EngineCommand::Checkmate(params.to_owned()),
+            EngineCommandRef::Info(ref params) => {
+                EngineCommand::Info(params.iter().map(InfoParamsRef::to_owned).collect())
+            }
+            EngineCommandRef::Option(ref params) => EngineCommand::Option(params.to_owned()),
+            EngineCommandRef::ReadyOk => EngineCommand::ReadyOk,
+            EngineCommandRef::UsiOk => EngineCommand::UsiOk,
+            EngineCommandRef::Unknown => EngineCommand::Unknown,
+            EngineCommandRef::Unparsed { ref error } => EngineCommand::Unparsed {
+                error: error.clone(),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let cases = [
+            (
+                "id name  Lesser\tkai",
+                EngineCommandRef::Id(IdParamsRef::Name("Lesser\tkai")),
+            ),
+            (
+                "bestmove 8h2b+ ponder 3a2b\r\n",
+                EngineCommandRef::BestMove(BestMoveParamsRef::MakeMove("8h2b+", Some("3a2b"))),
+            ),
+            (
+                "checkmate G*8f 9f9g",
+                EngineCommandRef::Checkmate(CheckmateParamsRef::Mate(vec!["G*8f", "9f9g"])),
+            ),
+            (
+                "info currmove 7g7f string 7g7f  (70%)\n",
+                EngineCommandRef::Info(vec![
+                    InfoParamsRef::CurrMove("7g7f"),
+                    InfoParamsRef::Text("7g7f  (70%)"),
+                ]),
+            ),
+            (
+                "option name Style type combo default <empty> var Solid var Risky",
+                EngineCommandRef::Option(OptionParamsRef {
+                    name: "Style",
+                    value: OptionKindRef::Combo {
+                        default: Some(""),
+                        vars: vec!["Solid", "Risky"],
+                    },
+                }),
+            ),
+        ];
+
+        for (line, cmd) in cases {
+            assert_eq!(cmd, EngineCommandRef::parse(line).unwrap(), "{line}");
+        }
+    }
+
+    #[test]
+    fn to_owned() {
+        let cases = [
+            (
+                "id name  Lesser\tkai",
+                EngineCommand::Id(IdParams::Name("Lesser kai".to_string())),
+            ),
+            (
+                "bestmove 8h2b+ ponder 3a2b",
+                EngineCommand::BestMove(BestMoveParams::MakeMove(
+                    "8h2b+".to_string(),
+                    Some("3a2b".to_string()),
+                )),
+            ),
+            (
+                "checkmate G*8f 9f9g",
+                EngineCommand::Checkmate(CheckmateParams::Mate(vec![
+                    "G*8f".to_string(),
+                    "9f9g".to_string(),
+                ])),
+            ),
+            (
+                "info depth 3 pv 7g7f 3c3d",
+                EngineCommand::Info(vec![
+                    InfoParams::Depth(3, None),
+                    InfoParams::Pv(vec!["7g7f".to_string(), "3c3d".to_string()]),
+                ]),
+            ),
+            (
+                "info score mate - string 7g7f  (70%)",
+                EngineCommand::Info(vec![
+                    InfoParams::Score(-1, ScoreKind::MateSignOnly),
+                    InfoParams::Text("7g7f (70%)".to_string()),
+                ]),
+            ),
+            (
+                "option name LearningFile type filename default <empty>",
+                EngineCommand::Option(OptionParams {
+                    name: "LearningFile".to_string(),
+                    value: OptionKind::Filename {
+                        default: Some(String::new()),
+                    },
+                }),
+            ),
+            ("readyok", EngineCommand::ReadyOk),
+        ];
+
+        for (line, cmd) in cases {
+            assert_eq!(
+                cmd,
+                EngineCommandRef::parse(line).unwrap().to_owned(),
+                "{line}"
+            );
+        }
+    }
+}
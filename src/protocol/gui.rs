@@ -1,8 +1,13 @@
 use std::fmt;
 use std::time::Duration;
 
+use super::parser::GuiCommandParser;
+use crate::error::Error;
+
 /// Represents parameters of "gameover" command.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum GameOverKind {
     Win,
     Lose,
@@ -20,24 +25,75 @@ impl fmt::Display for GameOverKind {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum MateParam {
-    Timeout(Duration),
+    Timeout(#[cfg_attr(feature = "serde", serde(with = "super::duration_ms"))] Duration),
     Infinite,
 }
 
 /// Represents parameters of "go" command.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct ThinkParams {
     ponder: bool,
+    #[cfg_attr(feature = "serde", serde(with = "super::duration_ms::option"))]
     btime: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(with = "super::duration_ms::option"))]
     wtime: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(with = "super::duration_ms::option"))]
     byoyomi: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(with = "super::duration_ms::option"))]
     binc: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(with = "super::duration_ms::option"))]
     winc: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(with = "super::duration_ms::option"))]
+    movetime: Option<Duration>,
+    #[cfg_attr(feature = "serde", serde(with = "super::duration_ms::option"))]
+    rtime: Option<Duration>,
+    depth: Option<u32>,
+    nodes: Option<u64>,
     infinite: bool,
     mate: Option<MateParam>,
+    searchmoves: Vec<String>,
 }
 
+/// Parameters of "go" command which cannot be given together, as pairs of a parameter
+/// and the ones conflicting with it.
+const CONFLICTS: &[(&str, &[&str])] = &[
+    (
+        "mate",
+        &[
+            "ponder",
+            "btime",
+            "wtime",
+            "byoyomi",
+            "binc",
+            "winc",
+            "movetime",
+            "rtime",
+            "depth",
+            "nodes",
+            "infinite",
+            "searchmoves",
+        ],
+    ),
+    (
+        "infinite",
+        &[
+            "ponder", "btime", "wtime", "byoyomi", "binc", "winc", "movetime", "rtime", "depth",
+            "nodes",
+        ],
+    ),
+    ("byoyomi", &["binc", "winc", "movetime", "rtime"]),
+    ("movetime", &["binc", "winc", "rtime"]),
+    ("rtime", &["binc", "winc"]),
+];
+
 impl ThinkParams {
     pub fn new() -> Self {
         ThinkParams::default()
@@ -79,6 +135,32 @@ impl ThinkParams {
         self
     }
 
+    /// Searches for exactly the given time, regardless of the clocks.
+    #[must_use]
+    pub fn movetime(mut self, t: Duration) -> Self {
+        self.movetime = Some(t);
+        self
+    }
+
+    /// Searches for the given time with some randomness, which is an extension of YaneuraOu.
+    #[must_use]
+    pub fn rtime(mut self, t: Duration) -> Self {
+        self.rtime = Some(t);
+        self
+    }
+
+    #[must_use]
+    pub fn depth(mut self, depth: u32) -> Self {
+        self.depth = Some(depth);
+        self
+    }
+
+    #[must_use]
+    pub fn nodes(mut self, nodes: u64) -> Self {
+        self.nodes = Some(nodes);
+        self
+    }
+
     #[must_use]
     pub fn infinite(mut self) -> Self {
         self.infinite = true;
@@ -90,6 +172,50 @@ impl ThinkParams {
         self.mate = Some(t);
         self
     }
+
+    /// Restricts the search to the given moves in USI format.
+    #[must_use]
+    pub fn searchmoves<I, S>(mut self, moves: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.searchmoves.extend(moves.into_iter().map(Into::into));
+        self
+    }
+
+    /// Checks that no contradictory parameters are given together, such as `infinite` and
+    /// `byoyomi`, or `byoyomi` and `binc`.
+    pub fn validate(&self) -> Result<(), Error> {
+        let given = [
+            ("ponder", self.ponder),
+            ("btime", self.btime.is_some()),
+            ("wtime", self.wtime.is_some()),
+            ("byoyomi", self.byoyomi.is_some()),
+            ("binc", self.binc.is_some()),
+            ("winc", self.winc.is_some()),
+            ("movetime", self.movetime.is_some()),
+            ("rtime", self.rtime.is_some()),
+            ("depth", self.depth.is_some()),
+            ("nodes", self.nodes.is_some()),
+            ("infinite", self.infinite),
+            ("mate", self.mate.is_some()),
+            ("searchmoves", !self.searchmoves.is_empty()),
+        ];
+        let has = |name: &str| given.iter().any(|&(n, v)| v && n == name);
+
+        for &(name, conflicts) in CONFLICTS {
+            if !has(name) {
+                continue;
+            }
+            if let Some(other) = conflicts.iter().find(|v| has(v)) {
+                return Err(Error::IllegalThinkParams(format!(
+                    "{name} cannot be used with {other}"
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl fmt::Display for ThinkParams {
@@ -112,6 +238,18 @@ impl fmt::Display for ThinkParams {
         if let Some(t) = self.winc {
             write!(f, " winc {}", to_ms(t))?;
         }
+        if let Some(t) = self.movetime {
+            write!(f, " movetime {}", to_ms(t))?;
+        }
+        if let Some(t) = self.rtime {
+            write!(f, " rtime {}", to_ms(t))?;
+        }
+        if let Some(depth) = self.depth {
+            write!(f, " depth {depth}")?;
+        }
+        if let Some(nodes) = self.nodes {
+            write!(f, " nodes {nodes}")?;
+        }
         if self.infinite {
             write!(f, " infinite")?;
         }
@@ -121,6 +259,10 @@ impl fmt::Display for ThinkParams {
                 MateParam::Infinite => write!(f, " mate infinite")?,
             }
         }
+        // Moves are consumed up to the next parameter, so they are written last.
+        if !self.searchmoves.is_empty() {
+            write!(f, " searchmoves {}", self.searchmoves.join(" "))?;
+        }
 
         Ok(())
     }
@@ -140,6 +282,11 @@ impl fmt::Display for ThinkParams {
 /// assert_eq!("go btime 1000 wtime 2000", cmd.to_string());
 /// ```
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "lowercase")
+)]
 pub enum GuiCommand {
     GameOver(GameOverKind),
     Go(ThinkParams),
@@ -153,6 +300,17 @@ pub enum GuiCommand {
     Quit,
 }
 
+impl GuiCommand {
+    /// Parses a USI command string sent from the GUI into a new instance of `GuiCommand`.
+    ///
+    /// `position startpos` is normalized into the equivalent SFEN string, and `go` command with
+    /// contradictory parameters is rejected as described in [`ThinkParams::validate`].
+    pub fn parse(cmd: &str) -> Result<GuiCommand, Error> {
+        let parser = GuiCommandParser::new(cmd);
+        parser.parse()
+    }
+}
+
 impl fmt::Display for GuiCommand {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
@@ -218,6 +376,32 @@ mod tests {
                 GuiCommand::Go(ThinkParams::new().mate(MateParam::Infinite)),
             ),
             ("go ponder", GuiCommand::Go(ThinkParams::new().ponder())),
+            (
+                "go btime 30000 wtime 30000 movetime 1500",
+                GuiCommand::Go(
+                    ThinkParams::new()
+                        .btime(Duration::from_secs(30))
+                        .wtime(Duration::from_secs(30))
+                        .movetime(Duration::from_millis(1500)),
+                ),
+            ),
+            (
+                "go rtime 500",
+                GuiCommand::Go(ThinkParams::new().rtime(Duration::from_millis(500))),
+            ),
+            (
+                "go depth 12 nodes 1000000 searchmoves 7g7f 2g2f",
+                GuiCommand::Go(
+                    ThinkParams::new()
+                        .depth(12)
+                        .nodes(1_000_000)
+                        .searchmoves(["7g7f", "2g2f"]),
+                ),
+            ),
+            (
+                "go infinite searchmoves P*5e",
+                GuiCommand::Go(ThinkParams::new().infinite().searchmoves(["P*5e"])),
+            ),
             ("isready", GuiCommand::IsReady),
             ("ponderhit", GuiCommand::Ponderhit),
             (
@@ -244,6 +428,156 @@ mod tests {
 
         for c in &cases {
             assert_eq!(c.0, c.1.to_string());
+            assert_eq!(c.1, GuiCommand::parse(c.0).unwrap());
         }
     }
+
+    #[test]
+    fn parse() {
+        let ok_cases = [
+            (
+                "position startpos",
+                "position sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1",
+            ),
+            (
+                "position startpos moves 7g7f 3c3d",
+                "position sfen lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1 \
+                 moves 7g7f 3c3d",
+            ),
+            ("go  btime 100   wtime 200", "go btime 100 wtime 200"),
+            (
+                "go searchmoves 7g7f 2g2f depth 5 btime 100",
+                "go btime 100 depth 5 searchmoves 7g7f 2g2f",
+            ),
+            (
+                "setoption name Book File value user book.bin",
+                "setoption name Book File value user book.bin",
+            ),
+        ];
+
+        let ng_cases = [
+            "",
+            "usi_foo",
+            "gameover foo",
+            "go btime",
+            "go btime foo",
+            "go foo",
+            "go mate",
+            "go depth",
+            "go depth -1",
+            "go searchmoves",
+            "go searchmoves depth 5",
+            "go infinite byoyomi 1000",
+            "go byoyomi 1000 binc 1000",
+            "go mate 1000 ponder",
+            "position",
+            "position sfen 9/9/9/9/9/9/9/9/9 b",
+            "position startpos 7g7f",
+            "setoption",
+            "setoption name",
+        ];
+
+        for (i, c) in ok_cases.iter().enumerate() {
+            assert_eq!(
+                c.1,
+                GuiCommand::parse(c.0).unwrap().to_string(),
+                "failed at #{i}"
+            );
+        }
+
+        for (i, c) in ng_cases.iter().enumerate() {
+            assert!(GuiCommand::parse(c).is_err(), "failed at #{i}");
+        }
+    }
+
+    #[test]
+    fn validate() {
+        let cases = [
+            (
+                ThinkParams::new().infinite().depth(10),
+                "infinite cannot be used with depth",
+            ),
+            (
+                ThinkParams::new()
+                    .byoyomi(Duration::from_secs(10))
+                    .winc(Duration::from_secs(10)),
+                "byoyomi cannot be used with winc",
+            ),
+            (
+                ThinkParams::new()
+                    .movetime(Duration::from_secs(1))
+                    .rtime(Duration::from_secs(1)),
+                "movetime cannot be used with rtime",
+            ),
+            (
+                ThinkParams::new()
+                    .mate(MateParam::Infinite)
+                    .searchmoves(["7g7f"]),
+                "mate cannot be used with searchmoves",
+            ),
+        ];
+
+        for (params, msg) in cases {
+            match params.validate() {
+                Err(Error::IllegalThinkParams(e)) => assert_eq!(msg, e),
+                r => panic!("unexpected result: {r:?}"),
+            }
+        }
+
+        assert!(ThinkParams::new()
+            .ponder()
+            .btime(Duration::from_secs(10))
+            .wtime(Duration::from_secs(10))
+            .byoyomi(Duration::from_secs(10))
+            .depth(20)
+            .validate()
+            .is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let cases = [
+            (
+                r#"{"type":"go","value":{"ponder":false,"btime":60000,"wtime":50000,"byoyomi":null,"binc":null,"winc":null,"movetime":null,"rtime":null,"depth":null,"nodes":null,"infinite":false,"mate":{"type":"timeout","value":1500},"searchmoves":[]}}"#,
+                GuiCommand::Go(
+                    ThinkParams::new()
+                        .btime(Duration::from_secs(60))
+                        .wtime(Duration::from_secs(50))
+                        .mate(MateParam::Timeout(Duration::from_millis(1500))),
+                ),
+            ),
+            (
+                r#"{"type":"gameover","value":"win"}"#,
+                GuiCommand::GameOver(GameOverKind::Win),
+            ),
+            (
+                r#"{"type":"setoption","value":["USI_Hash","256"]}"#,
+                GuiCommand::SetOption("USI_Hash".to_string(), Some("256".to_string())),
+            ),
+            (r#"{"type":"isready"}"#, GuiCommand::IsReady),
+        ];
+
+        for (json, cmd) in cases {
+            assert_eq!(json, serde_json::to_string(&cmd).unwrap());
+            assert_eq!(cmd, serde_json::from_str::<GuiCommand>(json).unwrap());
+        }
+
+        // Missing parameters are left unset.
+        assert_eq!(
+            GuiCommand::Go(ThinkParams::new().byoyomi(Duration::from_secs(10))),
+            serde_json::from_str(r#"{"type":"go","value":{"byoyomi":10000}}"#).unwrap()
+        );
+        assert_eq!(
+            GuiCommand::Go(
+                ThinkParams::new()
+                    .movetime(Duration::from_millis(2500))
+                    .searchmoves(["7g7f"])
+            ),
+            serde_json::from_str(
+                r#"{"type":"go","value":{"movetime":2500,"searchmoves":["7g7f"]}}"#
+            )
+            .unwrap()
+        );
+    }
 }
@@ -1,6 +1,10 @@
 mod command;
+mod command_ref;
+#[cfg(feature = "serde")]
+mod duration_ms;
 mod gui;
 mod parser;
 
 pub use self::command::*;
+pub use self::command_ref::*;
 pub use self::gui::*;
@@ -1,292 +1,619 @@
 use itertools::Itertools;
-use std::str::SplitWhitespace;
+use std::str::{FromStr, SplitWhitespace};
 use std::time::Duration;
 
 use super::{
-    BestMoveParams, CheckmateParams, EngineCommand, IdParams, InfoParams, OptionKind, OptionParams,
-    ScoreKind,
+    BestMoveParamsRef, CheckmateParamsRef, EngineCommandRef, GameOverKind, GuiCommand, IdParamsRef,
+    InfoParamsRef, MateParam, OptionKindRef, OptionParamsRef, ParseMode, ScoreKind, ThinkParams,
 };
-use crate::error::Error;
+use crate::error::{Error, ParseError};
+use crate::shogi::STARTPOS_SFEN;
+
+/// Parameters which may follow `info`, where a malformed value is not consumed in lenient mode.
+const INFO_PARAMS: [&str; 11] = [
+    "depth", "seldepth", "time", "nodes", "pv", "multipv", "score", "currmove", "hashfull", "nps",
+    "string",
+];
 
 pub struct EngineCommandParser<'a> {
-    iter: SplitWhitespace<'a>,
+    line: &'a str,
+    tokens: Vec<(usize, &'a str)>,
+    pos: usize,
+    // Index of the token returned by the last `next`, or `None` at the end of the line.
+    last: Option<usize>,
+    command: Option<&'static str>,
+    mode: ParseMode,
+    warnings: Vec<ParseError>,
 }
 
 impl<'a> EngineCommandParser<'a> {
-    pub fn new(cmd: &str) -> EngineCommandParser<'_> {
+    pub fn new(cmd: &'a str, mode: ParseMode) -> EngineCommandParser<'a> {
+        let tokens = cmd
+            .split_whitespace()
+            .map(|t| (t.as_ptr() as usize - cmd.as_ptr() as usize, t))
+            .collect();
+
         EngineCommandParser {
-            iter: cmd.split_whitespace(),
+            line: cmd,
+            tokens,
+            pos: 0,
+            last: None,
+            command: None,
+            mode,
+            warnings: Vec::new(),
         }
     }
 
-    pub fn parse(mut self) -> Result<EngineCommand, Error> {
-        let command = self.iter.next();
-        if command.is_none() {
-            return Err(Error::IllegalSyntax);
-        }
+    /// Parses the command, returning deviations from the spec tolerated in lenient mode as well.
+    pub fn parse(mut self) -> Result<(EngineCommandRef<'a>, Vec<ParseError>), ParseError> {
+        let command = match self.next() {
+            Some(command) => command,
+            None => return Err(self.error(None, "a command")),
+        };
 
-        let command = command.unwrap();
-        Ok(match command {
+        let command = match command {
             "bestmove" => self.parse_bestmove()?,
             "checkmate" => self.parse_checkmate()?,
             "id" => self.parse_id()?,
             "info" => self.parse_info()?,
             "option" => self.parse_option()?,
-            "readyok" => EngineCommand::ReadyOk,
-            "usiok" => EngineCommand::UsiOk,
-            _ => EngineCommand::Unknown,
-        })
+            "readyok" => {
+                self.command = Some("readyok");
+                self.expect_end()?;
+                EngineCommandRef::ReadyOk
+            }
+            "usiok" => {
+                self.command = Some("usiok");
+                self.expect_end()?;
+                EngineCommandRef::UsiOk
+            }
+            _ => EngineCommandRef::Unknown,
+        };
+        Ok((command, self.warnings))
     }
 
-    fn parse_bestmove(mut self) -> Result<EngineCommand, Error> {
-        match (self.iter.next(), self.iter.next(), self.iter.next()) {
-            (Some("resign"), None, None) => Ok(EngineCommand::BestMove(BestMoveParams::Resign)),
-            (Some("win"), None, None) => Ok(EngineCommand::BestMove(BestMoveParams::Win)),
-            (Some(m), None, None) => Ok(EngineCommand::BestMove(BestMoveParams::MakeMove(
-                m.to_string(),
-                None,
-            ))),
-            (Some(m), Some("ponder"), Some(pm)) => Ok(EngineCommand::BestMove(
-                BestMoveParams::MakeMove(m.to_string(), Some(pm.to_string())),
-            )),
-            _ => Err(Error::IllegalSyntax),
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.tokens.get(self.pos).map(|&(_, t)| t);
+        self.last = token.map(|_| self.pos);
+        self.pos = (self.pos + 1).min(self.tokens.len());
+        token
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|&(_, t)| t)
+    }
+
+    fn rest(&mut self) -> Vec<&'a str> {
+        let rest = self.tokens[self.pos..].iter().map(|&(_, t)| t).collect();
+        self.pos = self.tokens.len();
+        rest
+    }
+
+    /// Returns the rest of the line as is, without splitting it into tokens.
+    fn rest_str(&mut self) -> &'a str {
+        let rest = match self.tokens.get(self.pos) {
+            Some(&(offset, _)) => self.line[offset..].trim_end(),
+            None => "",
+        };
+        self.pos = self.tokens.len();
+        rest
+    }
+
+    /// Returns an error at the token returned by the last `next`.
+    fn error(&self, field: Option<&'static str>, expected: &'static str) -> ParseError {
+        ParseError::new(
+            self.line,
+            self.last.map(|i| (i, self.tokens[i].0)),
+            self.command,
+            field,
+            expected,
+        )
+    }
+
+    /// Fails in strict mode, or records a warning in lenient mode so that parsing can go on.
+    fn deviation(
+        &mut self,
+        field: Option<&'static str>,
+        expected: &'static str,
+    ) -> Result<(), ParseError> {
+        let err = self.error(field, expected);
+        match self.mode {
+            ParseMode::Strict => Err(err),
+            ParseMode::Lenient => {
+                self.warnings.push(err);
+                Ok(())
+            }
         }
     }
 
-    fn parse_checkmate(mut self) -> Result<EngineCommand, Error> {
-        match self.iter.next() {
-            Some("notimplemented") => Ok(EngineCommand::Checkmate(CheckmateParams::NoMate)),
-            Some("timeout") => Ok(EngineCommand::Checkmate(CheckmateParams::Timeout)),
-            Some("nomate") => Ok(EngineCommand::Checkmate(CheckmateParams::NoMate)),
-            Some(s) => {
-                let mut moves = vec![s.to_string()];
-                self.iter.for_each(|s| {
-                    moves.push(s.to_string());
-                });
-                Ok(EngineCommand::Checkmate(CheckmateParams::Mate(moves)))
+    /// Checks that no tokens are left, ignoring them in lenient mode.
+    fn expect_end(&mut self) -> Result<(), ParseError> {
+        if self.next().is_some() {
+            self.deviation(None, "the end of the line")?;
+            self.rest();
+        }
+        Ok(())
+    }
+
+    /// Parses a number of `field`, which is `None` if malformed in lenient mode.
+    fn number<T: FromStr>(&mut self, field: &'static str) -> Result<Option<T>, ParseError> {
+        match self.next().map(str::parse) {
+            Some(Ok(n)) => Ok(Some(n)),
+            _ => {
+                self.deviation(Some(field), "an integer")?;
+                Ok(None)
             }
-            _ => Err(Error::IllegalSyntax),
         }
     }
 
-    fn parse_id(mut self) -> Result<EngineCommand, Error> {
-        match self.iter.next() {
-            Some("name") => Ok(EngineCommand::Id(IdParams::Name(self.iter.join(" ")))),
-            Some("author") => Ok(EngineCommand::Id(IdParams::Author(self.iter.join(" ")))),
-            _ => Err(Error::IllegalSyntax),
+    /// Parses a number of `field` in `info` command, leaving the next parameter unconsumed
+    /// if the value is missing in lenient mode.
+    fn info_number<T: FromStr>(&mut self, field: &'static str) -> Result<Option<T>, ParseError> {
+        let n = self.number(field)?;
+        if n.is_none()
+            && self
+                .last
+                .is_some_and(|i| INFO_PARAMS.contains(&self.tokens[i].1))
+        {
+            self.pos -= 1;
+        }
+        Ok(n)
+    }
+
+    /// Parses an optional default value of an option, which must be the last item.
+    fn default_value(&mut self) -> Result<Option<&'a str>, ParseError> {
+        match self.next() {
+            None => Ok(None),
+            Some("default") => match self.next() {
+                Some(v) => {
+                    self.expect_end()?;
+                    Ok(Some(v))
+                }
+                None => {
+                    self.deviation(Some("default"), "a value")?;
+                    Ok(None)
+                }
+            },
+            Some(v) => {
+                // Engines omitting `default` are tolerated in lenient mode.
+                self.deviation(None, "`default`")?;
+                self.expect_end()?;
+                Ok(Some(v))
+            }
         }
     }
 
-    fn parse_info(self) -> Result<EngineCommand, Error> {
-        let mut iter = self.iter.peekable();
+    fn parse_bestmove(&mut self) -> Result<EngineCommandRef<'a>, ParseError> {
+        self.command = Some("bestmove");
+
+        let m = match self.next() {
+            Some(m) => m,
+            None => return Err(self.error(None, "a move, `resign` or `win`")),
+        };
+        let mut ponder = None;
+        match self.next() {
+            None => {}
+            Some("ponder") => match self.next() {
+                Some(pm) => {
+                    ponder = Some(pm);
+                    self.expect_end()?;
+                }
+                None => self.deviation(Some("ponder"), "a move")?,
+            },
+            Some(_) => {
+                self.deviation(None, "`ponder`")?;
+                self.rest();
+            }
+        }
+
+        Ok(EngineCommandRef::BestMove(match (m, ponder) {
+            ("resign", None) => BestMoveParamsRef::Resign,
+            ("win", None) => BestMoveParamsRef::Win,
+            (m, ponder) => BestMoveParamsRef::MakeMove(m, ponder),
+        }))
+    }
+
+    fn parse_checkmate(&mut self) -> Result<EngineCommandRef<'a>, ParseError> {
+        self.command = Some("checkmate");
+
+        let params = match self.next() {
+            Some("notimplemented") => CheckmateParamsRef::NotImplemented,
+            Some("timeout") => CheckmateParamsRef::Timeout,
+            Some("nomate") => CheckmateParamsRef::NoMate,
+            Some(s) => {
+                let mut moves = vec![s];
+                moves.extend(self.rest());
+                CheckmateParamsRef::Mate(moves)
+            }
+            None => return Err(self.error(None, "moves, `nomate`, `timeout` or `notimplemented`")),
+        };
+        self.expect_end()?;
+
+        Ok(EngineCommandRef::Checkmate(params))
+    }
+
+    fn parse_id(&mut self) -> Result<EngineCommandRef<'a>, ParseError> {
+        self.command = Some("id");
+
+        let (field, make): (_, fn(&'a str) -> IdParamsRef<'a>) = match self.next() {
+            Some("name") => ("name", IdParamsRef::Name),
+            Some("author") => ("author", IdParamsRef::Author),
+            _ => return Err(self.error(None, "`name` or `author`")),
+        };
+        let value = self.rest_str();
+        if value.is_empty() {
+            self.next();
+            self.deviation(Some(field), "a value")?;
+        }
+
+        Ok(EngineCommandRef::Id(make(value)))
+    }
+
+    fn parse_info(&mut self) -> Result<EngineCommandRef<'a>, ParseError> {
+        self.command = Some("info");
         let mut entries = Vec::new();
 
-        while let Some(kind) = iter.next() {
+        while let Some(kind) = self.next() {
             match kind {
                 "depth" => {
-                    let depth: i32 = iter
-                        .next()
-                        .and_then(|s| s.parse().ok())
-                        .ok_or(Error::IllegalSyntax)?;
+                    let depth = self.info_number("depth")?;
 
                     let mut sel_depth = None;
-                    if let Some(&peek_kind) = iter.peek() {
-                        if peek_kind == "seldepth" {
-                            iter.next();
-
-                            sel_depth = Some(
-                                iter.next()
-                                    .and_then(|s| s.parse().ok())
-                                    .ok_or(Error::IllegalSyntax)?,
-                            );
-                        }
+                    if self.peek() == Some("seldepth") {
+                        self.next();
+                        sel_depth = self.info_number("seldepth")?;
                     }
 
-                    entries.push(InfoParams::Depth(depth, sel_depth));
+                    if let Some(depth) = depth {
+                        entries.push(InfoParamsRef::Depth(depth, sel_depth));
+                    }
                 }
                 "time" => {
-                    let ms: u64 = iter
-                        .next()
-                        .and_then(|s| s.parse().ok())
-                        .ok_or(Error::IllegalSyntax)?;
-                    entries.push(InfoParams::Time(Duration::from_millis(ms)));
+                    if let Some(ms) = self.info_number("time")? {
+                        entries.push(InfoParamsRef::Time(Duration::from_millis(ms)));
+                    }
                 }
                 "multipv" => {
-                    let multipv: i32 = iter
-                        .next()
-                        .and_then(|s| s.parse().ok())
-                        .ok_or(Error::IllegalSyntax)?;
-                    entries.push(InfoParams::MultiPv(multipv));
+                    if let Some(n) = self.info_number("multipv")? {
+                        entries.push(InfoParamsRef::MultiPv(n));
+                    }
                 }
                 "nodes" => {
-                    let nodes: i32 = iter
-                        .next()
-                        .and_then(|s| s.parse().ok())
-                        .ok_or(Error::IllegalSyntax)?;
-                    entries.push(InfoParams::Nodes(nodes));
+                    if let Some(n) = self.info_number("nodes")? {
+                        entries.push(InfoParamsRef::Nodes(n));
+                    }
                 }
                 "pv" => {
-                    let pvs = iter.map(|v| v.to_string()).collect::<Vec<_>>();
-                    entries.push(InfoParams::Pv(pvs));
+                    entries.push(InfoParamsRef::Pv(self.rest()));
                     // "pv" or "str" must be the final item.
                     break;
                 }
-                "score" => match (iter.next(), iter.next()) {
-                    (Some("cp"), Some(cp)) => {
-                        let cp: i32 = cp.parse()?;
-
-                        if let Some(&peek_kind) = iter.peek() {
-                            match peek_kind {
-                                "lowerbound" => {
-                                    iter.next();
-                                    entries.push(InfoParams::Score(cp, ScoreKind::CpLowerbound));
-                                }
-                                "upperbound" => {
-                                    iter.next();
-                                    entries.push(InfoParams::Score(cp, ScoreKind::CpUpperbound));
-                                }
-                                _ => {
-                                    entries.push(InfoParams::Score(cp, ScoreKind::CpExact));
-                                }
-                            }
-                        }
-                    }
-                    (Some("mate"), Some("+")) => {
-                        entries.push(InfoParams::Score(1, ScoreKind::MateSignOnly))
+                "score" => {
+                    if let Some(score) = self.parse_score()? {
+                        entries.push(score);
                     }
-                    (Some("mate"), Some("-")) => {
-                        entries.push(InfoParams::Score(-1, ScoreKind::MateSignOnly))
-                    }
-                    (Some("mate"), Some(ply)) => {
-                        let ply: i32 = ply.parse()?;
-
-                        if let Some(&peek_kind) = iter.peek() {
-                            match peek_kind {
-                                "lowerbound" => {
-                                    iter.next();
-                                    entries.push(InfoParams::Score(ply, ScoreKind::MateLowerbound));
-                                }
-                                "upperbound" => {
-                                    iter.next();
-                                    entries.push(InfoParams::Score(ply, ScoreKind::MateUpperbound));
-                                }
-                                _ => {
-                                    entries.push(InfoParams::Score(ply, ScoreKind::MateExact));
-                                }
-                            }
-                        }
-                    }
-                    _ => return Err(Error::IllegalSyntax),
-                },
-                "currmove" => {
-                    let currmove = iter.next().ok_or(Error::IllegalSyntax)?;
-                    entries.push(InfoParams::CurrMove(currmove.to_string()));
                 }
+                "currmove" => match self.next() {
+                    Some(currmove) => entries.push(InfoParamsRef::CurrMove(currmove)),
+                    None => self.deviation(Some("currmove"), "a move")?,
+                },
                 "hashfull" => {
-                    let hashfull: i32 = iter
-                        .next()
-                        .and_then(|s| s.parse().ok())
-                        .ok_or(Error::IllegalSyntax)?;
-                    entries.push(InfoParams::HashFull(hashfull));
+                    if let Some(n) = self.info_number("hashfull")? {
+                        entries.push(InfoParamsRef::HashFull(n));
+                    }
                 }
                 "nps" => {
-                    let nps: i32 = iter
-                        .next()
-                        .and_then(|s| s.parse().ok())
-                        .ok_or(Error::IllegalSyntax)?;
-                    entries.push(InfoParams::Nps(nps));
+                    if let Some(n) = self.info_number("nps")? {
+                        entries.push(InfoParamsRef::Nps(n));
+                    }
                 }
                 "string" => {
-                    entries.push(InfoParams::Text(iter.join(" ")));
+                    entries.push(InfoParamsRef::Text(self.rest_str()));
                     // "pv" or "str" must be the final item.
                     break;
                 }
-                _ => return Err(Error::IllegalSyntax),
+                _ => self.deviation(None, "an info parameter")?,
             }
         }
 
-        Ok(EngineCommand::Info(entries))
+        Ok(EngineCommandRef::Info(entries))
     }
 
-    fn parse_option(mut self) -> Result<EngineCommand, Error> {
-        let opt_name = match (self.iter.next(), self.iter.next(), self.iter.next()) {
-            (Some("name"), Some(opt_name), Some("type")) => opt_name,
-            _ => return Err(Error::IllegalSyntax),
+    fn parse_score(&mut self) -> Result<Option<InfoParamsRef<'a>>, ParseError> {
+        let (value, exact, lower, upper) = match self.next() {
+            Some("cp") => match self.info_number("score cp")? {
+                Some(cp) => (
+                    cp,
+                    ScoreKind::CpExact,
+                    ScoreKind::CpLowerbound,
+                    ScoreKind::CpUpperbound,
+                ),
+                None => return Ok(None),
+            },
+            Some("mate") => match self.next() {
+                Some("+") => return Ok(Some(InfoParamsRef::Score(1, ScoreKind::MateSignOnly))),
+                Some("-") => return Ok(Some(InfoParamsRef::Score(-1, ScoreKind::MateSignOnly))),
+                ply => match ply.map(str::parse) {
+                    Some(Ok(ply)) => (
+                        ply,
+                        ScoreKind::MateExact,
+                        ScoreKind::MateLowerbound,
+                        ScoreKind::MateUpperbound,
+                    ),
+                    _ => {
+                        self.deviation(Some("score mate"), "an integer, `+` or `-`")?;
+                        return Ok(None);
+                    }
+                },
+            },
+            _ => {
+                self.deviation(Some("score"), "`cp` or `mate`")?;
+                return Ok(None);
+            }
         };
 
-        let opt_type = match self.iter.next() {
+        let kind = match self.peek() {
+            Some("lowerbound") => {
+                self.next();
+                lower
+            }
+            Some("upperbound") => {
+                self.next();
+                upper
+            }
+            _ => exact,
+        };
+        Ok(Some(InfoParamsRef::Score(value, kind)))
+    }
+
+    fn parse_option(&mut self) -> Result<EngineCommandRef<'a>, ParseError> {
+        self.command = Some("option");
+
+        if self.next() != Some("name") {
+            return Err(self.error(None, "`name`"));
+        }
+        let opt_name = match self.next() {
+            Some(opt_name) => opt_name,
+            None => return Err(self.error(Some("name"), "an option name")),
+        };
+        if self.next() != Some("type") {
+            return Err(self.error(None, "`type`"));
+        }
+
+        let opt_type = match self.next() {
             Some("check") => {
-                let default = self
-                    .iter
-                    .find(|v| *v != "default")
-                    .and_then(|s| s.parse().ok());
+                let default = match self.default_value()? {
+                    Some("true") => Some(true),
+                    Some("false") => Some(false),
+                    Some(_) => {
+                        self.deviation(Some("default"), "`true` or `false`")?;
+                        None
+                    }
+                    None => None,
+                };
 
-                OptionKind::Check { default }
+                OptionKindRef::Check { default }
             }
             Some("spin") => {
                 let mut default = None;
                 let mut min = None;
                 let mut max = None;
 
-                while let Some(kind) = self.iter.next() {
+                while let Some(kind) = self.next() {
                     match kind {
-                        "default" => default = self.iter.next().and_then(|s| s.parse().ok()),
-                        "min" => min = self.iter.next().and_then(|s| s.parse().ok()),
-                        "max" => max = self.iter.next().and_then(|s| s.parse().ok()),
-                        _ => {}
+                        "default" => default = self.number("default")?,
+                        "min" => min = self.number("min")?,
+                        "max" => max = self.number("max")?,
+                        _ => self.deviation(None, "`default`, `min` or `max`")?,
                     }
                 }
 
-                OptionKind::Spin { default, min, max }
+                OptionKindRef::Spin { default, min, max }
             }
             Some("combo") => {
                 let mut default = None;
                 let mut vars = Vec::new();
 
-                while let Some(kind) = self.iter.next() {
-                    match kind {
-                        "default" => default = self.iter.next().map(parse_default),
-                        "var" => {
-                            self.iter.for_each(|v| {
-                                vars.push(v.to_string());
-                            });
-                            break;
+                while let Some(kind) = self.next() {
+                    let field = match kind {
+                        "default" => "default",
+                        "var" => "var",
+                        _ => {
+                            self.deviation(None, "`default` or `var`")?;
+                            continue;
                         }
-                        _ => {}
+                    };
+                    match (field, self.next()) {
+                        ("default", Some(v)) => default = Some(parse_default(v)),
+                        (_, Some(v)) => vars.push(v),
+                        (_, None) => self.deviation(Some(field), "a value")?,
                     }
                 }
 
-                OptionKind::Combo { default, vars }
+                OptionKindRef::Combo { default, vars }
             }
             Some("button") => {
-                let default = self.iter.find(|v| *v != "default").map(parse_default);
+                let default = self.default_value()?.map(parse_default);
 
-                OptionKind::Button { default }
+                OptionKindRef::Button { default }
             }
             Some("string") => {
-                let default = self.iter.find(|v| *v != "default").map(parse_default);
+                let default = self.default_value()?.map(parse_default);
 
-                OptionKind::String { default }
+                OptionKindRef::String { default }
             }
             Some("filename") => {
-                let default = self.iter.find(|v| *v != "default").map(parse_default);
+                let default = self.default_value()?.map(parse_default);
 
-                OptionKind::Filename { default }
+                OptionKindRef::Filename { default }
+            }
+            _ => {
+                return Err(self.error(
+                    Some("type"),
+                    "`check`, `spin`, `combo`, `button`, `string` or `filename`",
+                ))
             }
-            _ => return Err(Error::IllegalSyntax),
         };
 
-        Ok(EngineCommand::Option(OptionParams {
-            name: opt_name.to_string(),
+        Ok(EngineCommandRef::Option(OptionParamsRef {
+            name: opt_name,
             value: opt_type,
         }))
     }
 }
 
-fn parse_default(s: &str) -> String {
+/// Names of the parameters of "go" command, which end the list of `searchmoves`.
+const GO_PARAMS: [&str; 13] = [
+    "ponder",
+    "btime",
+    "wtime",
+    "byoyomi",
+    "binc",
+    "winc",
+    "movetime",
+    "rtime",
+    "depth",
+    "nodes",
+    "infinite",
+    "searchmoves",
+    "mate",
+];
+
+pub struct GuiCommandParser<'a> {
+    iter: SplitWhitespace<'a>,
+}
+
+impl<'a> GuiCommandParser<'a> {
+    pub fn new(cmd: &str) -> GuiCommandParser<'_> {
+        GuiCommandParser {
+            iter: cmd.split_whitespace(),
+        }
+    }
+
+    pub fn parse(mut self) -> Result<GuiCommand, Error> {
+        let command = self.iter.next().ok_or(Error::IllegalSyntax)?;
+
+        Ok(match command {
+            "gameover" => self.parse_gameover()?,
+            "go" => self.parse_go()?,
+            "isready" => GuiCommand::IsReady,
+            "ponderhit" => GuiCommand::Ponderhit,
+            "position" => self.parse_position()?,
+            "setoption" => self.parse_setoption()?,
+            "stop" => GuiCommand::Stop,
+            "usi" => GuiCommand::Usi,
+            "usinewgame" => GuiCommand::UsiNewGame,
+            "quit" => GuiCommand::Quit,
+            _ => return Err(Error::IllegalSyntax),
+        })
+    }
+
+    fn parse_gameover(mut self) -> Result<GuiCommand, Error> {
+        match (self.iter.next(), self.iter.next()) {
+            (Some("win"), None) => Ok(GuiCommand::GameOver(GameOverKind::Win)),
+            (Some("lose"), None) => Ok(GuiCommand::GameOver(GameOverKind::Lose)),
+            (Some("draw"), None) => Ok(GuiCommand::GameOver(GameOverKind::Draw)),
+            _ => Err(Error::IllegalSyntax),
+        }
+    }
+
+    fn parse_go(mut self) -> Result<GuiCommand, Error> {
+        let mut params = ThinkParams::new();
+
+        while let Some(kind) = self.iter.next() {
+            params = match kind {
+                "ponder" => params.ponder(),
+                "btime" => params.btime(self.next_duration()?),
+                "wtime" => params.wtime(self.next_duration()?),
+                "byoyomi" => params.byoyomi(self.next_duration()?),
+                "binc" => params.binc(self.next_duration()?),
+                "winc" => params.winc(self.next_duration()?),
+                "movetime" => params.movetime(self.next_duration()?),
+                "rtime" => params.rtime(self.next_duration()?),
+                "depth" => params.depth(self.iter.next().ok_or(Error::IllegalSyntax)?.parse()?),
+                "nodes" => params.nodes(self.iter.next().ok_or(Error::IllegalSyntax)?.parse()?),
+                "infinite" => params.infinite(),
+                "searchmoves" => {
+                    let mut moves = Vec::new();
+                    while let Some(v) = self.iter.clone().next() {
+                        if GO_PARAMS.contains(&v) {
+                            break;
+                        }
+                        moves.push(v);
+                        self.iter.next();
+                    }
+                    if moves.is_empty() {
+                        return Err(Error::IllegalSyntax);
+                    }
+                    params.searchmoves(moves)
+                }
+                "mate" => match self.iter.next() {
+                    Some("infinite") => params.mate(MateParam::Infinite),
+                    Some(ms) => params.mate(MateParam::Timeout(Duration::from_millis(ms.parse()?))),
+                    None => return Err(Error::IllegalSyntax),
+                },
+                _ => return Err(Error::IllegalSyntax),
+            };
+        }
+
+        params.validate()?;
+        Ok(GuiCommand::Go(params))
+    }
+
+    fn parse_position(mut self) -> Result<GuiCommand, Error> {
+        let sfen = match self.iter.next() {
+            Some("startpos") => STARTPOS_SFEN.to_string(),
+            Some("sfen") => {
+                let fields = self.iter.by_ref().take(4).collect::<Vec<_>>();
+                if fields.len() != 4 {
+                    return Err(Error::IllegalSyntax);
+                }
+                fields.join(" ")
+            }
+            _ => return Err(Error::IllegalSyntax),
+        };
+
+        match self.iter.next() {
+            None => Ok(GuiCommand::Position(sfen)),
+            Some("moves") => {
+                let moves = self.iter.join(" ");
+                if moves.is_empty() {
+                    Ok(GuiCommand::Position(sfen))
+                } else {
+                    Ok(GuiCommand::Position(format!("{sfen} moves {moves}")))
+                }
+            }
+            _ => Err(Error::IllegalSyntax),
+        }
+    }
+
+    fn parse_setoption(mut self) -> Result<GuiCommand, Error> {
+        if self.iter.next() != Some("name") {
+            return Err(Error::IllegalSyntax);
+        }
+
+        let name = self.iter.by_ref().take_while(|v| *v != "value").join(" ");
+        if name.is_empty() {
+            return Err(Error::IllegalSyntax);
+        }
+
+        let value = self.iter.join(" ");
+        Ok(GuiCommand::SetOption(
+            name,
+            if value.is_empty() { None } else { Some(value) },
+        ))
+    }
+
+    fn next_duration(&mut self) -> Result<Duration, Error> {
+        let ms: u64 = self.iter.next().ok_or(Error::IllegalSyntax)?.parse()?;
+        Ok(Duration::from_millis(ms))
+    }
+}
+
+fn parse_default(s: &str) -> &str {
     if s == "<empty>" {
-        String::new()
+        ""
     } else {
-        s.to_string()
+        s
     }
 }
@@ -0,0 +1,10 @@
+mod moves;
+mod position;
+mod repetition;
+mod types;
+mod zobrist;
+
+pub use self::moves::Move;
+pub use self::position::{Position, STARTPOS_SFEN};
+pub use self::repetition::{Repetition, RepetitionTracker};
+pub use self::types::{Color, Piece, PieceType, Square};
@@ -0,0 +1,98 @@
+use std::fmt;
+use std::str::FromStr;
+
+use super::{PieceType, Square};
+use crate::error::Error;
+
+/// Represents a move in USI notation, such as `7g7f`, `8h2b+` or `P*5e`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Move {
+    Normal {
+        from: Square,
+        to: Square,
+        promote: bool,
+    },
+    Drop {
+        to: Square,
+        piece_type: PieceType,
+    },
+}
+
+impl Move {
+    /// Returns the destination square.
+    pub fn to(&self) -> Square {
+        match *self {
+            Move::Normal { to, .. } => to,
+            Move::Drop { to, .. } => to,
+        }
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Move::Normal { from, to, promote } => {
+                write!(f, "{from}{to}{}", if promote { "+" } else { "" })
+            }
+            Move::Drop { to, piece_type } => write!(f, "{piece_type}*{to}"),
+        }
+    }
+}
+
+impl FromStr for Move {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Move, Error> {
+        let err = || Error::IllegalMove(s.to_string());
+        if !s.is_ascii() {
+            return Err(err());
+        }
+
+        match s.len() {
+            4 if &s[1..2] == "*" => {
+                let piece_type: PieceType = s[0..1].parse().map_err(|_| err())?;
+                if !s.as_bytes()[0].is_ascii_uppercase() || piece_type.hand_index().is_none() {
+                    return Err(err());
+                }
+
+                Ok(Move::Drop {
+                    to: s[2..4].parse()?,
+                    piece_type,
+                })
+            }
+            4 | 5 => {
+                let promote = match s.get(4..) {
+                    Some("+") => true,
+                    Some("") => false,
+                    _ => return Err(err()),
+                };
+
+                Ok(Move::Normal {
+                    from: s[0..2].parse()?,
+                    to: s[2..4].parse()?,
+                    promote,
+                })
+            }
+            _ => Err(err()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let ok_cases = ["7g7f", "8h2b+", "P*5e", "R*1a"];
+        let ng_cases = ["", "7g", "7g7f=", "7g7f++", "K*5e", "+P*5e", "p*5e", "7g0f"];
+
+        for (i, c) in ok_cases.iter().enumerate() {
+            assert_eq!(*c, c.parse::<Move>().unwrap().to_string(), "failed at #{i}");
+        }
+
+        for (i, c) in ng_cases.iter().enumerate() {
+            assert!(c.parse::<Move>().is_err(), "failed at #{i}");
+        }
+    }
+}
@@ -0,0 +1,638 @@
+use std::fmt;
+
+use super::zobrist::KEYS;
+use super::{Color, Move, Piece, PieceType, Square};
+use crate::error::Error;
+
+/// SFEN representation of the initial position.
+pub const STARTPOS_SFEN: &str = "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1";
+
+const ALL_DIRECTIONS: [(i8, i8); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+const GOLD_STEPS: [(i8, i8); 6] = [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (0, 1)];
+const SILVER_STEPS: [(i8, i8); 5] = [(-1, -1), (0, -1), (1, -1), (-1, 1), (1, 1)];
+const KNIGHT_STEPS: [(i8, i8); 2] = [(-1, -2), (1, -2)];
+const PAWN_STEPS: [(i8, i8); 1] = [(0, -1)];
+const DIAGONALS: [(i8, i8); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+const ORTHOGONALS: [(i8, i8); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+/// Offsets of files and ranks, seen from the black side.
+type Directions = &'static [(i8, i8)];
+
+/// Returns the single-step and sliding directions of a piece.
+fn movements(pt: PieceType) -> (Directions, Directions) {
+    match pt {
+        PieceType::King => (&ALL_DIRECTIONS, &[]),
+        PieceType::Rook => (&[], &ORTHOGONALS),
+        PieceType::Bishop => (&[], &DIAGONALS),
+        PieceType::Dragon => (&DIAGONALS, &ORTHOGONALS),
+        PieceType::Horse => (&ORTHOGONALS, &DIAGONALS),
+        PieceType::Silver => (&SILVER_STEPS, &[]),
+        PieceType::Knight => (&KNIGHT_STEPS, &[]),
+        PieceType::Lance => (&[], &PAWN_STEPS),
+        PieceType::Pawn => (&PAWN_STEPS, &[]),
+        _ => (&GOLD_STEPS, &[]),
+    }
+}
+
+/// Returns the number of last ranks where the piece has no legal moves.
+fn dead_ranks(pt: PieceType) -> u8 {
+    match pt {
+        PieceType::Pawn | PieceType::Lance => 1,
+        PieceType::Knight => 2,
+        _ => 0,
+    }
+}
+
+/// Represents a state of the game: pieces on the board and in hand, and the side to move.
+///
+/// # Examples
+///
+/// ```
+/// use usi::{Move, Position};
+///
+/// let mut pos = Position::startpos();
+/// pos.make_move(&"7g7f".parse::<Move>().unwrap()).unwrap();
+/// assert_eq!(
+///     "lnsgkgsnl/1r5b1/ppppppppp/9/9/2P6/PP1PPPPPP/1B5R1/LNSGKGSNL w - 2",
+///     pos.to_sfen()
+/// );
+/// assert!(pos.make_move(&"7g7f".parse::<Move>().unwrap()).is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Position {
+    board: [Option<Piece>; 81],
+    hands: [[u8; 7]; 2],
+    side_to_move: Color,
+    ply: u32,
+}
+
+impl Position {
+    /// Returns the initial position.
+    pub fn startpos() -> Position {
+        Position::from_sfen(STARTPOS_SFEN).unwrap()
+    }
+
+    /// Parses a SFEN string. Moves following `moves` are applied to the position if exist.
+    pub fn from_sfen(sfen: &str) -> Result<Position, Error> {
+        let err = || Error::IllegalSfen(sfen.to_string());
+        let mut iter = sfen.split_whitespace();
+        let (board, side, hand) = match (iter.next(), iter.next(), iter.next()) {
+            (Some(board), Some(side), Some(hand)) => (board, side, hand),
+            _ => return Err(err()),
+        };
+
+        let mut pos = Position {
+            board: [None; 81],
+            hands: [[0; 7]; 2],
+            side_to_move: match side {
+                "b" => Color::Black,
+                "w" => Color::White,
+                _ => return Err(err()),
+            },
+            ply: 1,
+        };
+
+        let rows = board.split('/').collect::<Vec<_>>();
+        if rows.len() != 9 {
+            return Err(err());
+        }
+        for (r, row) in rows.iter().enumerate() {
+            let mut file = 10u8;
+            let mut promoted = false;
+            for c in row.chars() {
+                match c {
+                    '+' if !promoted => promoted = true,
+                    '1'..='9' if !promoted => file = file.saturating_sub(c as u8 - b'0'),
+                    _ => {
+                        let s = if promoted {
+                            format!("+{c}")
+                        } else {
+                            c.to_string()
+                        };
+                        let piece: Piece = s.parse().map_err(|_| err())?;
+                        file = file.saturating_sub(1);
+                        let sq = Square::new(file, r as u8 + 1).ok_or_else(err)?;
+                        pos.board[sq.index()] = Some(piece);
+                        promoted = false;
+                    }
+                }
+                if file == 0 && c != '+' {
+                    return Err(err());
+                }
+            }
+            if file != 1 || promoted {
+                return Err(err());
+            }
+        }
+
+        if hand != "-" {
+            let mut count = 0u32;
+            for c in hand.chars() {
+                if let Some(d) = c.to_digit(10) {
+                    count = count
+                        .checked_mul(10)
+                        .and_then(|n| n.checked_add(d))
+                        .ok_or_else(err)?;
+                    continue;
+                }
+                let piece: Piece = c.to_string().parse().map_err(|_| err())?;
+                let idx = piece.piece_type.hand_index().ok_or_else(err)?;
+                let held = &mut pos.hands[piece.color.index()][idx];
+                let n = u32::from(*held) + count.max(1);
+                if n > u32::from(piece.piece_type.max_count()) {
+                    return Err(err());
+                }
+                *held = n as u8;
+                count = 0;
+            }
+            if count != 0 {
+                return Err(err());
+            }
+        }
+
+        match iter.next() {
+            None => return Ok(pos),
+            Some("moves") => {}
+            Some(ply) => {
+                pos.ply = ply.parse().map_err(|_| err())?;
+                match iter.next() {
+                    None => return Ok(pos),
+                    Some("moves") => {}
+                    Some(_) => return Err(err()),
+                }
+            }
+        }
+
+        for m in iter {
+            pos.make_move(&m.parse()?)?;
+        }
+        Ok(pos)
+    }
+
+    /// Returns the SFEN representation of this position.
+    pub fn to_sfen(&self) -> String {
+        format!(
+            "{} {} {} {}",
+            self.board_sfen(),
+            self.side_to_move,
+            self.hand_sfen(),
+            self.ply
+        )
+    }
+
+    /// Returns the piece at the given square.
+    pub fn piece_at(&self, sq: Square) -> Option<Piece> {
+        self.board[sq.index()]
+    }
+
+    /// Returns the number of pieces of the given type held in hand.
+    pub fn hand(&self, color: Color, piece_type: PieceType) -> u8 {
+        piece_type
+            .hand_index()
+            .map_or(0, |i| self.hands[color.index()][i])
+    }
+
+    /// Returns the side to move.
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    /// Returns the move number, as recorded in the last field of SFEN.
+    pub fn ply(&self) -> u32 {
+        self.ply
+    }
+
+    /// Returns the Zobrist hash of the pieces on the board and in hand, and the side to move.
+    /// Positions differing only in the move number have the same key.
+    pub fn key(&self) -> u64 {
+        let mut key = match self.side_to_move {
+            Color::Black => 0,
+            Color::White => KEYS.side,
+        };
+        for (i, piece) in self.board.iter().enumerate() {
+            if let Some(p) = piece {
+                key ^= KEYS.board[p.color.index()][p.piece_type as usize][i];
+            }
+        }
+        for (c, hand) in self.hands.iter().enumerate() {
+            for (i, n) in hand.iter().enumerate() {
+                key = key.wrapping_add(KEYS.hand[c][i].wrapping_mul(*n as u64));
+            }
+        }
+        key
+    }
+
+    /// Returns the square of the king of the given color.
+    pub fn king_square(&self, color: Color) -> Option<Square> {
+        Square::iter().find(|sq| self.board[sq.index()] == Some(Piece::new(PieceType::King, color)))
+    }
+
+    /// Returns true if the king of the given color is attacked.
+    pub fn in_check(&self, color: Color) -> bool {
+        self.king_square(color)
+            .is_some_and(|sq| self.is_attacked(sq, color.flip()))
+    }
+
+    /// Returns true if the side to move is checkmated.
+    pub fn is_checkmate(&self) -> bool {
+        self.in_check(self.side_to_move) && self.legal_moves().is_empty()
+    }
+
+    /// Returns true if the given move is legal in this position.
+    pub fn is_legal(&self, m: &Move) -> bool {
+        self.is_pseudo_legal(m) && self.is_legal_pseudo(m)
+    }
+
+    /// Returns true if the given legal move checks the opponent's king.
+    pub fn gives_check(&self, m: &Move) -> bool {
+        let mut pos = self.clone();
+        pos.apply(m);
+        pos.in_check(pos.side_to_move)
+    }
+
+    /// Returns all legal moves in this position.
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let mut moves = self.pseudo_legal_moves();
+        moves.retain(|m| self.is_legal_pseudo(m));
+        moves
+    }
+
+    /// Applies the given move if it is legal, and returns the captured piece if any.
+    pub fn make_move(&mut self, m: &Move) -> Result<Option<Piece>, Error> {
+        if !self.is_legal(m) {
+            return Err(Error::IllegalMove(m.to_string()));
+        }
+        Ok(self.apply(m))
+    }
+
+    fn board_sfen(&self) -> String {
+        let mut s = String::new();
+        for rank in 1..=9 {
+            if rank > 1 {
+                s.push('/');
+            }
+            let mut empty = 0;
+            for file in (1..=9).rev() {
+                match self.board[Square::new(file, rank).unwrap().index()] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            s.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        s.push_str(&piece.to_string());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                s.push_str(&empty.to_string());
+            }
+        }
+        s
+    }
+
+    fn hand_sfen(&self) -> String {
+        let mut s = String::new();
+        for color in [Color::Black, Color::White] {
+            for (i, pt) in PieceType::HAND_TYPES.iter().enumerate() {
+                let n = self.hands[color.index()][i];
+                if n > 1 {
+                    s.push_str(&n.to_string());
+                }
+                if n > 0 {
+                    s.push_str(&Piece::new(*pt, color).to_string());
+                }
+            }
+        }
+        if s.is_empty() {
+            s.push('-');
+        }
+        s
+    }
+
+    /// Applies a move without checking its legality.
+    fn apply(&mut self, m: &Move) -> Option<Piece> {
+        let color = self.side_to_move;
+        let captured = match *m {
+            Move::Normal { from, to, promote } => {
+                let mut piece = self.board[from.index()].take().expect("no piece to move");
+                if promote {
+                    piece.piece_type = piece.piece_type.promote().expect("unpromotable piece");
+                }
+                let captured = self.board[to.index()].replace(piece);
+                if let Some(idx) = captured.and_then(|p| p.piece_type.unpromote().hand_index()) {
+                    self.hands[color.index()][idx] += 1;
+                }
+                captured
+            }
+            Move::Drop { to, piece_type } => {
+                let idx = piece_type.hand_index().expect("undroppable piece");
+                self.hands[color.index()][idx] -= 1;
+                self.board[to.index()] = Some(Piece::new(piece_type, color));
+                None
+            }
+        };
+
+        self.side_to_move = color.flip();
+        self.ply += 1;
+        captured
+    }
+
+    /// Iterates over squares a piece on `from` attacks, including ones occupied by any pieces.
+    fn attacks(&self, from: Square, piece: Piece) -> Vec<Square> {
+        let sign = match piece.color {
+            Color::Black => 1,
+            Color::White => -1,
+        };
+        let (steps, slides) = movements(piece.piece_type);
+
+        let mut targets = Vec::new();
+        for &(df, dr) in steps {
+            if let Some(to) = from.shift(df * sign, dr * sign) {
+                targets.push(to);
+            }
+        }
+        for &(df, dr) in slides {
+            let mut cur = from;
+            while let Some(to) = cur.shift(df * sign, dr * sign) {
+                targets.push(to);
+                if self.board[to.index()].is_some() {
+                    break;
+                }
+                cur = to;
+            }
+        }
+        targets
+    }
+
+    fn is_attacked(&self, sq: Square, by: Color) -> bool {
+        Square::iter().any(|from| match self.board[from.index()] {
+            Some(piece) if piece.color == by => self.attacks(from, piece).contains(&sq),
+            _ => false,
+        })
+    }
+
+    fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let color = self.side_to_move;
+        let mut moves = Vec::new();
+
+        for from in Square::iter() {
+            let piece = match self.board[from.index()] {
+                Some(p) if p.color == color => p,
+                _ => continue,
+            };
+
+            for to in self.attacks(from, piece) {
+                if self.board[to.index()].is_some_and(|p| p.color == color) {
+                    continue;
+                }
+                if piece.piece_type.promote().is_some()
+                    && (from.in_promotion_zone(color) || to.in_promotion_zone(color))
+                {
+                    moves.push(Move::Normal {
+                        from,
+                        to,
+                        promote: true,
+                    });
+                }
+                if to.relative_rank(color) > dead_ranks(piece.piece_type) {
+                    moves.push(Move::Normal {
+                        from,
+                        to,
+                        promote: false,
+                    });
+                }
+            }
+        }
+
+        for piece_type in PieceType::HAND_TYPES {
+            if self.hand(color, piece_type) == 0 {
+                continue;
+            }
+            for to in Square::iter() {
+                let m = Move::Drop { to, piece_type };
+                if self.is_pseudo_legal_drop(to, piece_type) {
+                    moves.push(m);
+                }
+            }
+        }
+
+        moves
+    }
+
+    fn is_pseudo_legal(&self, m: &Move) -> bool {
+        let color = self.side_to_move;
+        match *m {
+            Move::Normal { from, to, promote } => {
+                let piece = match self.board[from.index()] {
+                    Some(p) if p.color == color => p,
+                    _ => return false,
+                };
+                if self.board[to.index()].is_some_and(|p| p.color == color)
+                    || !self.attacks(from, piece).contains(&to)
+                {
+                    return false;
+                }
+                if promote {
+                    piece.piece_type.promote().is_some()
+                        && (from.in_promotion_zone(color) || to.in_promotion_zone(color))
+                } else {
+                    to.relative_rank(color) > dead_ranks(piece.piece_type)
+                }
+            }
+            Move::Drop { to, piece_type } => {
+                self.hand(color, piece_type) > 0 && self.is_pseudo_legal_drop(to, piece_type)
+            }
+        }
+    }
+
+    fn is_pseudo_legal_drop(&self, to: Square, piece_type: PieceType) -> bool {
+        let color = self.side_to_move;
+        if self.board[to.index()].is_some() || to.relative_rank(color) <= dead_ranks(piece_type) {
+            return false;
+        }
+
+        // Two unpromoted pawns on the same file (nifu) are not allowed.
+        piece_type != PieceType::Pawn
+            || !(1..=9).any(|rank| {
+                self.board[Square::new(to.file(), rank).unwrap().index()]
+                    == Some(Piece::new(PieceType::Pawn, color))
+            })
+    }
+
+    /// Checks if a pseudo-legal move leaves the own king safe and is not a checkmate by a pawn drop.
+    fn is_legal_pseudo(&self, m: &Move) -> bool {
+        let color = self.side_to_move;
+        let mut pos = self.clone();
+        pos.apply(m);
+        if pos.in_check(color) {
+            return false;
+        }
+
+        if let Move::Drop {
+            piece_type: PieceType::Pawn,
+            ..
+        } = *m
+        {
+            // Drops cannot resolve a check by an adjacent pawn, so it is enough to
+            // look for evasions by moving pieces on the board.
+            if pos.in_check(color.flip()) {
+                return pos
+                    .pseudo_legal_moves()
+                    .iter()
+                    .any(|m| matches!(m, Move::Normal { .. }) && pos.is_legal_pseudo(m));
+            }
+        }
+        true
+    }
+}
+
+impl Default for Position {
+    fn default() -> Position {
+        Position::startpos()
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_sfen())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legal_move_strings(sfen: &str) -> Vec<String> {
+        let mut moves = Position::from_sfen(sfen)
+            .unwrap()
+            .legal_moves()
+            .iter()
+            .map(|m| m.to_string())
+            .collect::<Vec<_>>();
+        moves.sort();
+        moves
+    }
+
+    #[test]
+    fn key() {
+        let play = |moves: &str| {
+            Position::from_sfen(&format!("{STARTPOS_SFEN} moves {moves}"))
+                .unwrap()
+                .key()
+        };
+
+        assert_eq!(play("7g7f 3c3d 2g2f"), play("2g2f 3c3d 7g7f"));
+        assert_ne!(play("7g7f 3c3d 2g2f"), play("7g7f 3c3d"));
+        assert_ne!(
+            Position::startpos().key(),
+            play("2h3h 8b7b 3h2h 7b8b 2h3h 8b7b 3h2h")
+        );
+        assert_eq!(Position::startpos().key(), play("2h3h 8b7b 3h2h 7b8b"));
+
+        // Pieces in hand are distinguished by their owners.
+        let black = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b P 1").unwrap();
+        let white = Position::from_sfen("4k4/9/9/9/9/9/9/9/4K4 b p 1").unwrap();
+        assert_ne!(black.key(), white.key());
+    }
+
+    #[test]
+    fn sfen() {
+        let ok_cases = [
+            STARTPOS_SFEN,
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL w - 1",
+            "8l/1l+R2P3/p2pBG1pp/kps1p4/Nn1P2G2/P1P1P2PP/1PS6/1KSG3+r1/LN2+p3L w Sbgn3p 124",
+            "4k4/9/9/9/9/9/9/9/4K4 b 2R2B4G4S4N4L18P 1",
+        ];
+        let ng_cases = [
+            "",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1 b - 1",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNLL b - 1",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSN b - 1",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSN+ b - 1",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL x - 1",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b K 1",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b 2 1",
+            "4k4/9/9/9/9/9/9/9/4K4 b 19P 1",
+            "4k4/9/9/9/9/9/9/9/4K4 b 300P 1",
+            "4k4/9/9/9/9/9/9/9/4K4 b 3R 1",
+            "4k4/9/9/9/9/9/9/9/4K4 b 2RR 1",
+            "4k4/9/9/9/9/9/9/9/4K4 b 9P9P9P 1",
+            "4k4/9/9/9/9/9/9/9/4K4 b 99999999999P 1",
+            "lnsgkgsnl/1r5b1/ppppppppp/9/9/9/PPPPPPPPP/1B5R1/LNSGKGSNL b - 1 moves 7g7e",
+        ];
+
+        for (i, c) in ok_cases.iter().enumerate() {
+            assert_eq!(
+                *c,
+                Position::from_sfen(c).unwrap().to_sfen(),
+                "failed at #{i}"
+            );
+        }
+        for (i, c) in ng_cases.iter().enumerate() {
+            assert!(Position::from_sfen(c).is_err(), "failed at #{i}");
+        }
+
+        let pos = Position::from_sfen(&format!("{STARTPOS_SFEN} moves 7g7f 3c3d 8h2b+")).unwrap();
+        assert_eq!(
+            "lnsgkgsnl/1r5+B1/pppppp1pp/6p2/9/2P6/PP1PPPPPP/7R1/LNSGKGSNL w B 4",
+            pos.to_sfen()
+        );
+    }
+
+    #[test]
+    fn legal_moves() {
+        assert_eq!(30, Position::startpos().legal_moves().len());
+
+        // Both promotion and non-promotion are generated in the promotion zone,
+        // but a pawn on the last rank must promote.
+        assert_eq!(
+            vec!["5b5a+", "5i4h", "5i4i", "5i5h", "5i6h", "5i6i"],
+            legal_move_strings("k8/4P4/9/9/9/9/9/9/4K4 b - 1")
+        );
+        assert_eq!(
+            vec!["1c1b", "1c1b+", "5i4h", "5i4i", "5i5h", "5i6h", "5i6i"],
+            legal_move_strings("k8/9/8P/9/9/9/9/9/4K4 b - 1")
+        );
+
+        // Pinned pieces cannot move away.
+        assert!(!legal_move_strings("4r4/9/9/9/9/9/9/4G4/4K4 b - 1").contains(&"5h4h".to_string()));
+
+        // Nifu and drops on the last rank are illegal.
+        let moves = legal_move_strings("k8/9/9/9/9/9/4P4/9/K8 b P 1");
+        assert!(!moves.iter().any(|m| m.starts_with("P*5")));
+        assert!(!moves
+            .iter()
+            .any(|m| m.starts_with("P*") && m.ends_with('a')));
+        assert!(moves.contains(&"P*4e".to_string()));
+    }
+
+    #[test]
+    fn checkmate() {
+        // Checkmate by a gold drop.
+        let mut pos = Position::from_sfen("4k4/9/4P4/9/9/9/9/9/4K4 b G 1").unwrap();
+        pos.make_move(&"G*5b".parse().unwrap()).unwrap();
+        assert!(pos.in_check(Color::White));
+        assert!(pos.is_checkmate());
+
+        // Checkmate by a pawn drop (uchifuzume) is illegal, while a pawn push is not.
+        let pos = Position::from_sfen("kl7/9/1S7/9/9/9/9/9/8K b P 1").unwrap();
+        assert!(!pos.is_legal(&"P*9b".parse().unwrap()));
+        let pos = Position::from_sfen("kl7/9/PS7/9/9/9/9/9/8K b - 1").unwrap();
+        assert!(pos.is_legal(&"9c9b".parse().unwrap()));
+        assert!(pos.gives_check(&"9c9b".parse().unwrap()));
+
+        // A pawn drop check which can be escaped is legal.
+        let pos = Position::from_sfen("k8/9/9/9/9/9/9/9/8K b P 1").unwrap();
+        assert!(pos.is_legal(&"P*9b".parse().unwrap()));
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+
+use super::{Color, Position};
+
+/// Represents how a repetition (sennichite) ends the game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Repetition {
+    /// The same position appeared four times.
+    Draw,
+    /// The same position appeared four times while the given color checked on every move.
+    /// The player of that color loses.
+    PerpetualCheck(Color),
+}
+
+/// `RepetitionTracker` detects fourfold repetitions in a game.
+///
+/// # Examples
+///
+/// ```
+/// use usi::{Move, Position, Repetition, RepetitionTracker};
+///
+/// let mut pos = Position::startpos();
+/// let mut tracker = RepetitionTracker::new(&pos);
+/// let moves = ["2h3h", "8b7b", "3h2h", "7b8b"];
+///
+/// let mut result = None;
+/// for m in moves.iter().cycle().take(12) {
+///     pos.make_move(&m.parse::<Move>().unwrap()).unwrap();
+///     result = tracker.push(&pos);
+/// }
+/// assert_eq!(Some(Repetition::Draw), result);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RepetitionTracker {
+    /// Keys of positions, the side to move and whether it is in check, from the start.
+    history: Vec<(u64, Color, bool)>,
+    counts: HashMap<u64, usize>,
+}
+
+impl RepetitionTracker {
+    /// Creates a tracker starting from the given position.
+    pub fn new(pos: &Position) -> RepetitionTracker {
+        let mut tracker = RepetitionTracker::default();
+        tracker.push(pos);
+        tracker
+    }
+
+    /// Records the position after a move, and returns the repetition if the game ends.
+    pub fn push(&mut self, pos: &Position) -> Option<Repetition> {
+        let key = pos.key();
+        let side = pos.side_to_move();
+        self.history.push((key, side, pos.in_check(side)));
+
+        let count = self.counts.entry(key).or_insert(0);
+        *count += 1;
+        if *count < 4 {
+            return None;
+        }
+
+        let first = self.history.iter().position(|(k, _, _)| *k == key)?;
+        let cycle = &self.history[first + 1..];
+        for color in [Color::Black, Color::White] {
+            // Positions where the opponent of `color` is to move follow moves by `color`.
+            let perpetual = cycle
+                .iter()
+                .filter(|(_, side, _)| *side == color.flip())
+                .all(|(_, _, checked)| *checked);
+            if perpetual {
+                return Some(Repetition::PerpetualCheck(color));
+            }
+        }
+        Some(Repetition::Draw)
+    }
+
+    /// Returns how many times the given position has appeared.
+    pub fn count(&self, pos: &Position) -> usize {
+        self.counts.get(&pos.key()).copied().unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shogi::Move;
+
+    fn play(sfen: &str, moves: &[&str], n: usize) -> Vec<Option<Repetition>> {
+        let mut pos = Position::from_sfen(sfen).unwrap();
+        let mut tracker = RepetitionTracker::new(&pos);
+        moves
+            .iter()
+            .cycle()
+            .take(n)
+            .map(|m| {
+                pos.make_move(&m.parse::<Move>().unwrap()).unwrap();
+                tracker.push(&pos)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn perpetual_check() {
+        let sfen = "8k/9/9/9/7R1/9/9/9/K8 b - 1";
+        let results = play(sfen, &["2e1e", "1a2a", "1e2e", "2a1a"], 12);
+        assert!(results[..11].iter().all(Option::is_none));
+        assert_eq!(Some(Repetition::PerpetualCheck(Color::Black)), results[11]);
+
+        // The repetition is counted from the position after the first check.
+        let results = play(sfen, &["2e1e", "1a2a", "1e2e", "2a1a"], 13);
+        assert_eq!(Some(Repetition::PerpetualCheck(Color::Black)), results[12]);
+    }
+}
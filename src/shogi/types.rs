@@ -0,0 +1,320 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::Error;
+
+/// Represents a side of the game. Black (sente) moves first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Color {
+    Black,
+    White,
+}
+
+impl Color {
+    /// Returns the opponent's color.
+    pub fn flip(self) -> Color {
+        match self {
+            Color::Black => Color::White,
+            Color::White => Color::Black,
+        }
+    }
+
+    pub(crate) fn index(self) -> usize {
+        match self {
+            Color::Black => 0,
+            Color::White => 1,
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Color::Black => write!(f, "b"),
+            Color::White => write!(f, "w"),
+        }
+    }
+}
+
+/// Represents a kind of pieces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PieceType {
+    King,
+    Rook,
+    Bishop,
+    Gold,
+    Silver,
+    Knight,
+    Lance,
+    Pawn,
+    Dragon,
+    Horse,
+    ProSilver,
+    ProKnight,
+    ProLance,
+    ProPawn,
+}
+
+impl PieceType {
+    /// Piece types which can be held in hand, in the order used by SFEN.
+    pub const HAND_TYPES: [PieceType; 7] = [
+        PieceType::Rook,
+        PieceType::Bishop,
+        PieceType::Gold,
+        PieceType::Silver,
+        PieceType::Knight,
+        PieceType::Lance,
+        PieceType::Pawn,
+    ];
+
+    /// Returns the promoted piece type, or `None` if this piece cannot be promoted.
+    pub fn promote(self) -> Option<PieceType> {
+        match self {
+            PieceType::Rook => Some(PieceType::Dragon),
+            PieceType::Bishop => Some(PieceType::Horse),
+            PieceType::Silver => Some(PieceType::ProSilver),
+            PieceType::Knight => Some(PieceType::ProKnight),
+            PieceType::Lance => Some(PieceType::ProLance),
+            PieceType::Pawn => Some(PieceType::ProPawn),
+            _ => None,
+        }
+    }
+
+    /// Returns the original piece type of a promoted piece, or itself otherwise.
+    pub fn unpromote(self) -> PieceType {
+        match self {
+            PieceType::Dragon => PieceType::Rook,
+            PieceType::Horse => PieceType::Bishop,
+            PieceType::ProSilver => PieceType::Silver,
+            PieceType::ProKnight => PieceType::Knight,
+            PieceType::ProLance => PieceType::Lance,
+            PieceType::ProPawn => PieceType::Pawn,
+            pt => pt,
+        }
+    }
+
+    /// Returns true if this piece type is a promoted one.
+    pub fn is_promoted(self) -> bool {
+        self.unpromote() != self
+    }
+
+    pub(crate) fn hand_index(self) -> Option<usize> {
+        PieceType::HAND_TYPES.iter().position(|pt| *pt == self)
+    }
+
+    /// Returns the number of pieces of this type in a game, counting promoted ones as well.
+    pub(crate) fn max_count(self) -> u8 {
+        match self.unpromote() {
+            PieceType::King | PieceType::Rook | PieceType::Bishop => 2,
+            PieceType::Pawn => 18,
+            _ => 4,
+        }
+    }
+
+    fn from_sfen_char(c: char) -> Option<PieceType> {
+        Some(match c.to_ascii_uppercase() {
+            'K' => PieceType::King,
+            'R' => PieceType::Rook,
+            'B' => PieceType::Bishop,
+            'G' => PieceType::Gold,
+            'S' => PieceType::Silver,
+            'N' => PieceType::Knight,
+            'L' => PieceType::Lance,
+            'P' => PieceType::Pawn,
+            _ => return None,
+        })
+    }
+
+    fn sfen_char(self) -> char {
+        match self.unpromote() {
+            PieceType::King => 'K',
+            PieceType::Rook => 'R',
+            PieceType::Bishop => 'B',
+            PieceType::Gold => 'G',
+            PieceType::Silver => 'S',
+            PieceType::Knight => 'N',
+            PieceType::Lance => 'L',
+            _ => 'P',
+        }
+    }
+}
+
+impl fmt::Display for PieceType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_promoted() {
+            write!(f, "+")?;
+        }
+        write!(f, "{}", self.sfen_char())
+    }
+}
+
+impl FromStr for PieceType {
+    type Err = Error;
+
+    /// Parses a piece type in SFEN notation, such as `P` or `+R`.
+    fn from_str(s: &str) -> Result<PieceType, Error> {
+        let err = || Error::IllegalSfen(s.to_string());
+        let (promoted, c) = match s.as_bytes() {
+            [b'+', c] => (true, *c as char),
+            [c] => (false, *c as char),
+            _ => return Err(err()),
+        };
+
+        let pt = PieceType::from_sfen_char(c).ok_or_else(err)?;
+        if promoted {
+            pt.promote().ok_or_else(err)
+        } else {
+            Ok(pt)
+        }
+    }
+}
+
+/// Represents a piece on the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Piece {
+    pub piece_type: PieceType,
+    pub color: Color,
+}
+
+impl Piece {
+    pub fn new(piece_type: PieceType, color: Color) -> Piece {
+        Piece { piece_type, color }
+    }
+}
+
+impl fmt::Display for Piece {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.piece_type.is_promoted() {
+            write!(f, "+")?;
+        }
+        let c = self.piece_type.sfen_char();
+        match self.color {
+            Color::Black => write!(f, "{c}"),
+            Color::White => write!(f, "{}", c.to_ascii_lowercase()),
+        }
+    }
+}
+
+impl FromStr for Piece {
+    type Err = Error;
+
+    /// Parses a piece in SFEN notation, where lowercase letters represent white pieces.
+    fn from_str(s: &str) -> Result<Piece, Error> {
+        let color = match s.chars().last() {
+            Some(c) if c.is_ascii_uppercase() => Color::Black,
+            Some(c) if c.is_ascii_lowercase() => Color::White,
+            _ => return Err(Error::IllegalSfen(s.to_string())),
+        };
+        Ok(Piece::new(s.parse()?, color))
+    }
+}
+
+/// Represents a square on the board.
+///
+/// Files are numbered from 1 to 9 from the right, and ranks from 1 (`a`) to 9 (`i`) from the top,
+/// both seen from the black side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Square(u8);
+
+impl Square {
+    /// Creates a square from 1-based file and rank numbers.
+    pub fn new(file: u8, rank: u8) -> Option<Square> {
+        if (1..=9).contains(&file) && (1..=9).contains(&rank) {
+            Some(Square((file - 1) * 9 + rank - 1))
+        } else {
+            None
+        }
+    }
+
+    pub fn file(self) -> u8 {
+        self.0 / 9 + 1
+    }
+
+    pub fn rank(self) -> u8 {
+        self.0 % 9 + 1
+    }
+
+    /// Returns the rank counted from the far side of the given color, i.e. 1 is the last rank.
+    pub fn relative_rank(self, c: Color) -> u8 {
+        match c {
+            Color::Black => self.rank(),
+            Color::White => 10 - self.rank(),
+        }
+    }
+
+    /// Returns true if this square is in the promotion zone of the given color.
+    pub fn in_promotion_zone(self, c: Color) -> bool {
+        self.relative_rank(c) <= 3
+    }
+
+    /// Iterates over all 81 squares.
+    pub fn iter() -> impl Iterator<Item = Square> {
+        (0..81).map(Square)
+    }
+
+    pub(crate) fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub(crate) fn shift(self, df: i8, dr: i8) -> Option<Square> {
+        let file = self.file() as i8 + df;
+        let rank = self.rank() as i8 + dr;
+        if (1..=9).contains(&file) && (1..=9).contains(&rank) {
+            Square::new(file as u8, rank as u8)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.file(), (b'a' + self.rank() - 1) as char)
+    }
+}
+
+impl FromStr for Square {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Square, Error> {
+        match s.as_bytes() {
+            [f @ b'1'..=b'9', r @ b'a'..=b'i'] => {
+                Square::new(f - b'0', r - b'a' + 1).ok_or_else(|| Error::IllegalMove(s.to_string()))
+            }
+            _ => Err(Error::IllegalMove(s.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square() {
+        for sq in Square::iter() {
+            assert_eq!(sq, sq.to_string().parse().unwrap());
+        }
+
+        let sq: Square = "7g".parse().unwrap();
+        assert_eq!((7, 7), (sq.file(), sq.rank()));
+        assert_eq!(3, sq.relative_rank(Color::White));
+        assert!(sq.in_promotion_zone(Color::White));
+        assert!(!sq.in_promotion_zone(Color::Black));
+
+        for s in ["", "0a", "1j", "a1", "7g7f"] {
+            assert!(s.parse::<Square>().is_err(), "{s}");
+        }
+    }
+
+    #[test]
+    fn piece() {
+        for s in ["K", "+P", "+r", "b", "l"] {
+            assert_eq!(s, s.parse::<Piece>().unwrap().to_string());
+        }
+
+        for s in ["", "+K", "+G", "x", "++P"] {
+            assert!(s.parse::<Piece>().is_err(), "{s}");
+        }
+    }
+}
@@ -0,0 +1,50 @@
+/// Random keys to hash positions, generated at compile time.
+pub(crate) struct ZobristKeys {
+    /// Indexed by color, piece type and square.
+    pub board: [[[u64; 81]; 14]; 2],
+    /// Indexed by color and hand index, added once per piece in hand.
+    pub hand: [[u64; 7]; 2],
+    /// Toggled when white is to move.
+    pub side: u64,
+}
+
+pub(crate) static KEYS: ZobristKeys = generate();
+
+/// Advances the state of SplitMix64 and returns the next value.
+const fn next(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+const fn generate() -> ZobristKeys {
+    let mut state = 0x5553_495f_7273_u64;
+    let mut keys = ZobristKeys {
+        board: [[[0; 81]; 14]; 2],
+        hand: [[0; 7]; 2],
+        side: 0,
+    };
+
+    let mut c = 0;
+    while c < 2 {
+        let mut pt = 0;
+        while pt < 14 {
+            let mut sq = 0;
+            while sq < 81 {
+                keys.board[c][pt][sq] = next(&mut state);
+                sq += 1;
+            }
+            pt += 1;
+        }
+        let mut i = 0;
+        while i < 7 {
+            keys.hand[c][i] = next(&mut state);
+            i += 1;
+        }
+        c += 1;
+    }
+    keys.side = next(&mut state);
+    keys
+}
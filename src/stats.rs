@@ -0,0 +1,308 @@
+use std::fmt;
+
+use crate::game::Score;
+
+/// z-score of the two-sided 95% confidence interval.
+const Z_95: f64 = 1.959_963_984_540_054;
+
+/// Represents results which can be treated as samples of a score between 0 and 1.
+pub trait Outcomes {
+    /// Returns pairs of a sample score and the number of its occurrences.
+    fn distribution(&self) -> Vec<(f64, u32)>;
+}
+
+impl Outcomes for Score {
+    fn distribution(&self) -> Vec<(f64, u32)> {
+        vec![(0.0, self.losses), (0.5, self.draws), (1.0, self.wins)]
+    }
+}
+
+/// Represents results of game pairs played from the same opening with colors swapped.
+///
+/// Counting pairs instead of games cancels out the bias of openings,
+/// which makes confidence intervals narrower.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Pentanomial {
+    /// Numbers of pairs which scored 0, 0.5, 1, 1.5 and 2 points.
+    pub counts: [u32; 5],
+}
+
+impl Pentanomial {
+    pub fn new() -> Pentanomial {
+        Pentanomial::default()
+    }
+
+    /// Records a pair of games with points (0, 0.5 or 1) of the tested engine in each game.
+    pub fn add_pair(&mut self, first: f64, second: f64) {
+        let i = ((first + second) * 2.0).round().clamp(0.0, 4.0) as usize;
+        self.counts[i] += 1;
+    }
+
+    /// Returns the number of pairs.
+    pub fn pairs(&self) -> u32 {
+        self.counts.iter().sum()
+    }
+}
+
+impl Outcomes for Pentanomial {
+    fn distribution(&self) -> Vec<(f64, u32)> {
+        self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i as f64 / 4.0, *c))
+            .collect()
+    }
+}
+
+impl fmt::Display for Pentanomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let c = &self.counts;
+        write!(f, "[{}, {}, {}, {}, {}]", c[0], c[1], c[2], c[3], c[4])
+    }
+}
+
+/// Returns the Elo difference expected from the score rate.
+pub fn elo_from_score(score: f64) -> f64 {
+    -400.0 * (1.0 / score.clamp(0.0, 1.0) - 1.0).log10()
+}
+
+/// Returns the score rate expected from the Elo difference.
+pub fn score_from_elo(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Returns the number of samples, the mean and the variance.
+fn moments<O: Outcomes + ?Sized>(outcomes: &O) -> Option<(f64, f64, f64)> {
+    let dist = outcomes.distribution();
+    let n = dist.iter().map(|(_, c)| *c as f64).sum::<f64>();
+    if n == 0.0 {
+        return None;
+    }
+
+    let mean = dist.iter().map(|(s, c)| s * *c as f64).sum::<f64>() / n;
+    let var = dist
+        .iter()
+        .map(|(s, c)| (s - mean).powi(2) * *c as f64)
+        .sum::<f64>()
+        / n;
+    Some((n, mean, var))
+}
+
+/// Returns the cumulative distribution function of the standard normal distribution.
+fn normal_cdf(x: f64) -> f64 {
+    0.5 * (1.0 + erf(x / std::f64::consts::SQRT_2))
+}
+
+/// Approximates the error function with a maximum error of 1.2e-7 (Numerical Recipes `erfcc`).
+fn erf(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.265_512_23
+        + t * (1.000_023_68
+            + t * (0.374_091_96
+                + t * (0.096_784_18
+                    + t * (-0.186_288_06
+                        + t * (0.278_868_07
+                            + t * (-1.135_203_98
+                                + t * (1.488_515_87 + t * (-0.822_152_23 + t * 0.170_872_77))))))));
+    let r = 1.0 - t * poly.exp();
+    if x >= 0.0 {
+        r
+    } else {
+        -r
+    }
+}
+
+/// Represents an Elo difference estimated from game results.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EloEstimate {
+    /// Estimated Elo difference.
+    pub elo: f64,
+    /// Lower bound of the 95% confidence interval.
+    pub lower: f64,
+    /// Upper bound of the 95% confidence interval.
+    pub upper: f64,
+    /// Likelihood of superiority, the probability that the Elo difference is positive.
+    pub los: f64,
+}
+
+impl EloEstimate {
+    /// Estimates the Elo difference, or returns `None` if there are no results.
+    pub fn new<O: Outcomes + ?Sized>(outcomes: &O) -> Option<EloEstimate> {
+        let (n, mean, var) = moments(outcomes)?;
+        let stderr = (var / n).sqrt();
+
+        let los = if stderr > 0.0 {
+            normal_cdf((mean - 0.5) / stderr)
+        } else if mean > 0.5 {
+            1.0
+        } else if mean < 0.5 {
+            0.0
+        } else {
+            0.5
+        };
+
+        Some(EloEstimate {
+            elo: elo_from_score(mean),
+            lower: elo_from_score(mean - Z_95 * stderr),
+            upper: elo_from_score(mean + Z_95 * stderr),
+            los,
+        })
+    }
+}
+
+impl fmt::Display for EloEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} [{:.1}, {:.1}] LOS {:.1}%",
+            self.elo,
+            self.lower,
+            self.upper,
+            self.los * 100.0
+        )
+    }
+}
+
+/// Represents the state of a sequential probability ratio test.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SprtResult {
+    /// The Elo difference is likely to be `elo0` or less.
+    AcceptH0,
+    /// The Elo difference is likely to be `elo1` or more.
+    AcceptH1,
+    /// More games are required.
+    Continue,
+}
+
+/// `Sprt` tests whether the Elo difference is `elo0` (H0) or `elo1` (H1)
+/// with the normal approximation of the generalized SPRT.
+///
+/// # Examples
+/// ```
+/// use usi::{Score, Sprt, SprtResult};
+///
+/// let sprt = Sprt::new(0.0, 5.0, 0.05, 0.05);
+/// let score = Score { wins: 6000, draws: 2000, losses: 5000 };
+/// assert_eq!(SprtResult::AcceptH1, sprt.test(&score));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprt {
+    elo0: f64,
+    elo1: f64,
+    alpha: f64,
+    beta: f64,
+}
+
+impl Sprt {
+    /// Creates a test of H0: `elo0` against H1: `elo1`, where `alpha` and `beta` are
+    /// the probabilities of type I and type II errors.
+    pub fn new(elo0: f64, elo1: f64, alpha: f64, beta: f64) -> Sprt {
+        Sprt {
+            elo0,
+            elo1,
+            alpha,
+            beta,
+        }
+    }
+
+    /// Returns the lower and upper bounds of the log-likelihood ratio.
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// Returns the log-likelihood ratio of H1 to H0.
+    /// Returns 0 if the results have no variance yet.
+    pub fn llr<O: Outcomes + ?Sized>(&self, outcomes: &O) -> f64 {
+        let (n, mean, var) = match moments(outcomes) {
+            Some(v) if v.2 > 0.0 => v,
+            _ => return 0.0,
+        };
+
+        let s0 = score_from_elo(self.elo0);
+        let s1 = score_from_elo(self.elo1);
+        n * (s1 - s0) * (2.0 * mean - s0 - s1) / (2.0 * var)
+    }
+
+    /// Decides whether the test is finished.
+    pub fn test<O: Outcomes + ?Sized>(&self, outcomes: &O) -> SprtResult {
+        let llr = self.llr(outcomes);
+        let (lower, upper) = self.bounds();
+        if llr >= upper {
+            SprtResult::AcceptH1
+        } else if llr <= lower {
+            SprtResult::AcceptH0
+        } else {
+            SprtResult::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(expected: f64, actual: f64) {
+        assert!((expected - actual).abs() < 1e-3, "{expected} != {actual}");
+    }
+
+    #[test]
+    fn elo() {
+        assert_near(0.0, elo_from_score(0.5));
+        assert_near(70.437, elo_from_score(0.6));
+        assert_near(0.6, score_from_elo(70.437));
+        assert_near(0.975, normal_cdf(Z_95));
+
+        assert_eq!(None, EloEstimate::new(&Score::default()));
+
+        let e = EloEstimate::new(&Score {
+            wins: 60,
+            draws: 0,
+            losses: 40,
+        })
+        .unwrap();
+        assert_near(70.437, e.elo);
+        assert!(e.lower > 0.0 && e.lower < 5.0);
+        assert!(e.upper > 140.0 && e.upper < 150.0);
+        assert!(e.los > 0.97 && e.los < 0.98);
+
+        // Pairs of a win and a loss have no variance of opening bias.
+        let mut p = Pentanomial::new();
+        for _ in 0..40 {
+            p.add_pair(1.0, 0.0);
+        }
+        for _ in 0..10 {
+            p.add_pair(1.0, 1.0);
+        }
+        assert_eq!([0, 0, 40, 0, 10], p.counts);
+        let e = EloEstimate::new(&p).unwrap();
+        assert_near(70.437, e.elo);
+        assert!(e.lower > 0.0);
+    }
+
+    #[test]
+    fn sprt() {
+        let sprt = Sprt::new(0.0, 10.0, 0.05, 0.05);
+        let (lower, upper) = sprt.bounds();
+        assert_near(-2.944, lower);
+        assert_near(2.944, upper);
+
+        let cases = [
+            (1000, 1000, 1000, SprtResult::Continue),
+            (4000, 2000, 3500, SprtResult::AcceptH1),
+            (3500, 2000, 4000, SprtResult::AcceptH0),
+            (30, 0, 0, SprtResult::Continue),
+        ];
+        for (wins, draws, losses, expected) in cases {
+            let score = Score {
+                wins,
+                draws,
+                losses,
+            };
+            assert_eq!(expected, sprt.test(&score), "{score}");
+        }
+    }
+}